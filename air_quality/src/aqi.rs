@@ -0,0 +1,129 @@
+//! US EPA Air Quality Index from PM2.5 and PM10 concentrations, using the 2024 breakpoints.
+//!
+//! The sensor reports whole µg/m³, so the PM2.5 table is kept in tenths to match the EPA's one
+//! decimal place and truncation rules.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Category {
+    Good,
+    Moderate,
+    UnhealthyForSensitiveGroups,
+    Unhealthy,
+    VeryUnhealthy,
+    Hazardous,
+}
+
+impl Category {
+    fn from_index(index: u16) -> Self {
+        match index {
+            0..=50 => Category::Good,
+            51..=100 => Category::Moderate,
+            101..=150 => Category::UnhealthyForSensitiveGroups,
+            151..=200 => Category::Unhealthy,
+            201..=300 => Category::VeryUnhealthy,
+            _ => Category::Hazardous,
+        }
+    }
+
+    /// Short label that fits a status line
+    pub fn label(&self) -> &'static str {
+        match self {
+            Category::Good => "Good",
+            Category::Moderate => "Moderate",
+            Category::UnhealthyForSensitiveGroups => "USG",
+            Category::Unhealthy => "Unhealthy",
+            Category::VeryUnhealthy => "Very Unhealthy",
+            Category::Hazardous => "Hazardous",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aqi {
+    pub value: u16,
+    pub category: Category,
+}
+
+impl Aqi {
+    fn new(value: u16) -> Self {
+        Self { value, category: Category::from_index(value) }
+    }
+}
+
+// (concentration low, concentration high, index low, index high), concentrations in tenths of µg/m³
+type Breakpoint = (u32, u32, u32, u32);
+
+const PM2_5_BREAKPOINTS: [Breakpoint; 6] = [
+    (0, 90, 0, 50),
+    (91, 354, 51, 100),
+    (355, 554, 101, 150),
+    (555, 1254, 151, 200),
+    (1255, 2254, 201, 300),
+    (2255, 3254, 301, 500),
+];
+
+const PM10_BREAKPOINTS: [Breakpoint; 6] = [
+    (0, 540, 0, 50),
+    (550, 1540, 51, 100),
+    (1550, 2540, 101, 150),
+    (2550, 3540, 151, 200),
+    (3550, 4240, 201, 300),
+    (4250, 6040, 301, 500),
+];
+
+// the index tops out at 500; anything beyond the last breakpoint is reported as 500
+const MAX_INDEX: u32 = 500;
+
+fn index(breakpoints: &[Breakpoint], tenths: u32) -> u16 {
+    let Some(&(c_lo, c_hi, i_lo, i_hi)) = breakpoints.iter().find(|(_, c_hi, _, _)| tenths <= *c_hi) else {
+        return MAX_INDEX as u16;
+    };
+    // linear interpolation, rounded to the nearest integer
+    let span = c_hi - c_lo;
+    let value = i_lo + ((i_hi - i_lo) * tenths.saturating_sub(c_lo) + span / 2) / span;
+    value as u16
+}
+
+/// Sub-index for a PM2.5 concentration in µg/m³
+pub fn pm2_5(concentration: u16) -> Aqi {
+    Aqi::new(index(&PM2_5_BREAKPOINTS, u32::from(concentration) * 10))
+}
+
+/// Sub-index for a PM10 concentration in µg/m³
+pub fn pm10(concentration: u16) -> Aqi {
+    Aqi::new(index(&PM10_BREAKPOINTS, u32::from(concentration) * 10))
+}
+
+/// The overall AQI is the worse of the two particulate sub-indices
+pub fn aqi(pm2_5_concentration: u16, pm10_concentration: u16) -> Aqi {
+    let fine = pm2_5(pm2_5_concentration);
+    let coarse = pm10(pm10_concentration);
+    if fine.value >= coarse.value { fine } else { coarse }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pm2_5_breakpoints() {
+        assert_eq!(pm2_5(0), Aqi { value: 0, category: Category::Good });
+        assert_eq!(pm2_5(9), Aqi { value: 50, category: Category::Good });
+        assert_eq!(pm2_5(35), Aqi { value: 99, category: Category::Moderate });
+        assert_eq!(pm2_5(56).category, Category::Unhealthy);
+        assert_eq!(pm2_5(1_000), Aqi { value: 500, category: Category::Hazardous });
+    }
+
+    #[test]
+    fn pm10_breakpoints() {
+        assert_eq!(pm10(54), Aqi { value: 50, category: Category::Good });
+        assert_eq!(pm10(100), Aqi { value: 73, category: Category::Moderate });
+        assert_eq!(pm10(604), Aqi { value: 500, category: Category::Hazardous });
+    }
+
+    #[test]
+    fn aqi_is_worst_sub_index() {
+        assert_eq!(aqi(5, 200).value, pm10(200).value);
+        assert_eq!(aqi(40, 20).value, pm2_5(40).value);
+    }
+}
@@ -1,13 +1,19 @@
 #![no_std]
 
+pub mod aqi;
+
+use embedded_hal::digital::OutputPin;
 use embedded_hal_async::i2c::I2c;
 use pmsa003i::{Error, Pmsa003i};
+use suspend::Suspend;
 
 #[derive(Debug, PartialEq)]
 pub enum AirQualityError<E> {
     I2C(E),
     InvalidChecksum,
-    InvalidMagic
+    InvalidMagic,
+    /// Driving the SET pin failed
+    SetPin,
 }
 
 impl<E> From<Error<E>> for AirQualityError<E> {
@@ -22,29 +28,57 @@ impl<E> From<Error<E>> for AirQualityError<E> {
 
 #[derive(Debug)]
 pub struct AirQualityReading {
+    pub pm1_0: u16,
     pub pm2_5: u16,
     pub pm10: u16,
 }
 
 impl AirQualityReading {
-    fn new(pm2_5: u16, pm10: u16) -> Self {
-        Self { pm2_5, pm10 }
+    fn new(pm1_0: u16, pm2_5: u16, pm10: u16) -> Self {
+        Self { pm1_0, pm2_5, pm10 }
     }
 }
 
-pub struct AQSensor<I2C> {
+/// A PMSA003I on I2C. With its SET pin wired, the sensor can be suspended.
+pub struct AQSensor<I2C, SET = ()> {
     i2c: I2C,
+    set: SET,
 }
 
 impl<I2C: I2c> AQSensor<I2C> {
     pub fn new(i2c: I2C) -> Self {
-        Self { i2c }
+        Self { i2c, set: () }
+    }
+}
+
+impl<I2C: I2c, SET: OutputPin> AQSensor<I2C, SET> {
+    /// `set` drives the sensor's SET pin, which starts out high with the fan running
+    pub fn with_set_pin(i2c: I2C, set: SET) -> Self {
+        Self { i2c, set }
     }
+}
 
+impl<I2C: I2c, SET> AQSensor<I2C, SET> {
     pub async fn read(&mut self) -> Result<AirQualityReading, AirQualityError<I2C::Error>> {
         let mut sensor = Pmsa003i::new(&mut self.i2c);
         let data = sensor.read().await.map_err(AirQualityError::from)?;
-        Ok(AirQualityReading::new(data.pm2_5, data.pm10))
+        Ok(AirQualityReading::new(data.pm1, data.pm2_5, data.pm10))
+    }
+}
+
+/// Pulling SET low stops the fan and laser, most of the sensor's ~100mA
+impl<I2C: I2c, SET: OutputPin> Suspend for AQSensor<I2C, SET> {
+    type Error = AirQualityError<I2C::Error>;
+
+    // the datasheet's time to stable readings once the fan restarts
+    const WARM_UP_MS: u32 = 30_000;
+
+    async fn suspend(&mut self) -> Result<(), Self::Error> {
+        self.set.set_low().map_err(|_| AirQualityError::SetPin)
+    }
+
+    async fn resume(&mut self) -> Result<(), Self::Error> {
+        self.set.set_high().map_err(|_| AirQualityError::SetPin)
     }
 }
 
@@ -52,6 +86,7 @@ impl<I2C: I2c> AQSensor<I2C> {
 mod tests {
     use crate::*;
     use embedded_hal_async::i2c::ErrorKind;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction as PinTransaction};
     use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
 
     const ADDR: u8 = 0x12;
@@ -109,6 +144,17 @@ mod tests {
         i2c.done();
     }
 
+    #[tokio::test]
+    async fn suspend_drives_set() {
+        let mut i2c = I2cMock::new(&[]);
+        let mut set = PinMock::new(&[PinTransaction::set(State::Low), PinTransaction::set(State::High)]);
+        let mut sensor = AQSensor::with_set_pin(&mut i2c, &mut set);
+        sensor.suspend().await.unwrap();
+        sensor.resume().await.unwrap();
+        i2c.done();
+        set.done();
+    }
+
     fn get_valid_response() -> [u8; RESPONSE_LEN] {
         let mut res = [0x00; RESPONSE_LEN];
         // valid start of frame
@@ -0,0 +1,216 @@
+#![no_std]
+
+//! Local alerts on the node's readings, for a paint booth's particulates or a server closet's
+//! temperature.
+//!
+//! Each [`AlertMetric`] has a [`Rule`] with optional low and high limits. The [`Engine`] is fed
+//! every reading and reports an [`Alert`] when a limit has been crossed for the rule's minimum
+//! duration, and again when the reading comes back inside it by the hysteresis. Once cleared, a
+//! rule stays quiet for its cooldown, so a reading hovering at a limit doesn't raise an alert on
+//! every sample.
+
+use heapless::Vec;
+use telemetry::{Alert, AlertMetric, AlertState, EnvReading};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rule {
+    /// `None` disables a limit; a rule with neither never raises
+    pub low: Option<u16>,
+    pub high: Option<u16>,
+    /// How far back inside a limit the reading must come before its alert clears
+    pub hysteresis: u16,
+    /// How long a limit must stay crossed before the alert is raised; a sample on the other side
+    /// starts it over
+    pub min_duration_secs: u16,
+    /// How long after clearing before the rule can raise again
+    pub cooldown_secs: u16,
+}
+
+impl Rule {
+    fn crossed(&self, value: u16) -> Option<AlertState> {
+        if self.high.is_some_and(|high| value > high) {
+            Some(AlertState::High)
+        } else if self.low.is_some_and(|low| value < low) {
+            Some(AlertState::Low)
+        } else {
+            None
+        }
+    }
+
+    fn recovered(&self, state: AlertState, value: u16) -> bool {
+        match state {
+            AlertState::High => self.high.is_none_or(|high| value <= high.saturating_sub(self.hysteresis)),
+            AlertState::Low => self.low.is_none_or(|low| value >= low.saturating_add(self.hysteresis)),
+            AlertState::Cleared => true,
+        }
+    }
+
+    fn limit(&self, state: AlertState) -> u16 {
+        match state {
+            AlertState::Low => self.low,
+            AlertState::High | AlertState::Cleared => self.high,
+        }
+        .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rules {
+    pub pm2_5: Rule,
+    /// Degrees F
+    pub temperature: Rule,
+    /// %RH
+    pub humidity: Rule,
+}
+
+impl Rules {
+    pub fn rule(&self, metric: AlertMetric) -> &Rule {
+        match metric {
+            AlertMetric::Pm2_5 => &self.pm2_5,
+            AlertMetric::Temperature => &self.temperature,
+            AlertMetric::Humidity => &self.humidity,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Clear,
+    /// Crossed `AlertState`'s limit at `since`, waiting out the minimum duration
+    Pending(AlertState, u64),
+    Raised(AlertState),
+    /// Cleared, and quiet until the time given
+    CoolingDown(u64),
+}
+
+/// Alerts raised or cleared by one reading, at most one per metric
+pub type Changes = Vec<Alert, { AlertMetric::ALL.len() }>;
+
+#[derive(Debug)]
+pub struct Engine {
+    rules: Rules,
+    states: [State; AlertMetric::ALL.len()],
+}
+
+impl Engine {
+    pub fn new(rules: Rules) -> Self {
+        Self { rules, states: [State::Clear; AlertMetric::ALL.len()] }
+    }
+
+    /// Evaluates a reading taken `now_secs` after boot, returning the alerts it raised or cleared
+    pub fn update(&mut self, now_secs: u64, reading: &EnvReading) -> Changes {
+        let mut changes = Changes::new();
+        for (metric, state) in AlertMetric::ALL.into_iter().zip(self.states.iter_mut()) {
+            let rule = self.rules.rule(metric);
+            let value = metric.value(reading);
+            let (next, change) = match *state {
+                State::Raised(crossed) if rule.recovered(crossed, value) => {
+                    let next = match rule.cooldown_secs {
+                        0 => State::Clear,
+                        secs => State::CoolingDown(now_secs + u64::from(secs)),
+                    };
+                    (next, Some(AlertState::Cleared))
+                }
+                State::Raised(crossed) => (State::Raised(crossed), None),
+                State::CoolingDown(until) if now_secs < until => (State::CoolingDown(until), None),
+                State::Pending(crossed, since) if rule.crossed(value) == Some(crossed) => {
+                    if now_secs - since >= u64::from(rule.min_duration_secs) {
+                        (State::Raised(crossed), Some(crossed))
+                    } else {
+                        (State::Pending(crossed, since), None)
+                    }
+                }
+                State::Clear | State::Pending(..) | State::CoolingDown(_) => match rule.crossed(value) {
+                    Some(crossed) if rule.min_duration_secs == 0 => (State::Raised(crossed), Some(crossed)),
+                    Some(crossed) => (State::Pending(crossed, now_secs), None),
+                    None => (State::Clear, None),
+                },
+            };
+            if let Some(alert_state) = change {
+                // the limit cleared is the one that was crossed
+                let crossed = match *state {
+                    State::Raised(crossed) => crossed,
+                    _ => alert_state,
+                };
+                // can't overflow; there's one slot per metric
+                let _ = changes.push(Alert { metric, state: alert_state, limit: rule.limit(crossed) });
+            }
+            *state = next;
+        }
+        changes
+    }
+
+    /// Every alert raised and not yet cleared
+    pub fn active(&self) -> impl Iterator<Item = Alert> + '_ {
+        AlertMetric::ALL.into_iter().zip(self.states.iter()).filter_map(|(metric, state)| match *state {
+            State::Raised(crossed) => Some(Alert { metric, state: crossed, limit: self.rules.rule(metric).limit(crossed) }),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(pm2_5: u16, temperature: u16) -> EnvReading {
+        EnvReading { aq_pm2_5: pm2_5, aq_pm10: 0, humidity: 45, temperature, aq_pm1_0: 0 }
+    }
+
+    fn pm2_5(high: u16, hysteresis: u16, min_duration_secs: u16, cooldown_secs: u16) -> Engine {
+        let rule = Rule { low: None, high: Some(high), hysteresis, min_duration_secs, cooldown_secs };
+        Engine::new(Rules { pm2_5: rule, ..Default::default() })
+    }
+
+    fn high(limit: u16) -> Alert {
+        Alert { metric: AlertMetric::Pm2_5, state: AlertState::High, limit }
+    }
+
+    fn cleared(limit: u16) -> Alert {
+        Alert { metric: AlertMetric::Pm2_5, state: AlertState::Cleared, limit }
+    }
+
+    #[test]
+    fn raises_and_clears_past_the_hysteresis() {
+        let mut engine = pm2_5(35, 5, 0, 0);
+        assert_eq!(engine.update(0, &reading(35, 70)), []);
+        assert_eq!(engine.update(1, &reading(36, 70)), [high(35)]);
+        assert_eq!(engine.active().collect::<Changes>(), [high(35)]);
+        // back under the limit, but not by the hysteresis
+        assert_eq!(engine.update(2, &reading(31, 70)), []);
+        assert_eq!(engine.update(3, &reading(30, 70)), [cleared(35)]);
+        assert_eq!(engine.active().count(), 0);
+    }
+
+    #[test]
+    fn waits_out_the_minimum_duration() {
+        let mut engine = pm2_5(35, 0, 60, 0);
+        assert_eq!(engine.update(0, &reading(40, 70)), []);
+        // a dip starts it over
+        assert_eq!(engine.update(30, &reading(20, 70)), []);
+        assert_eq!(engine.update(40, &reading(40, 70)), []);
+        assert_eq!(engine.update(99, &reading(40, 70)), []);
+        assert_eq!(engine.update(100, &reading(40, 70)), [high(35)]);
+    }
+
+    #[test]
+    fn stays_quiet_through_the_cooldown() {
+        let mut engine = pm2_5(35, 0, 0, 300);
+        assert_eq!(engine.update(0, &reading(40, 70)), [high(35)]);
+        assert_eq!(engine.update(10, &reading(20, 70)), [cleared(35)]);
+        assert_eq!(engine.update(20, &reading(40, 70)), []);
+        assert_eq!(engine.update(309, &reading(40, 70)), []);
+        assert_eq!(engine.update(310, &reading(40, 70)), [high(35)]);
+    }
+
+    #[test]
+    fn checks_low_limits_per_metric() {
+        let temperature = Rule { low: Some(40), high: Some(95), hysteresis: 2, ..Default::default() };
+        let mut engine = Engine::new(Rules { temperature, ..Default::default() });
+        let low = Alert { metric: AlertMetric::Temperature, state: AlertState::Low, limit: 40 };
+        // no PM2.5 rule, so particulates never alert
+        assert_eq!(engine.update(0, &reading(500, 39)), [low]);
+        assert_eq!(engine.update(1, &reading(500, 41)), []);
+        assert_eq!(engine.update(2, &reading(500, 42)), [Alert { state: AlertState::Cleared, ..low }]);
+    }
+}
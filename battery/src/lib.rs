@@ -0,0 +1,154 @@
+#![no_std]
+
+//! Battery voltage, state of charge and the low-battery policy for a node running on a LiPo.
+//!
+//! The firmware samples VBAT through the board's divider; [`millivolts`] undoes the divider,
+//! [`percent`] estimates the charge left from a typical LiPo discharge curve, and [`Policy`]
+//! decides when the node should save power or shut down.
+
+/// Full scale of the RP2040's 12-bit ADC
+const ADC_MAX: u32 = 4095;
+const ADC_REFERENCE_MV: u32 = 3300;
+/// The Feather halves VBAT before it reaches the ADC pin
+const DIVIDER: u32 = 2;
+
+/// Resting cell voltage against charge left, highest first. The curve is flat through the middle,
+/// so the estimate is coarse there and under load.
+const LIPO_CURVE: [(u16, u8); 11] = [
+    (4200, 100),
+    (4100, 90),
+    (4000, 80),
+    (3920, 70),
+    (3870, 60),
+    (3840, 50),
+    (3800, 40),
+    (3770, 30),
+    (3730, 20),
+    (3690, 10),
+    (3300, 0),
+];
+
+/// Converts an ADC reading of the VBAT divider to millivolts at the battery
+pub fn millivolts(counts: u16) -> u16 {
+    (u32::from(counts.min(ADC_MAX as u16)) * ADC_REFERENCE_MV * DIVIDER / ADC_MAX) as u16
+}
+
+/// Approximate state of charge, interpolated along the LiPo curve
+pub fn percent(millivolts: u16) -> u8 {
+    let (full_mv, _) = LIPO_CURVE[0];
+    if millivolts >= full_mv {
+        return 100;
+    }
+    for pair in LIPO_CURVE.windows(2) {
+        let ((high_mv, high), (low_mv, low)) = (pair[0], pair[1]);
+        if millivolts >= low_mv {
+            let span = u32::from(high - low) * u32::from(millivolts - low_mv) / u32::from(high_mv - low_mv);
+            return low + span as u8;
+        }
+    }
+    0
+}
+
+/// How hard the node should try to save power
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Normal,
+    /// Sample and send less often, and leave the air quality sensor off
+    Low,
+    /// Stop everything before the cell is damaged
+    Critical,
+}
+
+impl Level {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Normal => "normal",
+            Level::Low => "low",
+            Level::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Policy {
+    /// Below this the node saves power
+    pub low_mv: u16,
+    /// Below this the node shuts down
+    pub cutoff_mv: u16,
+    /// How far above a threshold the battery must recover before the level goes back up, so the
+    /// sag under a radio TX, or a cell resting near a threshold, doesn't flip it back and forth
+    pub hysteresis_mv: u16,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        // around 10% left, and the protection circuit trips not far below 3.0V
+        Self { low_mv: 3700, cutoff_mv: 3400, hysteresis_mv: 100 }
+    }
+}
+
+impl Policy {
+    /// The level to move to from `current` given a new reading
+    pub fn level(&self, current: Level, millivolts: u16) -> Level {
+        let recovered = |threshold: u16| millivolts >= threshold.saturating_add(self.hysteresis_mv);
+        if millivolts < self.cutoff_mv {
+            Level::Critical
+        } else if millivolts < self.low_mv {
+            match current {
+                Level::Critical if !recovered(self.cutoff_mv) => Level::Critical,
+                _ => Level::Low,
+            }
+        } else {
+            match current {
+                Level::Critical if !recovered(self.cutoff_mv) => Level::Critical,
+                Level::Critical | Level::Low if !recovered(self.low_mv) => Level::Low,
+                _ => Level::Normal,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_adc_counts() {
+        assert_eq!(millivolts(0), 0);
+        assert_eq!(millivolts(4095), 6600);
+        // a full cell reads just over half scale
+        assert_eq!(millivolts(2606), 4200);
+    }
+
+    #[test]
+    fn follows_the_lipo_curve() {
+        assert_eq!(percent(4300), 100);
+        assert_eq!(percent(4200), 100);
+        assert_eq!(percent(4150), 95);
+        assert_eq!(percent(3840), 50);
+        assert_eq!(percent(3690), 10);
+        assert_eq!(percent(3495), 5);
+        assert_eq!(percent(3300), 0);
+        assert_eq!(percent(3000), 0);
+    }
+
+    #[test]
+    fn drops_straight_through_thresholds() {
+        let policy = Policy::default();
+        assert_eq!(policy.level(Level::Normal, 3800), Level::Normal);
+        assert_eq!(policy.level(Level::Normal, 3699), Level::Low);
+        assert_eq!(policy.level(Level::Normal, 3399), Level::Critical);
+        assert_eq!(policy.level(Level::Low, 3399), Level::Critical);
+    }
+
+    #[test]
+    fn recovers_only_past_the_hysteresis() {
+        let policy = Policy::default();
+        assert_eq!(policy.level(Level::Low, 3750), Level::Low);
+        assert_eq!(policy.level(Level::Low, 3800), Level::Normal);
+        assert_eq!(policy.level(Level::Critical, 3450), Level::Critical);
+        assert_eq!(policy.level(Level::Critical, 3500), Level::Low);
+        // charging can lift a shut down node straight back to normal
+        assert_eq!(policy.level(Level::Critical, 4000), Level::Normal);
+    }
+}
@@ -0,0 +1,20 @@
+//! Small icons written as ASCII art, so they can be read and tweaked in the source.
+
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, Pixel, Point};
+
+/// Draws the `#`s in `rows` in `color`, leaving everything else untouched
+pub fn draw<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    rows: &[&str],
+    top_left: Point,
+    color: BinaryColor,
+) -> Result<(), D::Error> {
+    let pixels = rows.iter().enumerate().flat_map(|(y, row)| {
+        row.bytes()
+            .enumerate()
+            .filter(|(_, b)| *b == b'#')
+            .map(move |(x, _)| Pixel(top_left + Point::new(x as i32, y as i32), color))
+    });
+    target.draw_iter(pixels)
+}
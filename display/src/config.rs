@@ -0,0 +1,199 @@
+//! Idle behaviour and burn-in protection.
+//!
+//! OLED pixels wear in proportion to how long they're lit, so a page left on the SH1107 for
+//! months leaves a ghost of itself. The panel dims and then turns off when nobody is pressing
+//! buttons, and while on, the whole layout wanders a couple of pixels so no edge is lit forever.
+
+use core::fmt;
+use embedded_graphics::mono_font::MonoFont;
+use embedded_graphics::mono_font::ascii::FONT_7X13;
+use embedded_graphics::prelude::Point;
+use crate::glance::Thresholds;
+
+/// Furthest the layout is shifted right or down; the pages leave this much margin
+pub const MAX_PIXEL_SHIFT: u8 = 2;
+
+#[derive(Clone, Copy)]
+pub struct DisplayConfig {
+    /// Text font; the pages size their lines to it, so FONT_6X10 suits a 128x32 panel
+    pub font: &'static MonoFont<'static>,
+    /// Contrast while in use, 0-255
+    pub contrast: u8,
+    /// Drop to `dim_contrast` after this long without a press; `None` never dims
+    pub dim_after_secs: Option<u64>,
+    pub dim_contrast: u8,
+    /// Turn the panel off (or start the screensaver) after this long without a press; `None`
+    /// leaves it on
+    pub off_after_secs: Option<u64>,
+    /// Show a small wandering readout instead of going dark
+    pub screensaver: bool,
+    /// Pixels to shift the layout by, up to `MAX_PIXEL_SHIFT`; 0 disables shifting
+    pub pixel_shift: u8,
+    pub shift_every_secs: u64,
+    /// Readings beyond these flash the glance page's alert
+    pub thresholds: Thresholds,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            font: &FONT_7X13,
+            contrast: 0x80,
+            dim_after_secs: Some(30),
+            dim_contrast: 0x10,
+            off_after_secs: Some(120),
+            screensaver: false,
+            pixel_shift: MAX_PIXEL_SHIFT,
+            shift_every_secs: 60,
+            thresholds: Thresholds::default(),
+        }
+    }
+}
+
+// MonoFont is neither Debug nor PartialEq, so fonts are told apart by their size and compared by
+// identity
+impl fmt::Debug for DisplayConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DisplayConfig")
+            .field("font", &self.font.character_size)
+            .field("contrast", &self.contrast)
+            .field("dim_after_secs", &self.dim_after_secs)
+            .field("dim_contrast", &self.dim_contrast)
+            .field("off_after_secs", &self.off_after_secs)
+            .field("screensaver", &self.screensaver)
+            .field("pixel_shift", &self.pixel_shift)
+            .field("shift_every_secs", &self.shift_every_secs)
+            .field("thresholds", &self.thresholds)
+            .finish()
+    }
+}
+
+impl PartialEq for DisplayConfig {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self.font, other.font)
+            && self.contrast == other.contrast
+            && self.dim_after_secs == other.dim_after_secs
+            && self.dim_contrast == other.dim_contrast
+            && self.off_after_secs == other.off_after_secs
+            && self.screensaver == other.screensaver
+            && self.pixel_shift == other.pixel_shift
+            && self.shift_every_secs == other.shift_every_secs
+            && self.thresholds == other.thresholds
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Screen {
+    Active,
+    Dimmed,
+    Screensaver,
+    Off,
+}
+
+impl DisplayConfig {
+    /// What the panel should be doing `idle_secs` after the last press
+    pub fn screen(&self, idle_secs: u64) -> Screen {
+        if self.off_after_secs.is_some_and(|off| idle_secs >= off) {
+            if self.screensaver { Screen::Screensaver } else { Screen::Off }
+        } else if self.dim_after_secs.is_some_and(|dim| idle_secs >= dim) {
+            Screen::Dimmed
+        } else {
+            Screen::Active
+        }
+    }
+
+    pub fn contrast(&self, screen: Screen) -> u8 {
+        match screen {
+            Screen::Active => self.contrast,
+            Screen::Dimmed | Screen::Screensaver => self.dim_contrast,
+            Screen::Off => 0,
+        }
+    }
+
+    /// Seconds from `idle_secs` until `screen` next changes, if it ever does
+    pub fn next_change(&self, idle_secs: u64) -> Option<u64> {
+        [self.dim_after_secs, self.off_after_secs]
+            .into_iter()
+            .flatten()
+            .filter(|at| *at > idle_secs)
+            .min()
+            .map(|at| at - idle_secs)
+    }
+
+    /// Layout offset at `now_secs`, walking every position in the shift square in turn
+    pub fn shift(&self, now_secs: u64) -> Point {
+        let range = u64::from(self.pixel_shift.min(MAX_PIXEL_SHIFT)) + 1;
+        if range == 1 || self.shift_every_secs == 0 {
+            return Point::zero();
+        }
+        let step = now_secs / self.shift_every_secs % (range * range);
+        // boustrophedon, so within a pass each move is a single pixel
+        let row = step / range;
+        let column = if row.is_multiple_of(2) { step % range } else { range - 1 - step % range };
+        Point::new(column as i32, row as i32)
+    }
+
+    /// Seconds from `now_secs` until the layout next shifts
+    pub fn next_shift(&self, now_secs: u64) -> Option<u64> {
+        (self.pixel_shift > 0 && self.shift_every_secs > 0)
+            .then(|| self.shift_every_secs - now_secs % self.shift_every_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dims_then_turns_off() {
+        let config = DisplayConfig::default();
+        assert_eq!(config.screen(0), Screen::Active);
+        assert_eq!(config.screen(30), Screen::Dimmed);
+        assert_eq!(config.screen(120), Screen::Off);
+        assert_eq!(config.contrast(Screen::Dimmed), 0x10);
+
+        let saver = DisplayConfig { screensaver: true, ..config };
+        assert_eq!(saver.screen(600), Screen::Screensaver);
+
+        let always_on = DisplayConfig { dim_after_secs: None, off_after_secs: None, ..config };
+        assert_eq!(always_on.screen(u64::MAX), Screen::Active);
+    }
+
+    #[test]
+    fn next_change() {
+        let config = DisplayConfig::default();
+        assert_eq!(config.next_change(0), Some(30));
+        assert_eq!(config.next_change(45), Some(75));
+        assert_eq!(config.next_change(120), None);
+    }
+
+    #[test]
+    fn shift_walks_square_one_pixel_at_a_time() {
+        let config = DisplayConfig { pixel_shift: 2, shift_every_secs: 10, ..Default::default() };
+        let positions: [Point; 10] = core::array::from_fn(|i| config.shift(i as u64 * 10));
+        assert_eq!(positions[0], Point::zero());
+        // back to the start after all 9 positions
+        assert_eq!(positions[9], Point::zero());
+        for pair in positions[..9].windows(2) {
+            let step = pair[1] - pair[0];
+            assert!(step.x.abs() + step.y.abs() <= 1, "{:?}", pair);
+        }
+        for p in positions {
+            assert!((0..=2).contains(&p.x) && (0..=2).contains(&p.y));
+        }
+        assert_eq!(config.next_shift(25), Some(5));
+    }
+
+    #[test]
+    fn shift_disabled_or_clamped() {
+        let none = DisplayConfig { pixel_shift: 0, ..Default::default() };
+        assert_eq!(none.shift(12_345), Point::zero());
+        assert_eq!(none.next_shift(1), None);
+
+        let huge = DisplayConfig { pixel_shift: 10, shift_every_secs: 1, ..Default::default() };
+        for t in 0..100 {
+            let p = huge.shift(t);
+            assert!(p.x <= MAX_PIXEL_SHIFT as i32 && p.y <= MAX_PIXEL_SHIFT as i32);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! The display loop's decisions: which page is up, when the panel dims, sleeps or shifts, and when
+//! the reading goes stale.
+//!
+//! Time is seconds since boot, passed in, so the firmware's task and the desktop simulator drive
+//! the same logic from their own clocks and only do the panel I/O themselves.
+
+use telemetry::{Alert, EnvReading};
+use crate::Frame;
+use crate::config::{DisplayConfig, Screen};
+use crate::glance::{self, FLASH_SECS};
+use crate::ui::{Button, Effect, Page, Status, Ui};
+
+pub struct Controller {
+    config: DisplayConfig,
+    stale_after_secs: u64,
+    ui: Ui,
+    status: Status,
+    last_reading_secs: u64,
+    last_press_secs: u64,
+}
+
+impl Controller {
+    /// Starts with the panel dark; the reading counts as stale `stale_after_secs` after `now_secs`
+    /// if none arrives
+    pub fn new(config: DisplayConfig, stale_after_secs: u64, now_secs: u64) -> Self {
+        Self {
+            config,
+            stale_after_secs,
+            ui: Ui::default(),
+            status: Status::default(),
+            last_reading_secs: now_secs,
+            last_press_secs: now_secs,
+        }
+    }
+
+    pub fn config(&self) -> &DisplayConfig {
+        &self.config
+    }
+
+    pub fn page(&self) -> Page {
+        self.ui.page()
+    }
+
+    pub fn is_awake(&self) -> bool {
+        self.ui.is_awake()
+    }
+
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    /// For the radio and health updates that only change what the pages show
+    pub fn status_mut(&mut self) -> &mut Status {
+        &mut self.status
+    }
+
+    /// Handles a press, returning whether the panel should be blanked
+    pub fn press(&mut self, now_secs: u64, button: Button) -> bool {
+        let idle_secs = now_secs.saturating_sub(self.last_press_secs);
+        let saver = self.ui.is_awake() && self.config.screen(idle_secs) == Screen::Screensaver;
+        self.last_press_secs = now_secs;
+        // a press only wakes the screensaver, like a blank panel
+        if saver {
+            return false;
+        }
+        match self.ui.press(button) {
+            Effect::Blank => return true,
+            Effect::ResetMinMax => self.status.min_max = Default::default(),
+            Effect::Redraw => {}
+        }
+        false
+    }
+
+    pub fn reading(&mut self, now_secs: u64, reading: EnvReading) {
+        self.last_reading_secs = now_secs;
+        self.status.alert = glance::exceeded(&self.config.thresholds, &reading);
+        self.status.reading(now_secs, reading);
+    }
+
+    /// The node's alert rules raised or cleared `alarm`
+    pub fn alarm(&mut self, now_secs: u64, alarm: Option<Alert>, raised: bool) {
+        self.status.alarm = alarm;
+        // a new alert wakes the panel on its reading, as if just pressed
+        if let (Some(alert), true) = (alarm, raised) {
+            self.ui.alarm(alert.metric.into());
+            self.last_press_secs = now_secs;
+        }
+    }
+
+    /// Seconds from `now_secs` until the panel needs redrawing without any event, if ever
+    pub fn wake_in(&self, now_secs: u64) -> Option<u64> {
+        // once stale there's nothing to wake for until the next reading or press
+        let stale = (!self.status.stale).then(|| (self.last_reading_secs + self.stale_after_secs).saturating_sub(now_secs));
+        let mut awake = [None; 3];
+        if self.ui.is_awake() {
+            let idle_secs = now_secs.saturating_sub(self.last_press_secs);
+            let flashing = (self.status.alert || self.status.alarm.is_some()) && matches!(self.ui.page(), Page::Glance(_));
+            awake = [self.config.next_change(idle_secs), self.config.next_shift(now_secs), flashing.then_some(FLASH_SECS)];
+        }
+        [stale].into_iter().chain(awake).flatten().min()
+    }
+
+    /// What the panel should show at `now_secs`, or `None` while it's dark
+    pub fn frame(&mut self, now_secs: u64) -> Option<Frame> {
+        self.status.stale = now_secs >= self.last_reading_secs + self.stale_after_secs;
+        if !self.ui.is_awake() {
+            return None;
+        }
+        Some(match self.config.screen(now_secs.saturating_sub(self.last_press_secs)) {
+            Screen::Off => {
+                self.ui.timeout();
+                Frame::Off
+            }
+            Screen::Screensaver => Frame::Screensaver,
+            screen => Frame::Page(self.ui.page(), screen),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glance::Metric;
+
+    const READING: EnvReading = EnvReading { aq_pm2_5: 12, aq_pm10: 15, humidity: 48, temperature: 68, aq_pm1_0: 8 };
+
+    #[test]
+    fn wakes_dims_and_sleeps() {
+        let mut controller = Controller::new(DisplayConfig::default(), 9, 0);
+        controller.reading(0, READING);
+        assert_eq!(controller.frame(0), None);
+        assert_eq!(controller.wake_in(0), Some(9));
+
+        assert!(!controller.press(5, Button::C));
+        assert_eq!(controller.frame(5), Some(Frame::Page(Page::Glance(Metric::Aqi), Screen::Active)));
+        assert_eq!(controller.frame(35), Some(Frame::Page(Page::Glance(Metric::Aqi), Screen::Dimmed)));
+        assert!(controller.status().stale);
+        assert_eq!(controller.frame(125), Some(Frame::Off));
+        assert_eq!(controller.frame(126), None);
+    }
+
+    #[test]
+    fn press_only_wakes_the_screensaver() {
+        let config = DisplayConfig { screensaver: true, ..Default::default() };
+        let mut controller = Controller::new(config, 9, 0);
+        controller.press(0, Button::C);
+        assert_eq!(controller.frame(200), Some(Frame::Screensaver));
+        assert!(!controller.press(200, Button::C));
+        assert_eq!(controller.frame(200), Some(Frame::Page(Page::Glance(Metric::Aqi), Screen::Active)));
+    }
+}
@@ -0,0 +1,51 @@
+//! In-memory stand-in for the panel so pages and charts can be rendered and compared on the host.
+
+use core::convert::Infallible;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, OriginDimensions, Pixel, Size};
+
+pub struct Framebuffer<const W: usize, const H: usize> {
+    pixels: [[bool; W]; H],
+    /// Pixels drawn off the edges, which the real panel would silently drop
+    pub out_of_bounds: usize,
+}
+
+impl<const W: usize, const H: usize> Framebuffer<W, H> {
+    pub fn new() -> Self {
+        Self { pixels: [[false; W]; H], out_of_bounds: 0 }
+    }
+
+    pub fn lit(&self) -> usize {
+        self.pixels.iter().flatten().filter(|p| **p).count()
+    }
+
+    /// One row per line, `#` for a lit pixel and `.` otherwise
+    pub fn snapshot(&self) -> std::string::String {
+        self.pixels
+            .iter()
+            .map(|row| row.iter().map(|p| if *p { '#' } else { '.' }).collect::<std::string::String>())
+            .collect::<std::vec::Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl<const W: usize, const H: usize> OriginDimensions for Framebuffer<W, H> {
+    fn size(&self) -> Size {
+        Size::new(W as u32, H as u32)
+    }
+}
+
+impl<const W: usize, const H: usize> DrawTarget for Framebuffer<W, H> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I: IntoIterator<Item = Pixel<Self::Color>>>(&mut self, pixels: I) -> Result<(), Self::Error> {
+        for Pixel(point, color) in pixels {
+            match (usize::try_from(point.x), usize::try_from(point.y)) {
+                (Ok(x), Ok(y)) if x < W && y < H => self.pixels[y][x] = color.is_on(),
+                _ => self.out_of_bounds += 1,
+            }
+        }
+        Ok(())
+    }
+}
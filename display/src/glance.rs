@@ -0,0 +1,363 @@
+//! One metric, big enough to read from across the shop.
+//!
+//! The value is drawn as seven-segment digits sized to whatever panel it's on, with a small icon,
+//! name and unit beside it, and a warning triangle that flashes while any threshold is exceeded.
+
+use air_quality::aqi::aqi;
+use embedded_graphics::Drawable;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::ascii::FONT_4X6;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, Point, Primitive, Size};
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use heapless::String;
+use telemetry::{AlertMetric, EnvReading};
+pub use node_config::Thresholds;
+use crate::bitmap;
+use crate::config::MAX_PIXEL_SHIFT;
+
+/// The alert indicator is lit for this long, then dark for as long again
+pub const FLASH_SECS: u64 = 1;
+
+const ICON_SIZE: i32 = 12;
+// two icons side by side, with the name and unit under them
+const LABEL_COLUMN: i32 = 2 * ICON_SIZE + 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    Aqi,
+    Pm2_5,
+    Pm10,
+    Temperature,
+    Humidity,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 5] = [Metric::Aqi, Metric::Pm2_5, Metric::Pm10, Metric::Temperature, Metric::Humidity];
+
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|m| m == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn value(&self, reading: &EnvReading) -> u16 {
+        match self {
+            Metric::Aqi => aqi(reading.aq_pm2_5, reading.aq_pm10).value,
+            Metric::Pm2_5 => reading.aq_pm2_5,
+            Metric::Pm10 => reading.aq_pm10,
+            Metric::Temperature => reading.temperature,
+            Metric::Humidity => reading.humidity,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::Aqi => "AQI",
+            Metric::Pm2_5 => "PM2.5",
+            Metric::Pm10 => "PM10",
+            Metric::Temperature => "TEMP",
+            Metric::Humidity => "RH",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Metric::Aqi => "",
+            Metric::Pm2_5 | Metric::Pm10 => "ug/m3",
+            Metric::Temperature => "F",
+            Metric::Humidity => "%",
+        }
+    }
+
+    fn icon(&self) -> &'static Icon {
+        match self {
+            Metric::Aqi | Metric::Pm2_5 | Metric::Pm10 => &PARTICULATES,
+            Metric::Temperature => &THERMOMETER,
+            Metric::Humidity => &DROPLET,
+        }
+    }
+}
+
+/// Whether `reading` is beyond any of `thresholds`, which flashes the glance page's alert
+pub fn exceeded(thresholds: &Thresholds, reading: &EnvReading) -> bool {
+    let above = |max: Option<u16>, value: u16| max.is_some_and(|max| value > max);
+    above(thresholds.aqi_max, Metric::Aqi.value(reading))
+        || above(thresholds.pm2_5_max, reading.aq_pm2_5)
+        || above(thresholds.temperature_max, reading.temperature)
+        || thresholds.temperature_min.is_some_and(|min| reading.temperature < min)
+        || above(thresholds.humidity_max, reading.humidity)
+}
+
+impl From<AlertMetric> for Metric {
+    fn from(metric: AlertMetric) -> Self {
+        match metric {
+            AlertMetric::Pm2_5 => Metric::Pm2_5,
+            AlertMetric::Temperature => Metric::Temperature,
+            AlertMetric::Humidity => Metric::Humidity,
+        }
+    }
+}
+
+/// 12x12 bitmap, `#` lit
+type Icon = [&'static str; ICON_SIZE as usize];
+
+const THERMOMETER: Icon = [
+    "....##......",
+    "...#..#..##.",
+    "...#..#.....",
+    "...#..#..##.",
+    "...#.##.....",
+    "...#.##..##.",
+    "...#.##.....",
+    "..#.####....",
+    ".#.######...",
+    ".#.######...",
+    "..#.####....",
+    "...####.....",
+];
+
+const DROPLET: Icon = [
+    ".....##.....",
+    ".....##.....",
+    "....####....",
+    "....####....",
+    "...######...",
+    "..####.###..",
+    "..####.###..",
+    ".####.#####.",
+    ".####.#####.",
+    ".###########",
+    "..########..",
+    "...######...",
+];
+
+const PARTICULATES: Icon = [
+    "##......#...",
+    "##..##......",
+    ".....#...##.",
+    "..#......##.",
+    ".....###....",
+    "#....###..#.",
+    "..#..###....",
+    "...........#",
+    ".##...#.....",
+    ".##......##.",
+    "......#..##.",
+    "...#........",
+];
+
+const ALERT: Icon = [
+    ".....##.....",
+    "....####....",
+    "....#..#....",
+    "...##..##...",
+    "...#.##.#...",
+    "..##.##.##..",
+    "..#..##..#..",
+    ".##..##..##.",
+    ".#........#.",
+    "##...##...##",
+    "#..........#",
+    "############",
+];
+
+// segments a-g as bits 0-6, clockwise from the top with g across the middle
+const SEGMENTS: [u8; 10] = [0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07, 0x7f, 0x6f];
+const DASH: u8 = 0x40;
+
+fn digit<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    segments: u8,
+    top_left: Point,
+    size: Size,
+) -> Result<(), D::Error> {
+    let (w, h) = (size.width as i32, size.height as i32);
+    let t = (h / 9).max(1);
+    let middle = (h - t) / 2;
+    let bars = [
+        (t, 0, w - 2 * t, t),
+        (w - t, t, t, middle - t),
+        (w - t, middle + t, t, h - middle - 2 * t),
+        (t, h - t, w - 2 * t, t),
+        (0, middle + t, t, h - middle - 2 * t),
+        (0, t, t, middle - t),
+        (t, middle, w - 2 * t, t),
+    ];
+    let fill = PrimitiveStyle::with_fill(BinaryColor::On);
+    for (i, (x, y, bw, bh)) in bars.into_iter().enumerate() {
+        if segments & (1 << i) != 0 {
+            Rectangle::new(top_left + Point::new(x, y), Size::new(bw.max(0) as u32, bh.max(0) as u32))
+                .into_styled(fill)
+                .draw(target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Draws `text`, digits or `-`, as large as fits in `area`, right-aligned and vertically centred
+fn number<D: DrawTarget<Color = BinaryColor>>(target: &mut D, text: &str, area: Rectangle) -> Result<(), D::Error> {
+    let count = text.len().max(1) as u32;
+    // digits are half as wide as they are tall, with a gap of a quarter width between them
+    let width = (area.size.width * 4 / (5 * count - 1)).min(area.size.height / 2);
+    let size = Size::new(width, width * 2);
+    let gap = width / 4;
+    let right = area.top_left.x + area.size.width as i32;
+    let top = area.top_left.y + (area.size.height - size.height) as i32 / 2;
+    for (i, c) in text.bytes().rev().enumerate() {
+        let segments = match c {
+            b'0'..=b'9' => SEGMENTS[usize::from(c - b'0')],
+            _ => DASH,
+        };
+        let x = right - ((i as u32 + 1) * width + i as u32 * gap) as i32;
+        digit(target, segments, Point::new(x, top), size)?;
+    }
+    Ok(())
+}
+
+/// Draws the glance page for `metric`. `now_secs` drives the alert flash.
+pub fn draw<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    metric: Metric,
+    status: &crate::ui::Status,
+    now_secs: u64,
+) -> Result<(), D::Error> {
+    let bounds = target.bounding_box().size;
+    let shift = u32::from(MAX_PIXEL_SHIFT);
+    let small = MonoTextStyle::new(&FONT_4X6, BinaryColor::On);
+
+    bitmap::draw(target, metric.icon(), Point::zero(), BinaryColor::On)?;
+    if (status.alert || status.alarm.is_some()) && (now_secs / FLASH_SECS).is_multiple_of(2) {
+        bitmap::draw(target, &ALERT, Point::new(ICON_SIZE + 1, 0), BinaryColor::On)?;
+    }
+    Text::with_baseline(metric.name(), Point::new(0, ICON_SIZE + 1), small, Baseline::Top).draw(target)?;
+    // an old value is still shown, but loses its unit so it isn't mistaken for a live one
+    let unit = if status.stale { "old" } else { metric.unit() };
+    Text::with_baseline(unit, Point::new(0, ICON_SIZE + 7), small, Baseline::Top).draw(target)?;
+
+    let mut text: String<5> = String::new();
+    match &status.reading {
+        Some(reading) => {
+            let _ = core::fmt::write(&mut text, format_args!("{}", metric.value(reading)));
+        }
+        None => {
+            let _ = text.push_str("--");
+        }
+    }
+    let area = Rectangle::new(
+        Point::new(LABEL_COLUMN, 0),
+        Size::new(
+            bounds.width.saturating_sub(LABEL_COLUMN as u32 + shift),
+            bounds.height.saturating_sub(shift),
+        ),
+    );
+    number(target, &text, area)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::Framebuffer;
+    use telemetry::{Alert, AlertState};
+    use crate::ui::Status;
+
+    fn reading() -> EnvReading {
+        EnvReading { aq_pm2_5: 12, aq_pm10: 15, humidity: 48, temperature: 68, aq_pm1_0: 8 }
+    }
+
+    #[test]
+    fn cycles_every_metric() {
+        let mut metric = Metric::Aqi;
+        for expected in [Metric::Pm2_5, Metric::Pm10, Metric::Temperature, Metric::Humidity, Metric::Aqi] {
+            metric = metric.next();
+            assert_eq!(metric, expected);
+        }
+        assert_eq!(Metric::Aqi.value(&reading()), 56);
+        assert_eq!(Metric::Humidity.value(&reading()), 48);
+    }
+
+    #[test]
+    fn thresholds() {
+        let thresholds = Thresholds::default();
+        assert!(!exceeded(&thresholds, &reading()));
+        assert!(exceeded(&thresholds, &EnvReading { aq_pm2_5: 36, ..reading() }));
+        assert!(exceeded(&thresholds, &EnvReading { temperature: 39, ..reading() }));
+        assert!(exceeded(&thresholds, &EnvReading { humidity: 71, ..reading() }));
+        let never = Thresholds {
+            aqi_max: None,
+            pm2_5_max: None,
+            temperature_min: None,
+            temperature_max: None,
+            humidity_max: None,
+        };
+        assert!(!exceeded(&never, &EnvReading { aq_pm2_5: 500, aq_pm10: 600, ..reading() }));
+    }
+
+    #[test]
+    fn icons_are_square() {
+        for icon in [&THERMOMETER, &DROPLET, &PARTICULATES, &ALERT] {
+            assert!(icon.iter().all(|row| row.len() == ICON_SIZE as usize));
+        }
+    }
+
+    #[test]
+    fn digits_snapshot() {
+        let mut fb = Framebuffer::<24, 18>::new();
+        number(&mut fb, "42", Rectangle::new(Point::zero(), Size::new(24, 18))).unwrap();
+        assert_eq!(fb.snapshot(), [
+            ".................#####..",
+            ".................#####..",
+            "....##.....##.........##",
+            "....##.....##.........##",
+            "....##.....##.........##",
+            "....##.....##.........##",
+            "....##.....##.........##",
+            "....##.....##.........##",
+            "......#####......#####..",
+            "......#####......#####..",
+            "...........##..##.......",
+            "...........##..##.......",
+            "...........##..##.......",
+            "...........##..##.......",
+            "...........##..##.......",
+            "...........##..##.......",
+            ".................#####..",
+            ".................#####..",
+        ].join("\n"));
+        assert_eq!(fb.out_of_bounds, 0);
+    }
+
+    #[test]
+    fn alert_flashes() {
+        let mut status = Status::default();
+        status.reading(0, EnvReading { aq_pm2_5: 80, ..reading() });
+        let lit = |status: &Status, now_secs| {
+            let mut fb = Framebuffer::<128, 64>::new();
+            draw(&mut fb, Metric::Pm2_5, status, now_secs).unwrap();
+            assert_eq!(fb.out_of_bounds, 0);
+            fb.lit()
+        };
+        let quiet = lit(&status, 0);
+        status.alert = true;
+        assert!(lit(&status, 0) > lit(&status, FLASH_SECS));
+        assert_eq!(lit(&status, 0), lit(&status, 2 * FLASH_SECS));
+        assert_eq!(lit(&status, FLASH_SECS), quiet);
+
+        // a raised alert flashes the same warning
+        let flashing = lit(&status, 0);
+        status.alert = false;
+        status.alarm = Some(Alert { metric: AlertMetric::Pm2_5, state: AlertState::High, limit: 35 });
+        assert_eq!(lit(&status, 0), flashing);
+    }
+
+    #[test]
+    fn fits_a_short_panel() {
+        let mut status = Status::default();
+        status.reading(0, reading());
+        for metric in Metric::ALL {
+            let mut fb = Framebuffer::<128, 32>::new();
+            draw(&mut fb, metric, &status, 0).unwrap();
+            assert_eq!(fb.out_of_bounds, 0, "{:?}", metric);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! The last hour of readings as sparklines and bar charts.
+//!
+//! An hour at the node's 3s sample interval is 1200 points per metric, far more than the panel is
+//! wide, so samples are averaged into `HISTORY_BUCKET_SECS` buckets as they arrive. Three series of
+//! `HISTORY_POINTS` u16s come to 600 bytes, small next to the 1KB SH1107 framebuffer.
+
+use core::fmt::Write;
+use embedded_graphics::Drawable;
+use embedded_graphics::mono_font::ascii::FONT_4X6;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, Point, Primitive, Size};
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use heapless::{HistoryBuffer, String};
+use telemetry::EnvReading;
+
+pub const HISTORY_POINTS: usize = 100;
+pub const HISTORY_BUCKET_SECS: u64 = 3_600 / HISTORY_POINTS as u64;
+
+// wide enough for a 5 digit u16 in FONT_4X6, plus a gap
+const LABEL_WIDTH: u32 = 22;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Chart {
+    Sparkline,
+    Bars,
+}
+
+/// Fixed-size ring of bucket averages, oldest first
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Series {
+    points: HistoryBuffer<u16, HISTORY_POINTS>,
+    bucket: Option<u64>,
+    sum: u32,
+    count: u32,
+}
+
+impl Series {
+    /// Adds a sample taken `now_secs` after boot. A bucket with no samples, e.g. while a sensor
+    /// was failing, is skipped rather than drawn as zero.
+    pub fn add(&mut self, now_secs: u64, value: u16) {
+        let bucket = now_secs / HISTORY_BUCKET_SECS;
+        if self.bucket.is_some_and(|b| b != bucket) {
+            self.flush();
+        }
+        self.bucket = Some(bucket);
+        self.sum += u32::from(value);
+        self.count += 1;
+    }
+
+    fn flush(&mut self) {
+        if let Some(mean) = self.current() {
+            self.points.write(mean);
+        }
+        self.sum = 0;
+        self.count = 0;
+    }
+
+    fn current(&self) -> Option<u16> {
+        (self.count > 0).then(|| (self.sum / self.count) as u16)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.len() == 0 && self.count == 0
+    }
+
+    /// Completed buckets then the one still filling, at most `HISTORY_POINTS` in all
+    pub fn points(&self) -> impl Iterator<Item = u16> + Clone + '_ {
+        let current = self.current();
+        let skip = usize::from(self.points.len() == HISTORY_POINTS && current.is_some());
+        self.points.oldest_ordered().copied().skip(skip).chain(current)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct History {
+    pub temperature: Series,
+    pub humidity: Series,
+    pub pm2_5: Series,
+}
+
+impl History {
+    pub fn add(&mut self, now_secs: u64, reading: &EnvReading) {
+        self.temperature.add(now_secs, reading.temperature);
+        self.humidity.add(now_secs, reading.humidity);
+        self.pm2_5.add(now_secs, reading.aq_pm2_5);
+    }
+}
+
+/// Maps values onto the chart's rows, stretched to fill its height
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Scale {
+    min: u16,
+    max: u16,
+}
+
+impl Scale {
+    fn new(points: impl Iterator<Item = u16>) -> Option<Self> {
+        points.fold(None, |scale, p| match scale {
+            None => Some(Scale { min: p, max: p }),
+            Some(Scale { min, max }) => Some(Scale { min: min.min(p), max: max.max(p) }),
+        })
+    }
+
+    fn y(&self, value: u16, area: &Rectangle) -> i32 {
+        let rows = area.size.height.saturating_sub(1) as i32;
+        // a flat series sits on the bottom row rather than dividing by zero
+        let span = i32::from(self.max - self.min).max(1);
+        area.top_left.y + rows - i32::from(value - self.min) * rows / span
+    }
+}
+
+fn label<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    value: u16,
+    position: Point,
+    baseline: Baseline,
+) -> Result<(), D::Error> {
+    let mut text: String<5> = String::new();
+    let _ = write!(text, "{}", value);
+    Text::with_baseline(&text, position, MonoTextStyle::new(&FONT_4X6, BinaryColor::On), baseline)
+        .draw(target)?;
+    Ok(())
+}
+
+/// Draws `points` into `area`, newest at the right edge, with the min and max labelled on the left.
+/// Draws nothing for an empty series.
+pub fn chart<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    area: Rectangle,
+    chart: Chart,
+    points: impl Iterator<Item = u16> + Clone,
+) -> Result<(), D::Error> {
+    let Some(scale) = Scale::new(points.clone()) else {
+        return Ok(());
+    };
+    label(target, scale.max, area.top_left, Baseline::Top)?;
+    let bottom = area.top_left.y + area.size.height as i32 - 1;
+    label(target, scale.min, Point::new(area.top_left.x, bottom), Baseline::Bottom)?;
+
+    let plot = Rectangle::new(
+        area.top_left + Point::new(LABEL_WIDTH as i32, 0),
+        Size::new(area.size.width.saturating_sub(LABEL_WIDTH), area.size.height),
+    );
+    let count = points.clone().count() as i32;
+    let right = plot.top_left.x + plot.size.width as i32 - 1;
+    let stroke = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+
+    let mut previous: Option<Point> = None;
+    for (i, value) in points.enumerate() {
+        let x = right - (count - 1 - i as i32);
+        if x < plot.top_left.x {
+            continue;
+        }
+        let point = Point::new(x, scale.y(value, &plot));
+        match chart {
+            Chart::Sparkline => Line::new(previous.unwrap_or(point), point).into_styled(stroke).draw(target)?,
+            Chart::Bars => Line::new(Point::new(x, bottom), point).into_styled(stroke).draw(target)?,
+        }
+        previous = Some(point);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::Framebuffer;
+
+    fn series(values: &[u16]) -> Series {
+        let mut series = Series::default();
+        for (i, value) in values.iter().enumerate() {
+            series.add(i as u64 * HISTORY_BUCKET_SECS, *value);
+        }
+        series
+    }
+
+    #[test]
+    fn samples_averaged_per_bucket() {
+        let mut series = Series::default();
+        series.add(0, 60);
+        series.add(3, 70);
+        series.add(HISTORY_BUCKET_SECS, 80);
+        series.add(HISTORY_BUCKET_SECS + 3, 81);
+        // the second bucket is still filling but is shown
+        assert_eq!(series.points().collect::<heapless::Vec<_, 4>>(), [65, 80]);
+    }
+
+    #[test]
+    fn empty_buckets_skipped() {
+        let mut series = Series::default();
+        series.add(0, 60);
+        series.add(10 * HISTORY_BUCKET_SECS, 70);
+        assert_eq!(series.points().collect::<heapless::Vec<_, 4>>(), [60, 70]);
+    }
+
+    #[test]
+    fn ring_keeps_last_hour() {
+        let mut series = Series::default();
+        for i in 0..(HISTORY_POINTS as u64 + 20) {
+            series.add(i * HISTORY_BUCKET_SECS, i as u16);
+        }
+        let points = series.points();
+        assert_eq!(points.clone().count(), HISTORY_POINTS);
+        assert_eq!(points.clone().next(), Some(20));
+        assert_eq!(points.last(), Some(HISTORY_POINTS as u16 + 19));
+    }
+
+    #[test]
+    fn sparkline_snapshot() {
+        let mut fb = Framebuffer::<32, 14>::new();
+        let area = Rectangle::new(Point::zero(), Size::new(32, 14));
+        chart(&mut fb, area, Chart::Sparkline, series(&[10, 17, 24, 31, 24, 17, 10, 10]).points()).unwrap();
+        assert_eq!(fb.snapshot(), [
+            "###..#.....................#....",
+            "..#.##.....................#....",
+            ".#...#.....................#....",
+            "..#..#....................#.#...",
+            "##..###...................#.#...",
+            "..........................#.#...",
+            "..........................#.#...",
+            ".........................#..#...",
+            ".#...#...................#...#..",
+            "##..#.#..................#...#..",
+            ".#..###..................#...#..",
+            ".#..#.#.................#....#..",
+            "###..#..................#.....#.",
+            "........................#.....##",
+        ].join("\n"));
+        assert_eq!(fb.out_of_bounds, 0);
+    }
+
+    #[test]
+    fn bars_snapshot() {
+        let mut fb = Framebuffer::<28, 14>::new();
+        let area = Rectangle::new(Point::zero(), Size::new(28, 14));
+        chart(&mut fb, area, Chart::Bars, series(&[0, 5, 3, 1]).points()).unwrap();
+        assert_eq!(fb.snapshot(), [
+            "###......................#..",
+            "#........................#..",
+            "##.......................#..",
+            "..#......................#..",
+            "##.......................#..",
+            ".........................#..",
+            ".........................##.",
+            ".........................##.",
+            ".#.......................##.",
+            "#.#......................##.",
+            "###......................##.",
+            "#.#......................###",
+            ".#.......................###",
+            "........................####",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn flat_series_on_bottom_row() {
+        let mut fb = Framebuffer::<30, 6>::new();
+        let area = Rectangle::new(Point::zero(), Size::new(30, 6));
+        chart(&mut fb, area, Chart::Sparkline, series(&[7, 7, 7]).points()).unwrap();
+        assert!(fb.snapshot().lines().last().unwrap().ends_with("###"));
+    }
+
+    #[test]
+    fn empty_series_draws_nothing() {
+        let mut fb = Framebuffer::<30, 6>::new();
+        let area = Rectangle::new(Point::zero(), Size::new(30, 6));
+        chart(&mut fb, area, Chart::Bars, Series::default().points()).unwrap();
+        assert_eq!(fb.lit(), 0);
+    }
+}
@@ -1,61 +1,208 @@
 #![no_std]
 
-use display_interface_i2c::I2CInterface;
+#[cfg(test)]
+extern crate std;
+
+mod bitmap;
+pub mod config;
+pub mod controller;
+#[cfg(test)]
+mod framebuffer;
+pub mod glance;
+pub mod graph;
+#[cfg(feature = "sh1107")]
+pub mod sh1107;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+#[cfg(feature = "ssd1306")]
+pub mod ssd1306;
+pub mod status_bar;
+pub mod ui;
+
+use core::fmt::Debug;
+use display_interface::DisplayError;
+use suspend::Suspend;
 use embedded_graphics::Drawable;
-use embedded_graphics::mono_font::ascii::FONT_7X13;
-use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::draw_target::DrawTargetExt;
+use embedded_graphics::mono_font::MonoTextStyle;
 use embedded_graphics::pixelcolor::BinaryColor;
-use embedded_graphics::prelude::Point;
+use embedded_graphics::prelude::{DrawTarget, Point};
 use embedded_graphics::text::{Baseline, Text};
-use embedded_hal_async::i2c::I2c;
-use oled_async::Builder;
-use oled_async::displayrotation::DisplayRotation;
-use oled_async::displays::sh1107::Sh1107_64_128;
-use oled_async::prelude::GraphicsMode;
+use crate::config::{DisplayConfig, Screen};
+use crate::ui::{DeviceInfo, Page, Status};
+
+/// I2C address most OLED FeatherWings and breakouts ship with
+pub const DEFAULT_ADDRESS: u8 = 0x3c;
+
+/// Panel orientation, mapped onto each driver's own rotation type
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Rotation {
+    #[default]
+    Rotate0,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+/// What a [`controller::Controller`] wants on the panel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Frame {
+    /// Turn the panel off; what it showed is redrawn when it wakes
+    Off,
+    Screensaver,
+    Page(Page, Screen),
+}
+
+impl Frame {
+    pub fn screen(&self) -> Screen {
+        match self {
+            Frame::Off => Screen::Off,
+            Frame::Screensaver => Screen::Screensaver,
+            Frame::Page(_, screen) => *screen,
+        }
+    }
+}
+
+/// A monochrome panel with a framebuffer that is drawn into and then flushed in one go
+pub trait Panel: DrawTarget<Color = BinaryColor> {
+    fn init(&mut self) -> impl Future<Output = Result<(), DisplayError>>;
+
+    /// Blanks the framebuffer; the panel itself is unchanged until `flush`
+    fn clear_buffer(&mut self);
+
+    fn flush(&mut self) -> impl Future<Output = Result<(), DisplayError>>;
+
+    fn set_contrast(&mut self, contrast: u8) -> impl Future<Output = Result<(), DisplayError>>;
 
-const PIXELS: usize = 128 * 64 / 8;
+    /// Turns the panel off and back on, keeping the controller's RAM
+    fn set_display_on(&mut self, on: bool) -> impl Future<Output = Result<(), DisplayError>>;
+}
+
+/// A panel operation that failed, either drawing into the framebuffer or talking to the panel
+#[derive(Debug)]
+pub enum Error<E> {
+    Draw(E),
+    Panel(DisplayError),
+}
+
+impl<E> From<DisplayError> for Error<E> {
+    fn from(e: DisplayError) -> Self {
+        Error::Panel(e)
+    }
+}
 
-pub struct Display<I2C: I2c> {
-    display: GraphicsMode<Sh1107_64_128, I2CInterface<I2C>, PIXELS>,
+pub struct Display<P: Panel> {
+    panel: P,
     text_style: MonoTextStyle<'static, BinaryColor>,
+    config: DisplayConfig,
+    contrast: u8,
+    initialized: bool,
 }
 
-impl<I2C: I2c> Display<I2C> {
+impl<P: Panel> Display<P>
+where
+    P::Error: Debug,
+{
+    /// Nothing is sent to the panel until the first `show` or `draw`, so a missing panel only
+    /// fails those
+    pub fn new(panel: P, config: DisplayConfig) -> Self {
+        let text_style = MonoTextStyle::new(config.font, BinaryColor::On);
+
+        Self { panel, text_style, config, contrast: config.contrast, initialized: false }
+    }
+
+    pub fn config(&self) -> &DisplayConfig {
+        &self.config
+    }
 
-    pub async fn new(i2c: I2C) -> Self {
-        let di = I2CInterface::new(
-            i2c,
-            0x3c,
-            0x40
-        );
-        let raw_display = Builder::new(Sh1107_64_128 {})
-            .with_rotation(DisplayRotation::Rotate90)
-            .connect(di);
-        let mut display: GraphicsMode<_, _, PIXELS> = raw_display.into();
-        // reset is mapped appropriately by stacking the oled on top of the feather
-        display.init().await.unwrap();
-        display.clear();
-        display.flush().await.unwrap();
+    pub fn panel(&self) -> &P {
+        &self.panel
+    }
 
-        let text_style = MonoTextStyleBuilder::new()
-            .font(&FONT_7X13)
-            .text_color(BinaryColor::On)
-            .build();
+    /// Initializes and blanks the panel, once. Until it succeeds every `show` and `draw` tries
+    /// again, so a panel that was slow to come up or reseated starts working on its own.
+    async fn init(&mut self) -> Result<(), Error<P::Error>> {
+        if self.initialized {
+            return Ok(());
+        }
+        self.panel.init().await?;
+        self.panel.set_contrast(self.config.contrast).await?;
+        self.contrast = self.config.contrast;
+        self.panel.clear_buffer();
+        self.panel.flush().await?;
+        self.initialized = true;
+        Ok(())
+    }
 
-        Self { display, text_style }
+    /// Sets the contrast for `screen`, skipping the I2C write if it's unchanged
+    pub async fn set_screen(&mut self, screen: Screen) -> Result<(), Error<P::Error>> {
+        let contrast = self.config.contrast(screen);
+        if contrast != self.contrast {
+            self.panel.set_contrast(contrast).await?;
+            self.contrast = contrast;
+        }
+        Ok(())
     }
 
-    pub async fn clear(&mut self) {
-        self.display.clear();
-        self.display.flush().await.unwrap();
+    pub async fn clear(&mut self) -> Result<(), Error<P::Error>> {
+        self.init().await?;
+        self.panel.clear_buffer();
+        self.panel.flush().await?;
+        Ok(())
     }
 
-    pub async fn draw(&mut self, msg: &str) {
-        self.display.clear();
+    pub async fn draw(&mut self, msg: &str) -> Result<(), Error<P::Error>> {
+        self.init().await?;
+        self.panel.clear_buffer();
         Text::with_baseline(msg, Point::new(0, 0), self.text_style, Baseline::Top)
-            .draw(&mut self.display)
-            .unwrap();
-        self.display.flush().await.unwrap();
+            .draw(&mut self.panel)
+            .map_err(Error::Draw)?;
+        self.panel.flush().await?;
+        Ok(())
+    }
+
+    /// Draws `page`, shifted for burn-in protection according to `device.uptime_secs`
+    pub async fn render(&mut self, page: Page, status: &Status, device: &DeviceInfo) -> Result<(), Error<P::Error>> {
+        self.init().await?;
+        self.panel.clear_buffer();
+        let offset = self.config.shift(device.uptime_secs);
+        ui::render(&mut self.panel.translated(offset), self.text_style, page, status, device).map_err(Error::Draw)?;
+        self.panel.flush().await?;
+        Ok(())
+    }
+
+    pub async fn screensaver(&mut self, status: &Status, now_secs: u64) -> Result<(), Error<P::Error>> {
+        self.init().await?;
+        self.panel.clear_buffer();
+        ui::screensaver(&mut self.panel, self.text_style, now_secs, status).map_err(Error::Draw)?;
+        self.panel.flush().await?;
+        Ok(())
+    }
+
+    /// Shows `frame` at the contrast for its screen. `Frame::Off` only blanks the panel; the
+    /// firmware suspends it instead, which keeps what it showed. A failure leaves the frame
+    /// half drawn and is for the caller to log; the next `show` redraws it from scratch.
+    pub async fn show(&mut self, frame: Frame, status: &Status, device: &DeviceInfo) -> Result<(), Error<P::Error>> {
+        self.init().await?;
+        self.set_screen(frame.screen()).await?;
+        match frame {
+            Frame::Off => self.clear().await,
+            Frame::Screensaver => self.screensaver(status, device.uptime_secs).await,
+            Frame::Page(page, _) => self.render(page, status, device).await,
+        }
+    }
+}
+
+/// Turns the panel off, dropping it to a few uA; what was drawn comes back with it
+impl<P: Panel> Suspend for Display<P> {
+    type Error = DisplayError;
+
+    async fn suspend(&mut self) -> Result<(), DisplayError> {
+        self.panel.set_display_on(false).await
+    }
+
+    async fn resume(&mut self) -> Result<(), DisplayError> {
+        self.panel.set_display_on(true).await
     }
 }
 
@@ -63,7 +210,104 @@ impl<I2C: I2c> Display<I2C> {
 mod tests {
     use super::*;
 
+    use core::convert::Infallible;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+    use embedded_graphics::prelude::{OriginDimensions, Pixel, Size};
+    use crate::framebuffer::Framebuffer;
+
     #[test]
     fn it_works() {
     }
+
+    /// Nothing here ever waits, so a single poll finishes every future
+    fn block_on<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("panel future pending"),
+        }
+    }
+
+    /// A panel whose bus writes fail while `failing` is set, like one that's missing or loose
+    struct FlakyPanel {
+        framebuffer: Framebuffer<128, 64>,
+        failing: bool,
+        inits: usize,
+        flushes: usize,
+    }
+
+    impl FlakyPanel {
+        fn write(&self) -> Result<(), DisplayError> {
+            if self.failing { Err(DisplayError::BusWriteError) } else { Ok(()) }
+        }
+    }
+
+    impl OriginDimensions for FlakyPanel {
+        fn size(&self) -> Size {
+            self.framebuffer.size()
+        }
+    }
+
+    impl DrawTarget for FlakyPanel {
+        type Color = BinaryColor;
+        type Error = Infallible;
+
+        fn draw_iter<I: IntoIterator<Item = Pixel<Self::Color>>>(&mut self, pixels: I) -> Result<(), Self::Error> {
+            self.framebuffer.draw_iter(pixels)
+        }
+    }
+
+    impl Panel for FlakyPanel {
+        async fn init(&mut self) -> Result<(), DisplayError> {
+            self.write()?;
+            self.inits += 1;
+            Ok(())
+        }
+
+        fn clear_buffer(&mut self) {
+            self.framebuffer = Framebuffer::new();
+        }
+
+        async fn flush(&mut self) -> Result<(), DisplayError> {
+            self.write()?;
+            self.flushes += 1;
+            Ok(())
+        }
+
+        async fn set_contrast(&mut self, _contrast: u8) -> Result<(), DisplayError> {
+            self.write()
+        }
+
+        async fn set_display_on(&mut self, _on: bool) -> Result<(), DisplayError> {
+            self.write()
+        }
+    }
+
+    #[test]
+    fn keeps_going_when_the_panel_fails() {
+        let panel = FlakyPanel { framebuffer: Framebuffer::new(), failing: true, inits: 0, flushes: 0 };
+        let mut display = Display::new(panel, DisplayConfig::default());
+        let status = Status::default();
+        let device = DeviceInfo { node_id: 3, firmware: "0.1.0", uptime_secs: 60 };
+
+        // missing at boot: every frame fails, and each one tries to bring the panel up again
+        for _ in 0..3 {
+            let shown = block_on(display.show(Frame::Screensaver, &status, &device));
+            assert!(matches!(shown, Err(Error::Panel(DisplayError::BusWriteError))));
+        }
+        assert_eq!(display.panel().inits, 0);
+
+        display.panel.failing = false;
+        block_on(display.show(Frame::Screensaver, &status, &device)).unwrap();
+        assert_eq!(display.panel().inits, 1);
+        assert!(display.panel().framebuffer.lit() > 0);
+
+        // a flaky write later on only loses that frame, without initializing the panel again
+        display.panel.failing = true;
+        assert!(block_on(display.draw("hello")).is_err());
+        display.panel.failing = false;
+        block_on(display.draw("hello")).unwrap();
+        assert_eq!(display.panel().inits, 1);
+        assert_eq!(display.panel().flushes, 3);
+    }
 }
@@ -0,0 +1,82 @@
+//! Adafruit's 128x64 OLED FeatherWing, an SH1107 on I2C.
+
+use display_interface::DisplayError;
+use display_interface_i2c::I2CInterface;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, Pixel};
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::geometry::Dimensions;
+use embedded_hal_async::i2c::I2c;
+use oled_async::Builder;
+use oled_async::displayrotation::DisplayRotation;
+use oled_async::displays::sh1107::Sh1107_64_128;
+use oled_async::prelude::GraphicsMode;
+use crate::{Panel, Rotation};
+
+const PIXELS: usize = 128 * 64 / 8;
+
+type Graphics<I2C> = GraphicsMode<Sh1107_64_128, I2CInterface<I2C>, PIXELS>;
+
+pub struct Sh1107<I2C: I2c> {
+    display: Graphics<I2C>,
+}
+
+impl<I2C: I2c> Sh1107<I2C> {
+    /// The FeatherWing is at `DEFAULT_ADDRESS` and reads the right way up at `Rotation::Rotate90`
+    pub fn new(i2c: I2C, address: u8, rotation: Rotation) -> Self {
+        let di = I2CInterface::new(i2c, address, 0x40);
+        let raw_display = Builder::new(Sh1107_64_128 {})
+            .with_rotation(rotation.into())
+            .connect(di);
+        Self { display: raw_display.into() }
+    }
+}
+
+impl From<Rotation> for DisplayRotation {
+    fn from(rotation: Rotation) -> Self {
+        match rotation {
+            Rotation::Rotate0 => DisplayRotation::Rotate0,
+            Rotation::Rotate90 => DisplayRotation::Rotate90,
+            Rotation::Rotate180 => DisplayRotation::Rotate180,
+            Rotation::Rotate270 => DisplayRotation::Rotate270,
+        }
+    }
+}
+
+impl<I2C: I2c> Dimensions for Sh1107<I2C> {
+    fn bounding_box(&self) -> Rectangle {
+        self.display.bounding_box()
+    }
+}
+
+impl<I2C: I2c> DrawTarget for Sh1107<I2C> {
+    type Color = BinaryColor;
+    type Error = <Graphics<I2C> as DrawTarget>::Error;
+
+    fn draw_iter<I: IntoIterator<Item = Pixel<Self::Color>>>(&mut self, pixels: I) -> Result<(), Self::Error> {
+        self.display.draw_iter(pixels)
+    }
+}
+
+impl<I2C: I2c> Panel for Sh1107<I2C> {
+    async fn init(&mut self) -> Result<(), DisplayError> {
+        // reset is mapped appropriately by stacking the oled on top of the feather
+        self.display.init().await
+    }
+
+    fn clear_buffer(&mut self) {
+        self.display.clear();
+    }
+
+    async fn flush(&mut self) -> Result<(), DisplayError> {
+        self.display.flush().await
+    }
+
+    async fn set_contrast(&mut self, contrast: u8) -> Result<(), DisplayError> {
+        self.display.set_contrast(contrast).await
+    }
+
+    async fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        self.display.display_on(on).await
+    }
+}
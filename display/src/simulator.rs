@@ -0,0 +1,74 @@
+//! Host-side panel backed by embedded-graphics-simulator, for developing pages without hardware.
+
+use core::convert::Infallible;
+use display_interface::DisplayError;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, OriginDimensions, Pixel, Size};
+use embedded_graphics_simulator::SimulatorDisplay;
+use crate::Panel;
+
+pub struct Simulator {
+    display: SimulatorDisplay<BinaryColor>,
+    contrast: u8,
+    on: bool,
+}
+
+impl Simulator {
+    pub fn new(size: Size) -> Self {
+        Self { display: SimulatorDisplay::new(size), contrast: 0, on: true }
+    }
+
+    /// The framebuffer, for showing in a window or saving as an image
+    pub fn display(&self) -> &SimulatorDisplay<BinaryColor> {
+        &self.display
+    }
+
+    /// Last contrast set, which a real panel would show as brightness
+    pub fn contrast(&self) -> u8 {
+        self.contrast
+    }
+
+    /// Whether the panel is on; an off panel shows nothing whatever is in the framebuffer
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+}
+
+impl OriginDimensions for Simulator {
+    fn size(&self) -> Size {
+        self.display.size()
+    }
+}
+
+impl DrawTarget for Simulator {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I: IntoIterator<Item = Pixel<Self::Color>>>(&mut self, pixels: I) -> Result<(), Self::Error> {
+        self.display.draw_iter(pixels)
+    }
+}
+
+impl Panel for Simulator {
+    async fn init(&mut self) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    fn clear_buffer(&mut self) {
+        let _ = self.display.clear(BinaryColor::Off);
+    }
+
+    async fn flush(&mut self) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    async fn set_contrast(&mut self, contrast: u8) -> Result<(), DisplayError> {
+        self.contrast = contrast;
+        Ok(())
+    }
+
+    async fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        self.on = on;
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+//! SSD1306 panels on I2C, such as Adafruit's 128x32 OLED FeatherWing.
+
+use display_interface::DisplayError;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, OriginDimensions, Pixel, Size};
+use embedded_hal_async::i2c::I2c;
+use ssd1306::mode::BufferedGraphicsModeAsync;
+use ssd1306::prelude::{Brightness, DisplayConfigAsync, DisplayRotation, I2CInterface};
+use ssd1306::size::DisplaySizeAsync;
+use ssd1306::{I2CDisplayInterface, Ssd1306Async};
+use crate::{Panel, Rotation};
+
+pub use ssd1306::size::{DisplaySize128x32, DisplaySize128x64};
+
+// the datasheet's default precharge; contrast alone sets the brightness
+const PRECHARGE: u8 = 0x2;
+
+pub struct Ssd1306<I2C, SIZE: DisplaySizeAsync> {
+    display: Ssd1306Async<I2CInterface<I2C>, SIZE, BufferedGraphicsModeAsync<SIZE>>,
+}
+
+impl<I2C: I2c, SIZE: DisplaySizeAsync> Ssd1306<I2C, SIZE> {
+    pub fn new(i2c: I2C, size: SIZE, address: u8, rotation: Rotation) -> Self {
+        let di = I2CDisplayInterface::new_custom_address(i2c, address);
+        let display = Ssd1306Async::new(di, size, rotation.into()).into_buffered_graphics_mode();
+        Self { display }
+    }
+}
+
+impl From<Rotation> for DisplayRotation {
+    fn from(rotation: Rotation) -> Self {
+        match rotation {
+            Rotation::Rotate0 => DisplayRotation::Rotate0,
+            Rotation::Rotate90 => DisplayRotation::Rotate90,
+            Rotation::Rotate180 => DisplayRotation::Rotate180,
+            Rotation::Rotate270 => DisplayRotation::Rotate270,
+        }
+    }
+}
+
+impl<I2C: I2c, SIZE: DisplaySizeAsync> OriginDimensions for Ssd1306<I2C, SIZE> {
+    fn size(&self) -> Size {
+        self.display.size()
+    }
+}
+
+impl<I2C: I2c, SIZE: DisplaySizeAsync> DrawTarget for Ssd1306<I2C, SIZE> {
+    type Color = BinaryColor;
+    type Error = DisplayError;
+
+    fn draw_iter<I: IntoIterator<Item = Pixel<Self::Color>>>(&mut self, pixels: I) -> Result<(), Self::Error> {
+        self.display.draw_iter(pixels)
+    }
+}
+
+impl<I2C: I2c, SIZE: DisplaySizeAsync> Panel for Ssd1306<I2C, SIZE> {
+    async fn init(&mut self) -> Result<(), DisplayError> {
+        self.display.init().await
+    }
+
+    fn clear_buffer(&mut self) {
+        self.display.clear_buffer();
+    }
+
+    async fn flush(&mut self) -> Result<(), DisplayError> {
+        self.display.flush().await
+    }
+
+    async fn set_contrast(&mut self, contrast: u8) -> Result<(), DisplayError> {
+        self.display.set_brightness(Brightness::custom(PRECHARGE, contrast)).await
+    }
+
+    async fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        self.display.set_display_on(on).await
+    }
+}
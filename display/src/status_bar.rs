@@ -0,0 +1,225 @@
+//! A strip along the bottom of every page showing the node's health at a glance.
+//!
+//! Sensor and radio failures otherwise only reach the USB log, which is rarely attached in the
+//! field. Each source gets an icon, drawn inverted while its latest result is a failure, with a
+//! count of failures since boot beside it.
+
+use core::fmt::Write;
+use embedded_graphics::Drawable;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::ascii::FONT_4X6;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, Point, Primitive, Size};
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use heapless::String;
+use crate::bitmap;
+use crate::ui::Status;
+
+pub const HEIGHT: u32 = 6;
+// the largest error count shown; beyond it the badge stays at 999
+const MAX_BADGE: u32 = 999;
+
+const RADIO: [&str; 6] = [
+    "#.#.#",
+    "#.#.#",
+    ".###.",
+    "..#..",
+    "..#..",
+    "..#..",
+];
+
+const THERMOMETER: [&str; 6] = [
+    ".#.",
+    ".#.",
+    ".#.",
+    ".#.",
+    "###",
+    "###",
+];
+
+const PARTICULATES: [&str; 6] = [
+    "#...#",
+    "..#..",
+    "#...#",
+    "..#..",
+    "#...#",
+    "..#..",
+];
+
+const USB: [&str; 6] = [
+    ".###.",
+    ".#.#.",
+    "#####",
+    "#####",
+    ".###.",
+    "..#..",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sensor {
+    TempHumidity,
+    AirQuality,
+}
+
+/// Results of one sensor's reads since boot
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SensorHealth {
+    /// `None` until the first read
+    pub last_ok: Option<bool>,
+    pub errors: u32,
+}
+
+impl SensorHealth {
+    pub fn read(&mut self, ok: bool) {
+        self.last_ok = Some(ok);
+        if !ok {
+            self.errors += 1;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Health {
+    pub temp_humidity: SensorHealth,
+    pub air_quality: SensorHealth,
+    /// A USB host is attached, so the log can be read
+    pub usb: bool,
+    /// State of charge; `None` hides the gauge, e.g. with no battery fitted
+    pub battery_percent: Option<u8>,
+}
+
+impl Health {
+    pub fn sensor(&mut self, sensor: Sensor, ok: bool) {
+        match sensor {
+            Sensor::TempHumidity => self.temp_humidity.read(ok),
+            Sensor::AirQuality => self.air_quality.read(ok),
+        }
+    }
+}
+
+/// Draws `icon` at `x`, inverted if `last_ok` is a failure, then its error count; returns where the
+/// next item starts
+fn item<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    icon: &[&str],
+    position: Point,
+    last_ok: Option<bool>,
+    errors: u32,
+) -> Result<i32, D::Error> {
+    let width = icon.first().map_or(0, |row| row.len()) as i32;
+    let mut color = BinaryColor::On;
+    if last_ok == Some(false) {
+        Rectangle::new(position - Point::new(1, 0), Size::new(width as u32 + 2, HEIGHT))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(target)?;
+        color = BinaryColor::Off;
+    }
+    bitmap::draw(target, icon, position, color)?;
+
+    let mut x = position.x + width + 2;
+    if errors > 0 {
+        let mut badge: String<3> = String::new();
+        let _ = write!(badge, "{}", errors.min(MAX_BADGE));
+        let style = MonoTextStyle::new(&FONT_4X6, BinaryColor::On);
+        x = Text::with_baseline(&badge, Point::new(x, position.y), style, Baseline::Top).draw(target)?.x;
+    }
+    Ok(x + 3)
+}
+
+fn battery<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    top_right: Point,
+    percent: u8,
+) -> Result<(), D::Error> {
+    let left = top_right.x - 12;
+    Rectangle::new(Point::new(left, top_right.y), Size::new(11, HEIGHT))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(target)?;
+    // the terminal nub
+    Rectangle::new(Point::new(left + 11, top_right.y + 2), Size::new(1, 2))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(target)?;
+    let level = (u32::from(percent.min(100)) * 9 + 50) / 100;
+    Rectangle::new(Point::new(left + 1, top_right.y + 1), Size::new(level, HEIGHT - 2))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(target)?;
+    Ok(())
+}
+
+/// Draws the bar into `area`, which should be `HEIGHT` tall: radio and sensors from the left,
+/// USB and battery from the right
+pub fn draw<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    status: &Status,
+    area: Rectangle,
+) -> Result<(), D::Error> {
+    let health = &status.health;
+    let y = area.top_left.y;
+    // one pixel in, so an inverted icon's border isn't lost off the edge
+    let mut x = area.top_left.x + 1;
+    x = item(target, &RADIO, Point::new(x, y), status.radio.last_tx_ok, status.radio.failed)?;
+    x = item(target, &THERMOMETER, Point::new(x, y), health.temp_humidity.last_ok, health.temp_humidity.errors)?;
+    item(target, &PARTICULATES, Point::new(x, y), health.air_quality.last_ok, health.air_quality.errors)?;
+
+    let mut right = area.top_left.x + area.size.width as i32;
+    if let Some(percent) = health.battery_percent {
+        battery(target, Point::new(right, y), percent)?;
+        right -= 14;
+    }
+    if health.usb {
+        bitmap::draw(target, &USB, Point::new(right - USB[0].len() as i32, y), BinaryColor::On)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::Framebuffer;
+
+    #[test]
+    fn counts_sensor_errors() {
+        let mut health = Health::default();
+        health.sensor(Sensor::AirQuality, false);
+        health.sensor(Sensor::AirQuality, true);
+        health.sensor(Sensor::TempHumidity, true);
+        assert_eq!(health.air_quality, SensorHealth { last_ok: Some(true), errors: 1 });
+        assert_eq!(health.temp_humidity, SensorHealth { last_ok: Some(true), errors: 0 });
+    }
+
+    #[test]
+    fn bar_snapshot() {
+        let mut status = Status::default();
+        status.radio.tx(true);
+        status.radio.tx(false);
+        status.health.sensor(Sensor::AirQuality, false);
+        status.health.sensor(Sensor::TempHumidity, true);
+        status.health.usb = true;
+        status.health.battery_percent = Some(50);
+
+        let mut fb = Framebuffer::<64, 6>::new();
+        draw(&mut fb, &status, Rectangle::new(Point::zero(), Size::new(64, HEIGHT))).unwrap();
+        // radio and PM inverted with a failure each, USB attached and the battery half full
+        assert_eq!(fb.snapshot(), [
+            "#.#.#.#..#......#.....#.###.#..#..............###...###########.",
+            "#.#.#.#.##......#.....###.###.##..............#.#...######....#.",
+            "##...##..#......#.....#.###.#..#.............#####..######....##",
+            "###.###..#......#.....###.###..#.............#####..######....##",
+            "###.###.###....###....#.###.#.###.............###...######....#.",
+            "###.###........###....###.###..................#....###########.",
+        ].join("\n"));
+        assert_eq!(fb.out_of_bounds, 0);
+    }
+
+    #[test]
+    fn badge_caps_at_three_digits() {
+        let mut status = Status::default();
+        for _ in 0..1_500 {
+            status.radio.tx(false);
+        }
+        let mut fb = Framebuffer::<40, 6>::new();
+        draw(&mut fb, &status, Rectangle::new(Point::zero(), Size::new(40, HEIGHT))).unwrap();
+        assert_eq!(fb.out_of_bounds, 0);
+    }
+}
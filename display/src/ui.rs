@@ -0,0 +1,620 @@
+//! Page-based UI driven by the FeatherWing's A/B/C buttons.
+//!
+//! Everything here is independent of the panel so it can be exercised on the host against any
+//! `DrawTarget`; `Display::render` is the thin wrapper that flushes it to the OLED. The layout is
+//! worked out from the font and the target's size, so the same pages fit a 128x32 panel.
+
+use core::fmt::Write;
+use core::mem::discriminant;
+use air_quality::aqi::aqi;
+use embedded_graphics::Drawable;
+use embedded_graphics::draw_target::DrawTargetExt;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::geometry::Dimensions;
+use embedded_graphics::prelude::{DrawTarget, Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::{Baseline, Text};
+use heapless::{String, Vec};
+use psychrometrics::Psychrometrics;
+use telemetry::{Alert, EnvReading};
+use crate::config::MAX_PIXEL_SHIFT;
+use crate::glance::{self, Metric};
+use crate::status_bar::{self, Health};
+use crate::graph::{chart, Chart, History, Series};
+
+pub const MAX_LINES: usize = 4;
+// room for the narrowest font, 6px wide, across 128 pixels; `render` cuts each line to what the
+// style's font fits
+pub const LINE_LEN: usize = 21;
+// the screensaver readout jumps somewhere new this often
+const SCREENSAVER_MOVE_SECS: u64 = 10;
+
+pub type Line = String<LINE_LEN>;
+pub type Lines = Vec<Line, MAX_LINES>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Button {
+    /// Previous page
+    A,
+    /// Select: the current page's action
+    B,
+    /// Next page
+    C,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Page {
+    /// One metric in large digits, chosen with B
+    Glance(Metric),
+    Readings,
+    AirQuality,
+    /// Dew point, heat index and absolute humidity
+    Comfort,
+    MinMax,
+    TemperatureHistory,
+    HumidityHistory,
+    Pm2_5History,
+    Radio,
+    Device,
+}
+
+impl Page {
+    pub const ALL: [Page; 10] = [
+        Page::Glance(Metric::Aqi),
+        Page::Readings,
+        Page::AirQuality,
+        Page::Comfort,
+        Page::MinMax,
+        Page::TemperatureHistory,
+        Page::HumidityHistory,
+        Page::Pm2_5History,
+        Page::Radio,
+        Page::Device,
+    ];
+
+    fn index(&self) -> usize {
+        // the glance page is one page whichever metric it features
+        Self::ALL.iter().position(|p| discriminant(p) == discriminant(self)).unwrap_or(0)
+    }
+
+    pub fn next(&self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(&self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Page::Glance(_) => "Glance",
+            Page::Readings => "Readings",
+            Page::AirQuality => "Air Quality",
+            Page::Comfort => "Comfort",
+            Page::MinMax => "Min/Max",
+            Page::TemperatureHistory => "Temp 1h",
+            Page::HumidityHistory => "RH 1h",
+            Page::Pm2_5History => "PM2.5 1h",
+            Page::Radio => "Radio",
+            Page::Device => "Device",
+        }
+    }
+}
+
+/// What the caller should do after a button press
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    Redraw,
+    /// Blank the panel; the next press wakes it on the same page
+    Blank,
+    /// Clear the min/max since boot, then redraw
+    ResetMinMax,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ui {
+    page: Page,
+    awake: bool,
+    /// Featured on the glance page, remembered while on other pages
+    metric: Metric,
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        // the panel starts blank, as it did before there were pages
+        Self { page: Page::Glance(Metric::Aqi), awake: false, metric: Metric::Aqi }
+    }
+}
+
+impl Ui {
+    pub fn page(&self) -> Page {
+        self.page
+    }
+
+    pub fn is_awake(&self) -> bool {
+        self.awake
+    }
+
+    /// The panel went dark for lack of presses; like `Effect::Blank`, the next press only wakes it
+    pub fn timeout(&mut self) {
+        self.awake = false;
+    }
+
+    /// An alert was raised; wakes the panel on the glance page for the reading it's on
+    pub fn alarm(&mut self, metric: Metric) {
+        self.awake = true;
+        self.metric = metric;
+        self.page = Page::Glance(metric);
+    }
+
+    pub fn press(&mut self, button: Button) -> Effect {
+        // a blank panel wakes on the page it was left on, whichever button is pressed
+        if !self.awake {
+            self.awake = true;
+            return Effect::Redraw;
+        }
+        match (button, self.page) {
+            (Button::A, _) => self.page = self.page.previous(),
+            (Button::C, _) => self.page = self.page.next(),
+            (Button::B, Page::Glance(_)) => self.metric = self.metric.next(),
+            (Button::B, Page::MinMax) => return Effect::ResetMinMax,
+            (Button::B, _) => {
+                self.awake = false;
+                return Effect::Blank;
+            }
+        }
+        if let Page::Glance(_) = self.page {
+            self.page = Page::Glance(self.metric);
+        }
+        Effect::Redraw
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    pub min: u16,
+    pub max: u16,
+}
+
+impl Range {
+    fn new(value: u16) -> Self {
+        Self { min: value, max: value }
+    }
+
+    fn add(&mut self, value: u16) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+}
+
+fn add(range: &mut Option<Range>, value: u16) {
+    match range {
+        Some(range) => range.add(value),
+        None => *range = Some(Range::new(value)),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MinMax {
+    pub temperature: Option<Range>,
+    pub humidity: Option<Range>,
+    pub pm2_5: Option<Range>,
+}
+
+impl MinMax {
+    pub fn add(&mut self, reading: &EnvReading) {
+        add(&mut self.temperature, reading.temperature);
+        add(&mut self.humidity, reading.humidity);
+        add(&mut self.pm2_5, reading.aq_pm2_5);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RadioStatus {
+    /// `None` until the first transmission
+    pub last_tx_ok: Option<bool>,
+    pub sent: u32,
+    pub failed: u32,
+}
+
+impl RadioStatus {
+    pub fn tx(&mut self, ok: bool) {
+        self.last_tx_ok = Some(ok);
+        if ok {
+            self.sent += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+}
+
+/// Everything the pages show that the node collects between button presses
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Status {
+    pub reading: Option<EnvReading>,
+    /// Set by the owner when no reading has arrived for longer than it expects; every page is
+    /// marked so an old reading isn't mistaken for a live one
+    pub stale: bool,
+    /// Set by the owner when the reading exceeds its `Thresholds`; the glance page flashes a warning
+    pub alert: bool,
+    /// Set by the owner while an alert from the node's alert rules is raised; flashes the same
+    /// warning
+    pub alarm: Option<Alert>,
+    pub min_max: MinMax,
+    pub history: History,
+    pub radio: RadioStatus,
+    pub health: Health,
+}
+
+impl Status {
+    /// Records a reading taken `now_secs` after boot
+    pub fn reading(&mut self, now_secs: u64, reading: EnvReading) {
+        self.min_max.add(&reading);
+        self.history.add(now_secs, &reading);
+        self.reading = Some(reading);
+    }
+
+    fn history(&self, page: Page) -> Option<(&Series, Chart)> {
+        match page {
+            Page::TemperatureHistory => Some((&self.history.temperature, Chart::Sparkline)),
+            Page::HumidityHistory => Some((&self.history.humidity, Chart::Sparkline)),
+            // particulates come in bursts, which read better as bars
+            Page::Pm2_5History => Some((&self.history.pm2_5, Chart::Bars)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeviceInfo {
+    pub node_id: u16,
+    pub firmware: &'static str,
+    pub uptime_secs: u64,
+}
+
+fn push(lines: &mut Lines, args: core::fmt::Arguments) {
+    let mut line = Line::new();
+    // overlong lines are truncated rather than wrapped off the bottom of the panel
+    let _ = line.write_fmt(args);
+    let _ = lines.push(line);
+}
+
+fn range(lines: &mut Lines, label: &str, range: Option<Range>, unit: &str) {
+    match range {
+        Some(r) => push(lines, format_args!("{} {}-{}{}", label, r.min, r.max, unit)),
+        None => push(lines, format_args!("{} --", label)),
+    }
+}
+
+/// The text of `page`, title first
+pub fn lines(page: Page, status: &Status, device: &DeviceInfo) -> Lines {
+    let mut lines = Lines::new();
+    let marker = if status.stale { '!' } else { ' ' };
+    push(&mut lines, format_args!("{:<12}{}{}/{}", page.title(), marker, page.index() + 1, Page::ALL.len()));
+
+    match (page, &status.reading) {
+        (Page::Glance(_) | Page::Readings | Page::AirQuality | Page::Comfort, None) => push(&mut lines, format_args!("No reading yet")),
+        (Page::Glance(metric), Some(r)) => {
+            push(&mut lines, format_args!("{} {}{}", metric.name(), metric.value(r), metric.unit()));
+        }
+        (Page::Readings, Some(r)) => {
+            push(&mut lines, format_args!("{}F {}%RH", r.temperature, r.humidity));
+            push(&mut lines, format_args!("PM1.0 {}", r.aq_pm1_0));
+            push(&mut lines, format_args!("PM2.5 {} PM10 {}", r.aq_pm2_5, r.aq_pm10));
+        }
+        (Page::AirQuality, Some(r)) => {
+            let aqi = aqi(r.aq_pm2_5, r.aq_pm10);
+            push(&mut lines, format_args!("AQI {}", aqi.value));
+            push(&mut lines, format_args!("{}", aqi.category.label()));
+        }
+        (Page::Comfort, Some(r)) => {
+            let derived = Psychrometrics::new(r.temperature, r.humidity);
+            let absolute = derived.absolute_humidity_dg_m3;
+            push(&mut lines, format_args!("Dew point {}F", derived.dew_point_f));
+            push(&mut lines, format_args!("Heat index {}F", derived.heat_index_f));
+            push(&mut lines, format_args!("Abs hum {}.{}g/m3", absolute / 10, absolute % 10));
+        }
+        (Page::TemperatureHistory | Page::HumidityHistory | Page::Pm2_5History, _) => {
+            if status.history(page).is_some_and(|(series, _)| series.is_empty()) {
+                push(&mut lines, format_args!("No history yet"));
+            }
+        }
+        (Page::MinMax, _) => {
+            range(&mut lines, "T", status.min_max.temperature, "F");
+            range(&mut lines, "RH", status.min_max.humidity, "%");
+            range(&mut lines, "PM2.5", status.min_max.pm2_5, "");
+        }
+        (Page::Radio, _) => {
+            let last = match status.radio.last_tx_ok {
+                None => "--",
+                Some(true) => "ok",
+                Some(false) => "failed",
+            };
+            push(&mut lines, format_args!("last tx {}", last));
+            push(&mut lines, format_args!("sent {}", status.radio.sent));
+            push(&mut lines, format_args!("failed {}", status.radio.failed));
+        }
+        (Page::Device, _) => {
+            let up = device.uptime_secs;
+            push(&mut lines, format_args!("node {}", device.node_id));
+            push(&mut lines, format_args!("fw {}", device.firmware));
+            push(&mut lines, format_args!("up {}d {:02}:{:02}:{:02}", up / 86_400, up / 3_600 % 24, up / 60 % 60, up % 60));
+        }
+    }
+    lines
+}
+
+/// Draws `page` onto `target`, which the caller is expected to have cleared
+pub fn render<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    style: MonoTextStyle<'_, BinaryColor>,
+    page: Page,
+    status: &Status,
+    device: &DeviceInfo,
+) -> Result<(), D::Error> {
+    // the status bar sits along the bottom, clear of the pixel shift; pages lay out above it
+    let bounds = target.bounding_box().size;
+    let shift = u32::from(MAX_PIXEL_SHIFT);
+    let bar_top = bounds.height.saturating_sub(shift + status_bar::HEIGHT);
+    let bar = Rectangle::new(Point::new(0, bar_top as i32), Size::new(bounds.width - shift, status_bar::HEIGHT));
+    status_bar::draw(target, status, bar)?;
+    let page_area = Rectangle::new(Point::zero(), Size::new(bounds.width, (bar_top + shift).saturating_sub(1)));
+    let target = &mut target.clipped(&page_area);
+
+    if let Page::Glance(metric) = page {
+        return glance::draw(target, metric, status, device.uptime_secs);
+    }
+    let bounds = target.bounding_box().size;
+    let line_height = style.font.character_size.height;
+    // lines and characters that don't fit are dropped, leaving room to be pixel shifted
+    let rows = bounds.height.saturating_sub(MAX_PIXEL_SHIFT.into()) / line_height.max(1);
+    let advance = style.font.character_size.width + style.font.character_spacing;
+    let columns = bounds.width.saturating_sub(MAX_PIXEL_SHIFT.into()) / advance.max(1);
+    for (i, line) in lines(page, status, device).iter().take(rows as usize).enumerate() {
+        let line = line.char_indices().nth(columns as usize).map_or(line.as_str(), |(end, _)| &line[..end]);
+        Text::with_baseline(line, Point::new(0, (i as u32 * line_height) as i32), style, Baseline::Top)
+            .draw(target)?;
+    }
+    if let Some((series, kind)) = status.history(page) {
+        chart(target, chart_area(bounds, line_height), kind, series.points())?;
+    }
+    Ok(())
+}
+
+/// History pages put their chart under the title line, leaving room to be pixel shifted
+fn chart_area(bounds: Size, line_height: u32) -> Rectangle {
+    let shift = u32::from(MAX_PIXEL_SHIFT);
+    Rectangle::new(
+        Point::new(0, line_height as i32 + 1),
+        Size::new(
+            bounds.width.saturating_sub(shift),
+            bounds.height.saturating_sub(line_height + 1 + shift),
+        ),
+    )
+}
+
+/// A single short readout at a position that changes every `SCREENSAVER_MOVE_SECS`, so the panel
+/// shows something without lighting the same pixels for long
+pub fn screensaver<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    style: MonoTextStyle<'_, BinaryColor>,
+    now_secs: u64,
+    status: &Status,
+) -> Result<(), D::Error> {
+    let mut text = Line::new();
+    let _ = match &status.reading {
+        Some(r) => write!(text, "{}F AQI {}", r.temperature, aqi(r.aq_pm2_5, r.aq_pm10).value),
+        None => write!(text, "--"),
+    };
+    let size = Text::with_baseline(&text, Point::zero(), style, Baseline::Top).bounding_box().size;
+    let bounds = target.bounding_box().size;
+    let free_x = u64::from(bounds.width.saturating_sub(size.width)) + 1;
+    let free_y = u64::from(bounds.height.saturating_sub(size.height)) + 1;
+    // Knuth's multiplicative hash scatters consecutive steps across the panel
+    let hash = (now_secs / SCREENSAVER_MOVE_SECS).wrapping_mul(2_654_435_761);
+    let position = Point::new((hash % free_x) as i32, (hash / free_x % free_y) as i32);
+    Text::with_baseline(&text, position, style, Baseline::Top).draw(target)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::mono_font::ascii::{FONT_6X10, FONT_7X13};
+    use crate::framebuffer::Framebuffer;
+
+    fn style() -> MonoTextStyle<'static, BinaryColor> {
+        MonoTextStyle::new(&FONT_7X13, BinaryColor::On)
+    }
+
+    fn device() -> DeviceInfo {
+        DeviceInfo { node_id: 3, firmware: "0.1.0", uptime_secs: 93_784 }
+    }
+
+    fn status() -> Status {
+        let mut status = Status::default();
+        status.reading(0, EnvReading { aq_pm2_5: 12, aq_pm10: 15, humidity: 48, temperature: 68, aq_pm1_0: 8 });
+        status.reading(60, EnvReading { aq_pm2_5: 30, aq_pm10: 40, humidity: 45, temperature: 71, aq_pm1_0: 20 });
+        status.radio.tx(true);
+        status.radio.tx(false);
+        status
+    }
+
+    fn assert_lines(page: Page, status: &Status, expected: &[&str]) {
+        let lines = lines(page, status, &device());
+        let actual: Vec<&str, MAX_LINES> = lines.iter().map(|l| l.as_str()).collect();
+        assert_eq!(actual.as_slice(), expected);
+    }
+
+    #[test]
+    fn wakes_on_first_press_without_navigating() {
+        let mut ui = Ui::default();
+        assert!(!ui.is_awake());
+        assert_eq!(ui.press(Button::C), Effect::Redraw);
+        assert!(ui.is_awake());
+        assert_eq!(ui.page(), Page::Glance(Metric::Aqi));
+    }
+
+    #[test]
+    fn previous_and_next_wrap() {
+        let mut ui = Ui::default();
+        ui.press(Button::B);
+        ui.press(Button::A);
+        assert_eq!(ui.page(), Page::Device);
+        ui.press(Button::C);
+        ui.press(Button::C);
+        assert_eq!(ui.page(), Page::Readings);
+    }
+
+    #[test]
+    fn select_cycles_glance_metric_and_remembers_it() {
+        let mut ui = Ui::default();
+        ui.press(Button::B);
+        assert_eq!(ui.press(Button::B), Effect::Redraw);
+        assert_eq!(ui.page(), Page::Glance(Metric::Pm2_5));
+        ui.press(Button::B);
+        ui.press(Button::C);
+        assert_eq!(ui.page(), Page::Readings);
+        ui.press(Button::A);
+        assert_eq!(ui.page(), Page::Glance(Metric::Pm10));
+        assert_lines(ui.page(), &status(), &["Glance       1/10", "PM10 40ug/m3"]);
+    }
+
+    #[test]
+    fn select_blanks_or_resets_min_max() {
+        let mut ui = Ui::default();
+        ui.press(Button::B);
+        ui.press(Button::A);
+        assert_eq!(ui.press(Button::B), Effect::Blank);
+        assert!(!ui.is_awake());
+        ui.press(Button::B);
+        assert_eq!(ui.page(), Page::Device);
+
+        for _ in 0..5 {
+            ui.press(Button::C);
+        }
+        assert_eq!(ui.page(), Page::MinMax);
+        assert_eq!(ui.press(Button::B), Effect::ResetMinMax);
+        assert!(ui.is_awake());
+    }
+
+    #[test]
+    fn page_text() {
+        let status = status();
+        assert_lines(Page::Readings, &status, &["Readings     2/10", "71F 45%RH", "PM1.0 20", "PM2.5 30 PM10 40"]);
+        assert_lines(Page::AirQuality, &status, &["Air Quality  3/10", "AQI 90", "Moderate"]);
+        assert_lines(Page::Comfort, &status, &["Comfort      4/10", "Dew point 49F", "Heat index 70F", "Abs hum 8.5g/m3"]);
+        assert_lines(Page::MinMax, &status, &["Min/Max      5/10", "T 68-71F", "RH 45-48%", "PM2.5 12-30"]);
+        assert_lines(Page::Radio, &status, &["Radio        9/10", "last tx failed", "sent 1", "failed 1"]);
+        assert_lines(Page::Device, &status, &["Device       10/10", "node 3", "fw 0.1.0", "up 1d 02:03:04"]);
+    }
+
+    #[test]
+    fn page_text_before_first_reading() {
+        let status = Status::default();
+        assert_lines(Page::Readings, &status, &["Readings     2/10", "No reading yet"]);
+        assert_lines(Page::MinMax, &status, &["Min/Max      5/10", "T --", "RH --", "PM2.5 --"]);
+        assert_lines(Page::Radio, &status, &["Radio        9/10", "last tx --", "sent 0", "failed 0"]);
+    }
+
+    #[test]
+    fn history_pages() {
+        assert_lines(Page::Pm2_5History, &Status::default(), &["PM2.5 1h     8/10", "No history yet"]);
+        assert_lines(Page::TemperatureHistory, &status(), &["Temp 1h      6/10"]);
+
+        let mut fb = Framebuffer::<128, 64>::new();
+        render(&mut fb, style(), Page::TemperatureHistory, &status(), &device()).unwrap();
+        // the chart and its labels fill the space below the title
+        let snapshot = fb.snapshot();
+        assert!(snapshot.lines().skip(FONT_7X13.character_size.height as usize + 1).any(|row| row.contains('#')));
+    }
+
+    #[test]
+    fn stale_marker() {
+        let mut status = status();
+        status.stale = true;
+        assert_lines(Page::Readings, &status, &["Readings    !2/10", "71F 45%RH", "PM1.0 20", "PM2.5 30 PM10 40"]);
+        assert_lines(Page::Device, &status, &["Device      !10/10", "node 3", "fw 0.1.0", "up 1d 02:03:04"]);
+    }
+
+    #[test]
+    fn every_page_fits_the_panel() {
+        let status = status();
+        let shift = Point::new(MAX_PIXEL_SHIFT.into(), MAX_PIXEL_SHIFT.into());
+        for page in Page::ALL {
+            for offset in [Point::zero(), shift] {
+                let mut fb = Framebuffer::<128, 64>::new();
+                render(&mut fb.translated(offset), style(), page, &status, &device()).unwrap();
+                assert!(fb.lit() > 0, "{:?} drew nothing", page);
+                assert_eq!(fb.out_of_bounds, 0, "{:?} drew off the panel at {:?}", page, offset);
+            }
+        }
+    }
+
+    #[test]
+    fn long_lines_stop_at_a_whole_character() {
+        let device = DeviceInfo { firmware: "0.1.0-rc1+build.1234", ..device() };
+        let mut fb = Framebuffer::<128, 64>::new();
+        render(&mut fb, style(), Page::Device, &status(), &device).unwrap();
+        // 18 of FONT_7X13's 7px characters fit, clear of the pixel shift
+        let width = 18 * FONT_7X13.character_size.width as usize;
+        assert!(fb.snapshot().lines().all(|row| !row[width..].contains('#')));
+    }
+
+    #[test]
+    fn every_page_fits_a_short_panel() {
+        let status = status();
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let shift = Point::new(MAX_PIXEL_SHIFT.into(), MAX_PIXEL_SHIFT.into());
+        for page in Page::ALL {
+            for offset in [Point::zero(), shift] {
+                let mut fb = Framebuffer::<128, 32>::new();
+                render(&mut fb.translated(offset), style, page, &status, &device()).unwrap();
+                assert!(fb.lit() > 0, "{:?} drew nothing", page);
+                assert_eq!(fb.out_of_bounds, 0, "{:?} drew off the panel at {:?}", page, offset);
+            }
+        }
+        // the title and two lines fit, with the chart squeezed in below the title
+        let mut fb = Framebuffer::<128, 32>::new();
+        render(&mut fb, style, Page::Pm2_5History, &status, &device()).unwrap();
+        assert!(fb.snapshot().lines().skip(11).any(|row| row.contains('#')));
+    }
+
+    #[test]
+    fn alarm_wakes_on_its_metric() {
+        let mut ui = Ui::default();
+        ui.alarm(Metric::Humidity);
+        assert!(ui.is_awake());
+        assert_eq!(ui.page(), Page::Glance(Metric::Humidity));
+        // the glance page keeps it once the alarm has been looked at
+        ui.press(Button::C);
+        ui.press(Button::A);
+        assert_eq!(ui.page(), Page::Glance(Metric::Humidity));
+    }
+
+    #[test]
+    fn timeout_wakes_without_navigating() {
+        let mut ui = Ui::default();
+        ui.press(Button::A);
+        ui.press(Button::C);
+        ui.timeout();
+        assert!(!ui.is_awake());
+        assert_eq!(ui.press(Button::C), Effect::Redraw);
+        assert_eq!(ui.page(), Page::Readings);
+    }
+
+    #[test]
+    fn screensaver_moves_and_fits() {
+        let status = status();
+        let mut snapshots = std::vec::Vec::new();
+        for t in (0..100).step_by(SCREENSAVER_MOVE_SECS as usize) {
+            let mut fb = Framebuffer::<128, 64>::new();
+            screensaver(&mut fb, style(), t, &status).unwrap();
+            assert_eq!(fb.out_of_bounds, 0, "off the panel at {}s", t);
+            snapshots.push(fb.snapshot());
+        }
+        snapshots.dedup();
+        assert!(snapshots.len() > 5, "screensaver barely moved");
+    }
+}
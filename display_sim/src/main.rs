@@ -0,0 +1,166 @@
+mod readings;
+mod sim;
+mod snapshot;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand};
+use display::status_bar::Sensor;
+use embedded_graphics_simulator::{BinaryColorTheme, OutputSettingsBuilder};
+use telemetry::EnvReading;
+use crate::readings::{replay, synthetic, READ_INTERVAL_SECS};
+use crate::sim::{PanelKind, Sim};
+
+/// Render the node's display pages on the desktop, without flashing a board
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    #[arg(long, value_enum, default_value_t, global = true)]
+    panel: PanelKind,
+    /// Replay readings from a capture of gateway JSON lines or hex dumps instead of synthetic ones
+    #[arg(long, global = true)]
+    replay: Option<PathBuf>,
+    /// Node to take replayed readings from; the first one heard by default
+    #[arg(long, global = true)]
+    node: Option<u16>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write every page as a PNG for review
+    Png {
+        out: PathBuf,
+        #[arg(long, default_value_t = 4)]
+        scale: u32,
+    },
+    /// Compare every page, on every panel, against the checked-in snapshots
+    Snapshot {
+        #[arg(long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/snapshots"))]
+        dir: PathBuf,
+        /// Overwrite the snapshots with what is drawn now
+        #[arg(long)]
+        update: bool,
+    },
+    /// Open a window; a/b/c or left/space/right press the buttons, escape quits
+    #[cfg(feature = "window")]
+    Window {
+        /// Simulated seconds per real second
+        #[arg(long, default_value_t = 1)]
+        speed: u64,
+    },
+}
+
+/// Feeds `history_secs` of readings, one per read interval, with every 25th send and every 40th
+/// particulate read failing, and a USB host attached
+fn prime(sim: &mut Sim, readings: impl Iterator<Item = EnvReading>, history_secs: u64) {
+    sim.health().usb = true;
+    let start = sim.now_secs();
+    for (i, reading) in readings.take((history_secs / READ_INTERVAL_SECS) as usize).enumerate() {
+        sim.tick(start + i as u64 * READ_INTERVAL_SECS);
+        sim.reading(reading);
+        sim.tx(i % 25 != 24);
+        sim.health().sensor(Sensor::TempHumidity, true);
+        sim.health().sensor(Sensor::AirQuality, i % 40 != 39);
+    }
+}
+
+fn readings(cli: &Cli) -> Result<Box<dyn Iterator<Item = EnvReading>>> {
+    Ok(match &cli.replay {
+        // a short capture is looped so there's always another reading
+        Some(path) => Box::new(replay(path, cli.node)?.into_iter().cycle()),
+        None => Box::new(synthetic()),
+    })
+}
+
+fn png(cli: &Cli, out: &Path, scale: u32) -> Result<()> {
+    fs::create_dir_all(out)?;
+    let settings = OutputSettingsBuilder::new().scale(scale).theme(BinaryColorTheme::OledBlue).build();
+    let mut sim = snapshot::scene(cli.panel, readings(cli)?);
+    sim.each_page(|page, frame| {
+        let path = out.join(format!("{}.png", snapshot::slug(page)));
+        frame.to_rgb_output_image(&settings).save_png(&path)?;
+        println!("{}", path.display());
+        Ok(())
+    })
+}
+
+fn snapshots(cli: &Cli, dir: &Path, update: bool) -> Result<()> {
+    if cli.replay.is_some() {
+        bail!("snapshots are of synthetic readings only");
+    }
+    let mut mismatched = Vec::new();
+    for panel in [PanelKind::Sh1107, PanelKind::Ssd1306_128x32] {
+        let mut sim = snapshot::scene(panel, synthetic());
+        if update {
+            snapshot::update(dir, panel, &mut sim)?;
+        } else {
+            mismatched.extend(snapshot::check(dir, panel, &mut sim)?);
+        }
+    }
+    for path in &mismatched {
+        eprintln!("differs: {}", path.display());
+    }
+    if !mismatched.is_empty() {
+        bail!("{} snapshots differ; rerun with --update if the change is intended", mismatched.len());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "window")]
+fn window(cli: &Cli, speed: u64) -> Result<()> {
+    use std::time::Instant;
+    use display::ui::Button;
+    use embedded_graphics_simulator::sdl2::Keycode;
+    use embedded_graphics_simulator::{SimulatorEvent, Window};
+
+    let mut readings = readings(cli)?;
+    let mut sim = snapshot::scene(cli.panel, &mut readings);
+    let settings = OutputSettingsBuilder::new().scale(4).theme(BinaryColorTheme::OledBlue).build();
+    let mut window = Window::new("smart shop node", &settings);
+
+    let start = Instant::now();
+    let started_at = sim.now_secs();
+    let mut next_reading = started_at + READ_INTERVAL_SECS;
+    sim.press(Button::B);
+    loop {
+        let now = started_at + start.elapsed().as_secs() * speed;
+        sim.tick(now);
+        while next_reading <= now {
+            sim.tick(next_reading);
+            if let Some(reading) = readings.next() {
+                sim.reading(reading);
+                sim.tx(true);
+            }
+            next_reading += READ_INTERVAL_SECS;
+        }
+        sim.tick(now);
+
+        sim.draw();
+        window.update(sim.frame());
+        for event in window.events() {
+            match event {
+                SimulatorEvent::Quit | SimulatorEvent::KeyDown { keycode: Keycode::Escape, .. } => return Ok(()),
+                SimulatorEvent::KeyDown { keycode, repeat: false, .. } => match keycode {
+                    Keycode::A | Keycode::Left => sim.press(Button::A),
+                    Keycode::B | Keycode::Space => sim.press(Button::B),
+                    Keycode::C | Keycode::Right => sim.press(Button::C),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    match &cli.command {
+        Command::Png { out, scale } => png(&cli, out, *scale),
+        Command::Snapshot { dir, update } => snapshots(&cli, dir, *update),
+        #[cfg(feature = "window")]
+        Command::Window { speed } => window(&cli, *speed),
+    }
+}
@@ -0,0 +1,89 @@
+//! Readings to drive the simulated node: a deterministic synthetic day, or a gateway capture.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use anyhow::{bail, Result};
+use shop_cli::input::{for_each_line, parse_line, Event};
+use shop_cli::record::Decoded;
+use telemetry::EnvReading;
+
+/// The node's sample interval, which replayed readings are assumed to have been taken at
+pub const READ_INTERVAL_SECS: u64 = 3;
+
+// a triangle wave rather than a sine so the output, and so the snapshots, are identical everywhere
+fn wave(i: u64, period: u64, low: u16, high: u16) -> u16 {
+    let phase = i % period;
+    let half = period / 2;
+    let rise = if phase < half { phase } else { period - phase };
+    low + (u64::from(high - low) * rise / half) as u16
+}
+
+/// Slowly drifting temperature and humidity with dust that comes and goes, one per
+/// `READ_INTERVAL_SECS`
+pub fn synthetic() -> impl Iterator<Item = EnvReading> {
+    (0..).map(|i| {
+        let jitter = (i * 7 % 5) as u16;
+        let aq_pm2_5 = wave(i, 600, 4, 38) + jitter;
+        EnvReading {
+            temperature: wave(i, 1_600, 64, 75),
+            humidity: wave(i, 1_100, 38, 56),
+            aq_pm1_0: aq_pm2_5 * 2 / 3,
+            aq_pm2_5,
+            aq_pm10: aq_pm2_5 + 6 + jitter,
+        }
+    })
+}
+
+/// Readings from a capture of gateway JSON lines or hex dumps, for `node` or else the first node
+/// heard from
+pub fn replay(path: &Path, node: Option<u16>) -> Result<Vec<EnvReading>> {
+    let mut node = node;
+    let mut readings = Vec::new();
+    for_each_line(BufReader::new(File::open(path)?), |line| {
+        let Ok(Some(Event::Frame(frame))) = parse_line(line) else {
+            return Ok(());
+        };
+        let Ok(Decoded::Reading(record)) = Decoded::decode(&frame, None) else {
+            return Ok(());
+        };
+        if *node.get_or_insert(record.node) == record.node {
+            readings.push(EnvReading {
+                temperature: record.temperature_f,
+                humidity: record.humidity,
+                aq_pm1_0: record.pm1_0,
+                aq_pm2_5: record.pm2_5,
+                aq_pm10: record.pm10,
+            });
+        }
+        Ok(())
+    })?;
+    if readings.is_empty() {
+        bail!("no readings in {}", path.display());
+    }
+    Ok(readings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wave_spans_range() {
+        let values: Vec<u16> = (0..100).map(|i| wave(i, 100, 10, 20)).collect();
+        assert_eq!(values[0], 10);
+        assert_eq!(values[50], 20);
+        assert_eq!(values.iter().min(), Some(&10));
+        assert_eq!(values.iter().max(), Some(&20));
+    }
+
+    #[test]
+    fn synthetic_is_repeatable_and_plausible() {
+        let first: Vec<EnvReading> = synthetic().take(1_000).collect();
+        assert_eq!(first, synthetic().take(1_000).collect::<Vec<_>>());
+        for r in first {
+            assert!((64..=75).contains(&r.temperature));
+            assert!(r.aq_pm1_0 <= r.aq_pm2_5 && r.aq_pm2_5 < r.aq_pm10);
+        }
+    }
+}
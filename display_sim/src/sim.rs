@@ -0,0 +1,176 @@
+//! The node's display loop without the node: the firmware's `Controller`, driven by explicit
+//! presses, readings and clock ticks, drawing into an embedded-graphics-simulator framebuffer.
+
+use clap::ValueEnum;
+use display::Display;
+use display::config::{DisplayConfig, Screen};
+use display::controller::Controller;
+use display::simulator::Simulator;
+use display::status_bar::Health;
+use display::ui::{Button, DeviceInfo, Page};
+use embassy_futures::block_on;
+use embedded_graphics::mono_font::MonoFont;
+use embedded_graphics::mono_font::ascii::{FONT_6X10, FONT_7X13};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::Size;
+use embedded_graphics_simulator::SimulatorDisplay;
+use telemetry::EnvReading;
+use crate::readings::READ_INTERVAL_SECS;
+
+// as on the node, a few missed samples before the reading is flagged as old
+const STALE_AFTER_SECS: u64 = READ_INTERVAL_SECS * 3;
+const NODE_ID: u16 = 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum PanelKind {
+    /// The 128x64 SH1107 FeatherWing the nodes ship with
+    #[default]
+    Sh1107,
+    /// A 128x32 SSD1306 FeatherWing
+    #[value(name = "ssd1306-128x32")]
+    Ssd1306_128x32,
+}
+
+impl PanelKind {
+    pub fn size(&self) -> Size {
+        match self {
+            PanelKind::Sh1107 => Size::new(128, 64),
+            PanelKind::Ssd1306_128x32 => Size::new(128, 32),
+        }
+    }
+
+    pub fn font(&self) -> &'static MonoFont<'static> {
+        match self {
+            PanelKind::Sh1107 => &FONT_7X13,
+            PanelKind::Ssd1306_128x32 => &FONT_6X10,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PanelKind::Sh1107 => "sh1107",
+            PanelKind::Ssd1306_128x32 => "ssd1306-128x32",
+        }
+    }
+}
+
+pub struct Sim {
+    display: Display<Simulator>,
+    controller: Controller,
+    now_secs: u64,
+}
+
+impl Sim {
+    pub fn new(panel: PanelKind) -> Self {
+        let config = DisplayConfig { font: panel.font(), ..Default::default() };
+        let display = Display::new(Simulator::new(panel.size()), config);
+        Self { display, controller: Controller::new(config, STALE_AFTER_SECS, 0), now_secs: 0 }
+    }
+
+    pub fn now_secs(&self) -> u64 {
+        self.now_secs
+    }
+
+    pub fn page(&self) -> Page {
+        self.controller.page()
+    }
+
+    /// Moves the clock on; it never goes backwards
+    pub fn tick(&mut self, now_secs: u64) {
+        self.now_secs = self.now_secs.max(now_secs);
+    }
+
+    pub fn reading(&mut self, reading: EnvReading) {
+        self.controller.reading(self.now_secs, reading);
+    }
+
+    pub fn tx(&mut self, ok: bool) {
+        self.controller.status_mut().radio.tx(ok);
+    }
+
+    pub fn health(&mut self) -> &mut Health {
+        &mut self.controller.status_mut().health
+    }
+
+    pub fn press(&mut self, button: Button) {
+        if self.controller.press(self.now_secs, button) {
+            // the simulator never fails a write
+            block_on(self.display.clear()).unwrap();
+        }
+    }
+
+    /// Redraws the frame as the node would at `now_secs`, returning what the panel is doing
+    pub fn draw(&mut self) -> Option<Screen> {
+        let frame = self.controller.frame(self.now_secs)?;
+        let device = DeviceInfo { node_id: NODE_ID, firmware: env!("CARGO_PKG_VERSION"), uptime_secs: self.now_secs };
+        block_on(self.display.show(frame, self.controller.status(), &device)).unwrap();
+        Some(frame.screen())
+    }
+
+    pub fn frame(&self) -> &SimulatorDisplay<BinaryColor> {
+        self.display.panel().display()
+    }
+
+    /// Wakes the panel and visits every page in order, handing each frame to `f`
+    pub fn each_page<E>(&mut self, mut f: impl FnMut(Page, &SimulatorDisplay<BinaryColor>) -> Result<(), E>) -> Result<(), E> {
+        if !self.controller.is_awake() {
+            self.press(Button::C);
+        }
+        while self.page() != Page::ALL[0] {
+            self.press(Button::C);
+        }
+        for page in Page::ALL {
+            self.draw();
+            f(page, self.frame())?;
+            self.press(Button::C);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::geometry::Dimensions;
+    use embedded_graphics::primitives::PointsIter;
+    use crate::readings::synthetic;
+
+    fn lit(frame: &SimulatorDisplay<BinaryColor>) -> usize {
+        frame.bounding_box().points().filter(|p| frame.get_pixel(*p).is_on()).count()
+    }
+
+    #[test]
+    fn dark_until_pressed_then_dims_and_sleeps() {
+        let mut sim = Sim::new(PanelKind::Sh1107);
+        sim.reading(synthetic().next().unwrap());
+        assert_eq!(sim.draw(), None);
+        assert_eq!(lit(sim.frame()), 0);
+
+        sim.press(Button::A);
+        assert_eq!(sim.draw(), Some(Screen::Active));
+        assert_eq!(sim.page(), Page::ALL[0]);
+        assert!(lit(sim.frame()) > 0);
+
+        sim.tick(30);
+        assert_eq!(sim.draw(), Some(Screen::Dimmed));
+        sim.tick(120);
+        assert_eq!(sim.draw(), Some(Screen::Off));
+        assert_eq!(lit(sim.frame()), 0);
+        assert_eq!(sim.draw(), None);
+    }
+
+    #[test]
+    fn every_page_drawn_on_both_panels() {
+        for panel in PanelKind::value_variants() {
+            let mut sim = Sim::new(*panel);
+            sim.reading(synthetic().next().unwrap());
+            let mut pages = Vec::new();
+            sim.each_page(|page, frame| {
+                assert!(lit(frame) > 0, "{:?} blank on {:?}", page, panel);
+                pages.push(page);
+                Ok::<_, ()>(())
+            }).unwrap();
+            assert_eq!(pages, Page::ALL);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Every page at 1:1 in black and white, compared against the PNGs checked in under `snapshots/`
+//! so a layout change shows up as a test failure and an image diff in review.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use display::ui::Page;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::OriginDimensions;
+use embedded_graphics_simulator::{OutputSettings, SimulatorDisplay};
+use telemetry::EnvReading;
+use crate::sim::{PanelKind, Sim};
+
+/// A file name for `page` that stays put when pages are reordered or renamed on the panel; the
+/// glance page is one page whichever metric it features
+pub fn slug(page: Page) -> &'static str {
+    match page {
+        Page::Glance(_) => "glance",
+        Page::Readings => "readings",
+        Page::AirQuality => "air-quality",
+        Page::Comfort => "comfort",
+        Page::MinMax => "min-max",
+        Page::TemperatureHistory => "temperature-history",
+        Page::HumidityHistory => "humidity-history",
+        Page::Pm2_5History => "pm2_5-history",
+        Page::Radio => "radio",
+        Page::Device => "device",
+    }
+}
+
+pub fn path(dir: &Path, panel: PanelKind, page: Page) -> PathBuf {
+    dir.join(panel.name()).join(format!("{}.png", slug(page)))
+}
+
+/// Writes every page for `panel` under `dir`
+pub fn update(dir: &Path, panel: PanelKind, sim: &mut Sim) -> Result<()> {
+    fs::create_dir_all(dir.join(panel.name()))?;
+    sim.each_page(|page, frame| {
+        frame.to_rgb_output_image(&OutputSettings::default()).save_png(path(dir, panel, page))?;
+        Ok(())
+    })
+}
+
+/// Snapshots that are missing or differ from what `sim` draws now
+pub fn check(dir: &Path, panel: PanelKind, sim: &mut Sim) -> Result<Vec<PathBuf>> {
+    let mut mismatched = Vec::new();
+    sim.each_page(|page, frame| {
+        let path = path(dir, panel, page);
+        match SimulatorDisplay::<BinaryColor>::load_png(&path) {
+            Ok(expected) if expected.size() == frame.size() && expected.diff(frame).is_none() => {}
+            _ => mismatched.push(path),
+        }
+        Ok::<_, anyhow::Error>(())
+    })?;
+    Ok(mismatched)
+}
+
+/// The fixed scene the snapshots show: an hour of synthetic readings with the odd failed send
+pub fn scene(panel: PanelKind, readings: impl Iterator<Item = EnvReading>) -> Sim {
+    let mut sim = Sim::new(panel);
+    crate::prime(&mut sim, readings, 3_600);
+    sim
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+    use crate::readings::synthetic;
+
+    #[test]
+    fn snapshots_match() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots");
+        for panel in PanelKind::value_variants() {
+            let mismatched = check(&dir, *panel, &mut scene(*panel, synthetic())).unwrap();
+            assert!(
+                mismatched.is_empty(),
+                "{:?} differ; review `cargo run -p display_sim -- snapshot --update` output",
+                mismatched,
+            );
+        }
+    }
+}
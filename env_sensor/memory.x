@@ -1,6 +1,8 @@
 MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
-    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+    /* the last five 4K sectors are reserved for the node config and LoRaWAN session */
+    /* (see src/settings.rs)                                                         */
+    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100 - 20K
 
     /* Pick one of the two options for RAM layout     */
 
@@ -0,0 +1,67 @@
+use core::future::pending;
+use embassy_futures::select::{select, Either};
+use embassy_rp::gpio::Output;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Sender;
+use embassy_sync::signal::Signal;
+use embassy_time::Instant;
+use alerts::{Engine, Rules};
+use telemetry::{AlertState, Body};
+use crate::indicator::{self, Update};
+use crate::scheduler::transmit;
+use crate::{Event, Uplink, READINGS};
+
+static SHUT_DOWN: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Releases the output for good and stops raising alerts
+pub fn shut_down() {
+    SHUT_DOWN.signal(());
+}
+
+/// Runs every reading through the alert rules. Each alert raised or cleared goes out straight
+/// away as its own packet, and while any is raised the display and NeoPixel show it and, with
+/// `drive_output`, `output` is driven high for a relay or buzzer.
+#[embassy_executor::task]
+pub async fn alarm(
+    rules: Rules,
+    mut output: Output<'static>,
+    drive_output: bool,
+    uplink: Uplink,
+    node_id: u16,
+    control: Sender<'static, CriticalSectionRawMutex, Event, 64>,
+) {
+    let mut engine = Engine::new(rules);
+    let mut readings = READINGS.receiver().unwrap();
+    loop {
+        let reading = match select(SHUT_DOWN.wait(), readings.changed()).await {
+            Either::First(_) => break,
+            Either::Second(reading) => reading,
+        };
+        let changes = engine.update(Instant::now().as_secs(), &reading);
+        if changes.is_empty() {
+            continue;
+        }
+
+        for alert in &changes {
+            let value = alert.metric.value(&reading);
+            match alert.state {
+                AlertState::Cleared => log::info!("alert cleared: {} {}, limit {}", alert.metric.name(), value, alert.limit),
+                state => log::warn!("alert: {} {} {}, limit {}", alert.metric.name(), state.name(), value, alert.limit),
+            }
+        }
+        let active = engine.active().next();
+        let raised = changes.iter().any(|alert| alert.state != AlertState::Cleared);
+        output.set_level((drive_output && active.is_some()).into());
+        indicator::update(Update::Alarm(active.is_some()));
+        let _ = control.try_send(Event::Alarm(active, raised));
+        // ahead of the next scheduled uplink, which may be minutes away
+        for alert in changes {
+            transmit(uplink, node_id, Body::Alert(reading.clone(), alert)).await;
+        }
+    }
+
+    output.set_low();
+    log::info!("alarm output released for shutdown");
+    // dropping the output would float the pin, and with it whatever the relay or buzzer does
+    pending::<()>().await;
+}
@@ -3,14 +3,26 @@ use embassy_rp::peripherals;
 
 // TODO it's weird to reference pin #s twice...
 
+pub struct Battery {
+    pub adc: peripherals::ADC,
+    /// VBAT through the board's 1:2 divider
+    pub vbat: peripherals::PIN_29,
+}
+
 pub struct DMA {
     pub ch0: peripherals::DMA_CH0,
     pub ch1: peripherals::DMA_CH1,
+    pub ch2: peripherals::DMA_CH2,
 }
 
 pub struct GPIO {
     pub p5: peripherals::PIN_5,
-    pub p9: peripherals::PIN_9
+    pub p7: peripherals::PIN_7,
+    pub p9: peripherals::PIN_9,
+    /// The alert output, for a relay or buzzer
+    pub p24: peripherals::PIN_24,
+    /// The PMSA003I's SET
+    pub p25: peripherals::PIN_25,
 }
 
 pub struct I2C {
@@ -19,6 +31,13 @@ pub struct I2C {
     pub sda: peripherals::PIN_2,
 }
 
+pub struct Leds {
+    /// The WS2812, driven by PIO0
+    pub neopixel: peripherals::PIN_4,
+    pub pio: peripherals::PIO0,
+    pub red: peripherals::PIN_13,
+}
+
 pub struct LoRa<'a> {
     pub dio0: Input<'a>,
     pub nss: Output<'a>,
@@ -33,25 +52,38 @@ pub struct SPI {
 }
 
 pub struct Board {
+    pub battery: Battery,
     pub dma: DMA,
+    pub flash: peripherals::FLASH,
     pub gpio: GPIO,
     pub i2c: I2C,
+    pub leds: Leds,
     pub lora: LoRa<'static>,
     pub spi: SPI,
-    pub usb: peripherals::USB
+    pub usb: peripherals::USB,
+    pub watchdog: peripherals::WATCHDOG,
 }
 
 impl Default for Board {
     fn default() -> Self {
         let peri = embassy_rp::init(Default::default());
         Self {
+            battery: Battery {
+                adc: peri.ADC,
+                vbat: peri.PIN_29,
+            },
             dma: DMA {
                 ch0: peri.DMA_CH0,
                 ch1: peri.DMA_CH1,
+                ch2: peri.DMA_CH2,
             },
+            flash: peri.FLASH,
             gpio: GPIO {
                 p5: peri.PIN_5,
+                p7: peri.PIN_7,
                 p9: peri.PIN_9,
+                p24: peri.PIN_24,
+                p25: peri.PIN_25,
             },
             // TODO just configure I2C1 device here?
             i2c: I2C {
@@ -59,6 +91,11 @@ impl Default for Board {
                 scl: peri.PIN_3,
                 sda: peri.PIN_2
             },
+            leds: Leds {
+                neopixel: peri.PIN_4,
+                pio: peri.PIO0,
+                red: peri.PIN_13,
+            },
             lora: LoRa {
                 dio0: Input::new(peri.PIN_21, Pull::None),
                 nss: Output::new(peri.PIN_16, Level::High),
@@ -71,7 +108,8 @@ impl Default for Board {
                 mosi: peri.PIN_15,
                 miso: peri.PIN_8,
             },
-            usb: peri.USB
+            usb: peri.USB,
+            watchdog: peri.WATCHDOG,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,113 @@
+use core::cell::RefCell;
+use embassy_rp::gpio::{Level, OutputOpenDrain};
+use embassy_rp::i2c::{self, Async, I2c};
+use embassy_rp::peripherals::{I2C1, PIN_2, PIN_3};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{with_timeout, Delay, Duration};
+use embedded_hal_async::i2c::{ErrorKind, ErrorType, Operation};
+use i2c_recovery::{clock_out, Device, Faults, Outcome, SCL_PULSES};
+use crate::board;
+use crate::{I2c1Bus, Irqs};
+
+static FAULTS: Mutex<CriticalSectionRawMutex, RefCell<Faults>> = Mutex::new(RefCell::new(Faults::new()));
+
+/// Fault counts since boot, for the shell's `status` and the uplink
+pub fn faults() -> Faults {
+    FAULTS.lock(|faults| *faults.borrow())
+}
+
+// defaults to 100 kbps, which is the only speed the AQ sensor works with
+fn config() -> i2c::Config {
+    i2c::Config::default()
+}
+
+/// Takes the board's I2C1 and its pins for good; only a recovery touches them again
+pub fn new(i2c: board::I2C) -> I2c<'static, I2C1, Async> {
+    I2c::new_async(i2c.bus, i2c.scl, i2c.sda, Irqs, config())
+}
+
+/// The longest a transaction may hold the bus, which bounds how long the other devices wait on a
+/// hung one. Each byte takes 9 clocks, 90us at 100 kbps. The SHT30 stretches the clock through its
+/// measurement, up to 15.5ms; a PMSA003I frame is the address and 32 bytes, 3ms. The OLED is
+/// written a chunk per transaction, the address, a control byte and 16 data bytes, 1.6ms; 10ms
+/// leaves room for the executor getting round to it.
+fn timeout(device: Device) -> Duration {
+    match device {
+        Device::TempHumidity => Duration::from_millis(50),
+        Device::AirQuality => Duration::from_millis(50),
+        Device::Display => Duration::from_millis(10),
+    }
+}
+
+/// Clocks out whatever device is holding SDA and brings the controller back up from reset. The
+/// caller holds the bus, so nothing else is mid-transaction.
+fn recover(bus: &mut I2c<'static, I2C1, Async>) {
+    // SAFETY: `new` took these for the bus alone, and the locked bus isn't using them. The GPIOs
+    // are dropped, handing the pins back, before the controller claims them again.
+    let (scl, sda) = unsafe { (PIN_3::steal(), PIN_2::steal()) };
+    let mut scl = OutputOpenDrain::new(scl, Level::High);
+    let mut sda = OutputOpenDrain::new(sda, Level::High);
+    let Ok(released) = clock_out(&mut scl, &mut sda, &mut Delay);
+    drop((scl, sda));
+    // the old driver holds no state of its own to drop; this resets the controller
+    *bus = unsafe { I2c::new_async(I2C1::steal(), PIN_3::steal(), PIN_2::steal(), Irqs, config()) };
+    if released {
+        log::warn!("i2c transaction timed out, bus recovered with {} SCL pulses", SCL_PULSES);
+    } else {
+        log::error!("i2c transaction timed out, SDA still held low after {} SCL pulses", SCL_PULSES);
+    }
+}
+
+#[derive(Debug)]
+pub enum BusError {
+    I2c(i2c::Error),
+    /// The transaction took longer than the device's timeout and was abandoned
+    Timeout,
+}
+
+impl embedded_hal_async::i2c::Error for BusError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            BusError::I2c(e) => embedded_hal_async::i2c::Error::kind(e),
+            BusError::Timeout => ErrorKind::Other,
+        }
+    }
+}
+
+/// One device's share of the bus. Each transaction is abandoned after the device's timeout and its
+/// outcome counted, and every timeout recovers the bus.
+pub struct BusDevice {
+    bus: &'static I2c1Bus,
+    device: Device,
+}
+
+impl BusDevice {
+    pub fn new(bus: &'static I2c1Bus, device: Device) -> Self {
+        Self { bus, device }
+    }
+}
+
+impl ErrorType for BusDevice {
+    type Error = BusError;
+}
+
+impl embedded_hal_async::i2c::I2c for BusDevice {
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), BusError> {
+        let mut bus = self.bus.lock().await;
+        let transaction = embedded_hal_async::i2c::I2c::transaction(&mut *bus, address, operations);
+        let (result, outcome) = match with_timeout(timeout(self.device), transaction).await {
+            Ok(Ok(())) => (Ok(()), Outcome::Ok),
+            Ok(Err(e)) => (Err(BusError::I2c(e)), Outcome::Error),
+            Err(_) => (Err(BusError::Timeout), Outcome::Timeout),
+        };
+        // with_timeout drops the transfer without aborting it, so the controller may still be
+        // mid-transfer and a device mid-byte
+        let recover_bus = FAULTS.lock(|faults| faults.borrow_mut().record(self.device, outcome));
+        if recover_bus {
+            recover(&mut bus);
+            FAULTS.lock(|faults| faults.borrow_mut().recovered());
+        }
+        result
+    }
+}
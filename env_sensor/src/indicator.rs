@@ -0,0 +1,92 @@
+use embassy_futures::select::{select4, Either4};
+use embassy_rp::gpio::{Level, Output};
+use embassy_rp::peripherals::PIO0;
+use embassy_rp::pio_programs::ws2812::PioWs2812;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use smart_leds::RGB8;
+use air_quality::aqi::aqi;
+use display::status_bar::Sensor;
+use status_led::{Color, Conditions, Event, Indicator};
+use crate::READINGS;
+
+/// The Feather's one WS2812, on GPIO4
+pub type NeoPixel = PioWs2812<'static, PIO0, 0, 1>;
+
+pub enum Update {
+    Event(Event),
+    /// Whether a sensor's latest read succeeded
+    Sensor(Sensor, bool),
+    LowBattery(bool),
+    /// Whether any alert is raised
+    Alarm(bool),
+}
+
+// a full channel drops the update; sensor results come round again with the next read
+static UPDATES: Channel<CriticalSectionRawMutex, Update, 8> = Channel::new();
+// apart from the updates so a full channel can't lose it
+static SHUT_DOWN: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Never blocks, so sampling and the uplink aren't held up by the LEDs
+pub fn update(update: Update) {
+    let _ = UPDATES.try_send(update);
+}
+
+/// Turns both LEDs off for good
+pub fn shut_down() {
+    SHUT_DOWN.signal(());
+}
+
+async fn show(neopixel: &mut NeoPixel, color: Color) {
+    neopixel.write(&[RGB8::new(color.r, color.g, color.b)]).await;
+}
+
+/// Plays the status patterns on the NeoPixel, and lights the red LED on GPIO13 while something
+/// needs attention
+#[embassy_executor::task]
+pub async fn indicator(mut neopixel: NeoPixel, mut red_led: Output<'static>, brightness: u8, aqi_colors: bool) {
+    let mut indicator = Indicator::new(brightness, aqi_colors);
+    indicator.event(Event::Boot);
+    let mut readings = READINGS.receiver().unwrap();
+    let mut faults = [false; 2];
+    loop {
+        let step = indicator.step();
+        show(&mut neopixel, step.color).await;
+        let until = Instant::now() + Duration::from_millis(step.ms.into());
+        // waits out the step unless an update changes what should show
+        loop {
+            red_led.set_level(if indicator.fault() { Level::High } else { Level::Low });
+            let conditions = indicator.conditions();
+            let next = select4(SHUT_DOWN.wait(), Timer::at(until), UPDATES.receive(), readings.changed());
+            let changed = match next.await {
+                Either4::First(_) => {
+                    show(&mut neopixel, Color::OFF).await;
+                    red_led.set_low();
+                    return;
+                }
+                Either4::Second(_) => true,
+                Either4::Third(Update::Event(event)) => {
+                    indicator.event(event);
+                    true
+                }
+                Either4::Third(Update::Sensor(sensor, ok)) => {
+                    faults[sensor as usize] = !ok;
+                    indicator.set_conditions(Conditions { sensor_fault: faults.contains(&true), ..conditions })
+                }
+                Either4::Third(Update::LowBattery(low_battery)) => {
+                    indicator.set_conditions(Conditions { low_battery, ..conditions })
+                }
+                Either4::Third(Update::Alarm(alarm)) => indicator.set_conditions(Conditions { alarm, ..conditions }),
+                Either4::Fourth(reading) => {
+                    let aqi = aqi(reading.aq_pm2_5, reading.aq_pm10);
+                    indicator.set_conditions(Conditions { aqi: Some(aqi.category), ..conditions })
+                }
+            };
+            if changed {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use lora_radio::lorawan::{Activation, LorawanConfig, Session, SessionStore, Subband, DR};
+use node_config::store::{self, BUFFER_SIZE, MAX_SESSION_SIZE};
+use node_config::LorawanKeys;
+use crate::settings::{SharedFlash, STORAGE_RANGE};
+
+const FPORT: u8 = 1;
+
+pub fn config(keys: &LorawanKeys) -> LorawanConfig {
+    if *keys == LorawanKeys::default() {
+        log::warn!("lorawan keys not provisioned, set lorawan.dev_eui, app_eui and app_key");
+    }
+    LorawanConfig {
+        activation: Activation::Otaa {
+            dev_eui: keys.dev_eui,
+            app_eui: keys.app_eui,
+            app_key: keys.app_key,
+        },
+        fport: FPORT,
+        confirmed: false,
+        // US915 channels 8-15 + 65, the default for most 8-channel gateways
+        subband: Subband::_2,
+        data_rate: DR::_3,
+    }
+}
+
+/// Keeps the LoRaWAN session in the config map so frame counters survive a reboot, and writes
+/// wear across its sectors along with the config's
+pub struct FlashSessionStore {
+    flash: &'static SharedFlash,
+}
+
+impl FlashSessionStore {
+    pub fn new(flash: &'static SharedFlash) -> Self {
+        Self { flash }
+    }
+}
+
+impl SessionStore for FlashSessionStore {
+    async fn load(&mut self) -> Option<Session> {
+        let mut buffer = [0; BUFFER_SIZE];
+        match store::load_session(&mut *self.flash.lock().await, STORAGE_RANGE, &mut buffer).await {
+            Ok(Some(session)) => postcard::from_bytes(session).ok(),
+            Ok(None) => None,
+            Err(e) => {
+                log::error!("lorawan session load failed: {:?}", e);
+                None
+            }
+        }
+    }
+
+    async fn save(&mut self, session: &Session) {
+        let mut record = [0u8; MAX_SESSION_SIZE];
+        let record = match postcard::to_slice(session, &mut record) {
+            Ok(record) => record,
+            Err(e) => {
+                log::error!("lorawan session encode failed: {:?}", e);
+                return;
+            }
+        };
+        match store::save_session(&mut *self.flash.lock().await, STORAGE_RANGE, record).await {
+            Ok(()) => log::debug!("lorawan session saved"),
+            Err(e) => log::error!("lorawan session write failed: {:?}", e),
+        }
+    }
+}
@@ -1,107 +1,198 @@
 #![no_std]
 #![no_main]
 
+mod alarm;
 mod board;
+mod bus;
+mod indicator;
+mod lorawan;
+mod power;
+mod scheduler;
+mod settings;
+mod shell;
+mod sleep;
+mod watchdog;
 
-use core::fmt::Write;
-use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
-use embassy_embedded_hal::shared_bus::I2cDeviceError;
+use embassy_embedded_hal::adapter::BlockingAsync;
 use embassy_executor::Spawner;
+use embassy_rp::adc::{self, Adc};
 use embassy_rp::bind_interrupts;
-use embassy_futures::join::join;
-use embassy_futures::select::{select, Either};
-use embassy_rp::gpio::{Input, Pull};
+use embassy_rp::clocks::RoscRng;
+use embassy_futures::select::{select, select3, Either3};
+use embassy_rp::gpio::{Input, Level, Output, Pull};
+use embassy_rp::flash::Flash;
 use embassy_rp::i2c;
-use embassy_rp::i2c::{Async, Error, I2c};
-use embassy_rp::peripherals::{I2C1, SPI1, USB};
+use embassy_rp::i2c::{Async, I2c};
+use embassy_rp::peripherals::{I2C1, PIO0, SPI1, USB};
+use embassy_rp::pio::Pio;
+use embassy_rp::pio_programs::ws2812::{PioWs2812, PioWs2812Program};
 use embassy_rp::spi::Spi;
 use embassy_rp::usb::Driver;
+use embassy_rp::watchdog::Watchdog;
 use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
 use embassy_sync::channel::{Channel, Receiver, Sender};
 use embassy_sync::mutex::Mutex;
 use embassy_sync::signal::Signal;
-use embassy_time::Timer;
-use heapless::String;
-use packed_struct::prelude::*;
+use embassy_sync::watch::Watch;
+use embassy_time::{Duration, Instant, Timer};
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use embassy_usb::{Builder, UsbDevice};
 use panic_halt as _;
 use static_cell::StaticCell;
-use air_quality::{AQSensor, AirQualityError, AirQualityReading};
-use display::Display;
-use lora_radio::{radio_tx, LoraRadio};
-use sht30::{Sht30, Sht30Error, Sht30Reading};
+use display::{DEFAULT_ADDRESS, Display, Frame, Rotation};
+use display::sh1107::Sh1107;
+use display::config::DisplayConfig;
+use display::controller::Controller;
+use display::status_bar::{Health, Sensor};
+use display::ui::{Button, DeviceInfo, RadioStatus};
+use i2c_recovery::Device;
+use lora_radio::{lorawan_radio, LoraRadio, Sx1276LorawanRadio};
+use lora_radio::lbt::ListenBeforeTalk;
+use lora_radio::lorawan::{EmbassyTimer, LorawanNode};
+use supervisor::{Deadlines, Task};
+use suspend::Suspend;
+use telemetry::{Alert, EnvReading};
 use crate::board::Board;
+use crate::bus::BusDevice;
+use crate::lorawan::FlashSessionStore;
+use crate::settings::SharedFlash;
+use crate::sleep::Managed;
+use crate::watchdog::{check_in, CHECK_IN_EVERY};
 
 pub type I2c1Bus = Mutex<NoopRawMutex, I2c<'static, I2C1, Async>>;
 pub type LoRaRadio = Mutex<NoopRawMutex, LoraRadio>;
+pub type LoRaWanNode = Mutex<NoopRawMutex, LorawanNode<Sx1276LorawanRadio, EmbassyTimer, RoscRng, FlashSessionStore>>;
 pub type Spi1Bus = Mutex<NoopRawMutex, Spi<'static, SPI1, embassy_rp::spi::Async>>;
+type UsbDriver = Driver<'static, USB>;
 
-enum Event {
-    DisplayActivated,
-    DisplayDeactivated,
+#[allow(dead_code)]
+enum RadioMode {
+    /// point-to-point LoRa, picked up by anything listening with the same modulation params
+    Raw,
+    /// LoRaWAN 1.0.x Class A via an existing network server
+    LoRaWan,
 }
-static CHANNEL: Channel<CriticalSectionRawMutex, Event, 64> = Channel::new();
 
-static LAST_ENV_READING: Signal<CriticalSectionRawMutex, EnvReading> = Signal::new();
-
-#[derive(PackedStruct, Clone, Debug)]
-#[packed_struct(endian="lsb")]
-struct EnvReading {
-    #[packed_field()]
-    aq_pm2_5: u16,
-    #[packed_field()]
-    aq_pm10: u16,
-    #[packed_field()]
-    humidity: u16,
-    #[packed_field()]
-    temperature: u16,
+#[derive(Clone, Copy)]
+enum Uplink {
+    Raw(&'static LoRaRadio),
+    LoRaWan(&'static LoRaWanNode),
+}
+
+enum Event {
+    Button(Button),
+    Tx(bool),
+    Sensor(Sensor, bool),
+    Usb(bool),
+    /// State of charge, in percent
+    Battery(u8),
+    /// The battery is critical; blank the panel and stop drawing
+    Shutdown,
+    /// The first raised alert, if any, and whether a new one was just raised
+    Alarm(Option<Alert>, bool),
 }
+static CHANNEL: Channel<CriticalSectionRawMutex, Event, 64> = Channel::new();
 
-impl Into<String<64>> for EnvReading {
+// the display, the shell, the status LEDs and the alert rules
+const READING_SUBSCRIBERS: usize = 4;
+// latest reading, broadcast to every subscriber
+static READINGS: Watch<CriticalSectionRawMutex, EnvReading, READING_SUBSCRIBERS> = Watch::new();
 
-    fn into(self) -> String<64> {
-        let mut msg: String<64> = String::new();
-        core::write!(
-            &mut msg,
-            "Temp   = {}F\nRH     = {}%\nPM 2.5 = {}\nPM 10  = {}",
-            self.temperature, self.humidity, self.aq_pm2_5, self.aq_pm10
-        ).unwrap();
-        msg
-    }
+/// What the shell's `status` reports, published by the display task as results arrive
+#[derive(Clone, Copy, Default)]
+struct Diagnostics {
+    radio: RadioStatus,
+    health: Health,
 }
+static DIAGNOSTICS: Watch<CriticalSectionRawMutex, Diagnostics, 1> = Watch::new();
+
+// take a sample now rather than at the end of the interval
+static SAMPLE_NOW: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 
+const USB_MAX_PACKET_SIZE: u16 = 64;
 
-const READ_INTERVAL_SECONDS: u64 = 3;
+// a few missed samples before the display flags its reading as old
+const STALE_AFTER_SAMPLES: u32 = 3;
+// the watchdog resets the node once a scheduled job is this many intervals overdue, with slack
+// for a slow uplink
+const STALLED_AFTER_RUNS: u32 = 3;
+const JOB_SLACK: Duration = Duration::from_secs(30);
+// for the tasks that check in every CHECK_IN_EVERY
+const IDLE_DEADLINE: Duration = Duration::from_secs(30);
+const RADIO_MODE: RadioMode = RadioMode::Raw;
+// out of 255; the NeoPixel is glaring at full brightness and draws up to 60mA
+const LED_BRIGHTNESS: u8 = 32;
+// whether the NeoPixel holds the latest AQI category's color between events, rather than going dark
+const LED_AQI_COLORS: bool = true;
+// whether uplinks carry the dew point, heat index and absolute humidity too, for receivers that
+// don't compute them
+const UPLINK_DERIVED: bool = false;
 
 bind_interrupts!(struct Irqs {
+    ADC_IRQ_FIFO => adc::InterruptHandler;
     I2C1_IRQ => i2c::InterruptHandler<I2C1>;
+    PIO0_IRQ_0 => embassy_rp::pio::InterruptHandler<PIO0>;
     USBCTRL_IRQ => embassy_rp::usb::InterruptHandler<USB>;
 });
 
-async fn air_quality(
-    i2c_bus: &'static I2c1Bus,
-) -> Result<AirQualityReading, AirQualityError<I2cDeviceError<Error>>> {
-    let i2c_device = I2cDevice::new(i2c_bus);
-    let mut sensor = AQSensor::new(i2c_device);
-    sensor.read().await
-}
-
 #[embassy_executor::task]
 async fn display(
     control: Receiver<'static, CriticalSectionRawMutex, Event, 64>,
     i2c_bus: &'static I2c1Bus,
+    config: DisplayConfig,
+    node_id: u16,
+    stale_after: Duration,
 ) {
-    let i2c_device = I2cDevice::new(i2c_bus);
-    let mut oled = Display::new(i2c_device).await;
+    let panel = Sh1107::new(BusDevice::new(i2c_bus, Device::Display), DEFAULT_ADDRESS, Rotation::Rotate90);
+    let mut oled = Managed::new("display", Display::new(panel, config));
+
+    let mut readings = READINGS.receiver().unwrap();
 
+    // readings and radio results are collected while the panel is blank so every page is ready
+    let mut controller = Controller::new(config, stale_after.as_secs(), Instant::now().as_secs());
     loop {
-        match control.receive().await {
-            Event::DisplayActivated => {
-                let reading = LAST_ENV_READING.wait().await;
-                let msg: String<64> = reading.into();
-                oled.draw(&*msg).await
+        check_in(Task::Display);
+        // the supervisor needs to hear from the task even when nothing else wakes it
+        let wake_in = controller.wake_in(Instant::now().as_secs()).map_or(CHECK_IN_EVERY, Duration::from_secs);
+        let wake_at = Instant::now() + wake_in.min(CHECK_IN_EVERY);
+
+        match select3(control.receive(), readings.changed(), Timer::at(wake_at)).await {
+            Either3::First(Event::Button(button)) => {
+                if controller.press(Instant::now().as_secs(), button) {
+                    oled.suspend().await;
+                }
             }
-            Event::DisplayDeactivated => {
-                oled.clear().await
+            Either3::First(Event::Tx(ok)) => controller.status_mut().radio.tx(ok),
+            Either3::First(Event::Sensor(sensor, ok)) => controller.status_mut().health.sensor(sensor, ok),
+            Either3::First(Event::Usb(attached)) => controller.status_mut().health.usb = attached,
+            Either3::First(Event::Battery(percent)) => controller.status_mut().health.battery_percent = Some(percent),
+            Either3::First(Event::Alarm(alarm, raised)) => controller.alarm(Instant::now().as_secs(), alarm, raised),
+            Either3::First(Event::Shutdown) => {
+                oled.suspend().await;
+                log::info!("display off for shutdown");
+                return;
+            }
+            Either3::Second(reading) => controller.reading(Instant::now().as_secs(), reading),
+            Either3::Third(_) => {}
+        }
+        let status = controller.status();
+        DIAGNOSTICS.sender().send(Diagnostics { radio: status.radio, health: status.health });
+
+        let now = Instant::now();
+        match controller.frame(now.as_secs()) {
+            None => {}
+            // the panel keeps what it showed, which is redrawn as soon as a press resumes it
+            Some(Frame::Off) => oled.suspend().await,
+            Some(frame) => {
+                let device = DeviceInfo {
+                    node_id,
+                    firmware: env!("CARGO_PKG_VERSION"),
+                    uptime_secs: now.as_secs(),
+                };
+                // a missing or flaky panel costs a frame, not the node
+                if let Err(e) = oled.get().await.show(frame, controller.status(), &device).await {
+                    log::error!("display failed: {:?}", e);
+                }
             }
         }
     }
@@ -110,65 +201,108 @@ async fn display(
 #[embassy_executor::task]
 async fn display_controls(
     mut btn_a: Input<'static>,
+    mut btn_b: Input<'static>,
     mut btn_c: Input<'static>,
     control: Sender<'static, CriticalSectionRawMutex, Event, 64>
 ) {
     loop {
-        match select(btn_a.wait_for_falling_edge(), btn_c.wait_for_falling_edge()).await {
-            Either::First(_) => control.send(Event::DisplayActivated).await,
-            Either::Second(_) => control.send(Event::DisplayDeactivated).await
-        }
+        let button = match select3(
+            btn_a.wait_for_falling_edge(),
+            btn_b.wait_for_falling_edge(),
+            btn_c.wait_for_falling_edge()
+        ).await {
+            Either3::First(_) => Button::A,
+            Either3::Second(_) => Button::B,
+            Either3::Third(_) => Button::C,
+        };
+        control.send(Event::Button(button)).await
     }
 }
 
 #[embassy_executor::task]
-async fn logger(driver: Driver<'static, USB>) {
-    embassy_usb_logger::run!(1024, log::LevelFilter::Info, driver);
+async fn logger(class: CdcAcmClass<'static, UsbDriver>) {
+    // log calls drop records rather than wait on the host, so the logger can't hold anything up;
+    // it only stops checking in if it quits
+    let check_ins = async {
+        loop {
+            check_in(Task::Logger);
+            Timer::after(CHECK_IN_EVERY).await;
+        }
+    };
+    select(embassy_usb_logger::with_class!(1024, log::LevelFilter::Info, class), check_ins).await;
 }
 
+/// Reports whether a USB host is attached, for the display's status bar
 #[embassy_executor::task]
-async fn env_sensors(
-    i2c_bus: &'static I2c1Bus,
-    radio: &'static LoRaRadio,
-) {
-    match join(
-        air_quality(i2c_bus),
-        temp_humidity(i2c_bus)
-    ).await {
-        (Ok(aq), Ok(th)) => {
-            let reading = EnvReading {
-                aq_pm2_5: aq.pm2_5.into(),
-                aq_pm10: aq.pm10.into(),
-                humidity: th.humidity.into(),
-                temperature: th.temperature_f.into(),
-            };
-            LAST_ENV_READING.signal(reading.clone());
-
-            let payload: [u8; 8] = reading.pack().unwrap();
-            match radio_tx(radio, &payload).await {
-                Ok(_) => log::debug!("radio tx succeeded: {:?}", payload),
-                Err(e) => log::error!("radio tx failed: {:?}", e),
-            }
-        },
-        // nop bc each sensor is responsible for logging its errors
-        _ => {}
+async fn usb_monitor(control: Sender<'static, CriticalSectionRawMutex, Event, 64>) {
+    let mut attached = None;
+    loop {
+        // the usb task owns the driver, so read the controller's status directly. The Feather has
+        // no VBUS sense, but the bus suspends within milliseconds of the host going away.
+        let sie = embassy_rp::pac::USB.sie_status().read();
+        let now = sie.connected() && !sie.suspended();
+        if attached != Some(now) {
+            attached = Some(now);
+            control.send(Event::Usb(now)).await;
+        }
+        Timer::after_secs(1).await;
     }
 }
 
-async fn temp_humidity(
-    i2c_bus: &'static I2c1Bus,
-) -> Result<Sht30Reading, Sht30Error<I2cDeviceError<Error>>> {
-    let i2c_device = I2cDevice::new(i2c_bus);
-    let mut sensor = Sht30::new(i2c_device);
-    sensor.read().await
+#[embassy_executor::task]
+async fn usb(mut device: UsbDevice<'static, UsbDriver>) {
+    device.run().await
 }
 
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let board = Board::default();
+    // read before anything can reset again
+    let mut wdt = Watchdog::new(board.watchdog);
+    let last_reset = watchdog::last_reset(&mut wdt);
+    // running before the flash and radio setup, either of which can hang
+    spawner.must_spawn(watchdog::supervisor(wdt));
 
+    // composite device: one CDC-ACM port for logs, one for the shell
     let usb_driver = Driver::new(board.usb, Irqs);
-    spawner.must_spawn(logger(usb_driver));
+    let mut usb_config = embassy_usb::Config::new(0x2e8a, 0x000a);
+    usb_config.manufacturer = Some("ardentTech");
+    usb_config.product = Some("smart-shop env sensor");
+    usb_config.max_power = 100;
+    usb_config.max_packet_size_0 = 64;
+    usb_config.device_class = 0xef;
+    usb_config.device_sub_class = 0x02;
+    usb_config.device_protocol = 0x01;
+    usb_config.composite_with_iads = true;
+
+    static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONTROL_BUF: StaticCell<[u8; 64]> = StaticCell::new();
+    let mut builder = Builder::new(
+        usb_driver,
+        usb_config,
+        CONFIG_DESCRIPTOR.init([0; 256]),
+        BOS_DESCRIPTOR.init([0; 256]),
+        &mut [],
+        CONTROL_BUF.init([0; 64]),
+    );
+    static LOGGER_STATE: StaticCell<State> = StaticCell::new();
+    let logger_class = CdcAcmClass::new(&mut builder, LOGGER_STATE.init(State::new()), USB_MAX_PACKET_SIZE);
+    static SHELL_STATE: StaticCell<State> = StaticCell::new();
+    let shell_class = CdcAcmClass::new(&mut builder, SHELL_STATE.init(State::new()), USB_MAX_PACKET_SIZE);
+    spawner.must_spawn(usb(builder.build()));
+    spawner.must_spawn(logger(logger_class));
+
+    let Pio { mut common, sm0, .. } = Pio::new(board.leds.pio, Irqs);
+    let program = PioWs2812Program::new(&mut common);
+    let neopixel = PioWs2812::new(&mut common, sm0, board.dma.ch2, board.leds.neopixel, &program);
+    let red_led = Output::new(board.leds.red, Level::Low);
+    spawner.must_spawn(indicator::indicator(neopixel, red_led, LED_BRIGHTNESS, LED_AQI_COLORS));
+
+    static FLASH: StaticCell<SharedFlash> = StaticCell::new();
+    let flash: &SharedFlash = FLASH.init(Mutex::new(BlockingAsync::new(Flash::new_blocking(board.flash))));
+    let config = settings::load(flash).await;
+    log::info!("config: {:?}", config);
 
     let spi = Spi::new(
         board.spi.bus,
@@ -182,28 +316,80 @@ async fn main(spawner: Spawner) {
     static SPI_BUS: StaticCell<Spi1Bus> = StaticCell::new();
     let spi_bus = SPI_BUS.init(Mutex::new(spi));
 
-    static RADIO: StaticCell<LoRaRadio> = StaticCell::new();
-    let radio = RADIO.init(Mutex::new(LoraRadio::new(spi_bus, board.lora.nss, board.lora.reset, board.lora.dio0).await));
-
-    // TODO handle this config in Board?
-    // defaults to 100 kbps, which is the only speed the AQ sensor works with
-    let i2c = i2c::I2c::new_async(
-        board.i2c.bus,
-        board.i2c.scl,
-        board.i2c.sda,
-        Irqs,
-        i2c::Config::default()
-    );
+    let uplink = match RADIO_MODE {
+        RadioMode::Raw => {
+            let params = settings::radio_params(&config);
+            let mut radio = LoraRadio::new(spi_bus, board.lora.nss, board.lora.reset, board.lora.dio0, params).await;
+            radio.set_listen_before_talk(Some(ListenBeforeTalk::default()));
+            // LoRa::new leaves the radio in standby; it wakes itself for each tx and sleeps again after
+            if let Err(e) = radio.suspend().await {
+                log::error!("radio sleep failed: {:?}", e);
+            }
+            static RADIO: StaticCell<LoRaRadio> = StaticCell::new();
+            Uplink::Raw(RADIO.init(Mutex::new(radio)))
+        }
+        RadioMode::LoRaWan => {
+            let radio = lorawan_radio(spi_bus, board.lora.nss, board.lora.reset, board.lora.dio0).await;
+            let store = FlashSessionStore::new(flash);
+            let node = LorawanNode::new(radio, EmbassyTimer::new(), RoscRng, lorawan::config(&config.lorawan), store).await;
+            static NODE: StaticCell<LoRaWanNode> = StaticCell::new();
+            Uplink::LoRaWan(NODE.init(Mutex::new(node)))
+        }
+    };
+
     static I2C_BUS: StaticCell<I2c1Bus> = StaticCell::new();
-    let i2c_bus = I2C_BUS.init(Mutex::new(i2c));
+    let i2c_bus = I2C_BUS.init(Mutex::new(bus::new(board.i2c)));
+
+    if config.low_power {
+        let mut core = cortex_m::Peripherals::take().unwrap();
+        sleep::enable(&mut core.SCB);
+        log::info!("low-power mode");
+    }
 
     let btn_a = Input::new(board.gpio.p9, Pull::Up);
+    let btn_b = Input::new(board.gpio.p7, Pull::Up);
     let btn_c = Input::new(board.gpio.p5, Pull::Up);
-    spawner.must_spawn(display_controls(btn_a, btn_c, CHANNEL.sender()));
-    spawner.must_spawn(display(CHANNEL.receiver(), i2c_bus));
+    spawner.must_spawn(display_controls(btn_a, btn_b, btn_c, CHANNEL.sender()));
+    let intervals = config.intervals;
+    // the display only gets a reading once both sensors have been read
+    let reading_interval = scheduler::interval(intervals.temp_humidity_secs.max(intervals.air_quality_secs));
+    spawner.must_spawn(display(
+        CHANNEL.receiver(),
+        i2c_bus,
+        // the panel keeps the display crate's defaults; the stored config sets the timeouts and thresholds
+        settings::display_config(&config, DisplayConfig::default()),
+        config.node_id,
+        reading_interval * STALE_AFTER_SAMPLES,
+    ));
+    spawner.must_spawn(usb_monitor(CHANNEL.sender()));
+    let adc = Adc::new(board.battery.adc, Irqs, adc::Config::default());
+    let vbat = adc::Channel::new_pin(board.battery.vbat, Pull::None);
+    spawner.must_spawn(power::power(adc, vbat, CHANNEL.sender()));
+    let alert_output = Output::new(board.gpio.p24, Level::Low);
+    spawner.must_spawn(alarm::alarm(
+        settings::alert_rules(&config),
+        alert_output,
+        config.alerts.output,
+        uplink,
+        config.node_id,
+        CHANNEL.sender(),
+    ));
+    spawner.must_spawn(shell::shell(shell_class, config, flash, i2c_bus, uplink, last_reset));
 
-    loop {
-        spawner.must_spawn(env_sensors(i2c_bus, radio));
-        Timer::after_secs(READ_INTERVAL_SECONDS).await;
-    }
+    // logged once the USB logger task has had a chance to install itself
+    log::info!("last reset: {}", last_reset);
+    let deadline = |secs| (scheduler::interval(secs) * STALLED_AFTER_RUNS + JOB_SLACK).as_millis();
+    watchdog::booted(Deadlines {
+        temp_humidity_ms: deadline(intervals.temp_humidity_secs),
+        air_quality_ms: deadline(intervals.air_quality_secs),
+        radio_ms: deadline(intervals.uplink_secs),
+        display_ms: IDLE_DEADLINE.as_millis(),
+        logger_ms: IDLE_DEADLINE.as_millis(),
+    });
+
+    // between runs every task is parked on a timer or GPIO edge, so the executor WFEs the core
+    // while the radio sits in sleep
+    // high keeps the PMSA003I's fan running until the scheduler suspends it
+    let aq_set = Output::new(board.gpio.p25, Level::High);
+    spawner.must_spawn(scheduler::scheduler(i2c_bus, aq_set, uplink, config.node_id, intervals, config.low_power));
 }
\ No newline at end of file
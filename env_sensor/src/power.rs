@@ -0,0 +1,84 @@
+use embassy_rp::adc::{Adc, Async, Channel};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Sender;
+use embassy_sync::watch::Watch;
+use embassy_time::{Duration, Timer};
+use battery::{Level, Policy};
+use crate::alarm;
+use crate::indicator::{self, Update};
+use crate::watchdog;
+use crate::Event;
+
+// a LiPo drains over hours, so there's nothing to gain from looking more often
+const POLL: Duration = Duration::from_secs(30);
+// averaged to smooth out ADC noise and the sag while the radio transmits
+const SAMPLES: u32 = 8;
+/// On a low battery each job only runs on every this many ticks
+pub const LOW_BATTERY_STRETCH: u32 = 4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BatteryStatus {
+    pub millivolts: u16,
+    pub percent: u8,
+    pub level: Level,
+}
+
+/// The latest battery measurement, for the scheduler, the uplink and the shell
+pub static BATTERY: Watch<CriticalSectionRawMutex, BatteryStatus, 1> = Watch::new();
+
+/// `Normal` until the first measurement
+pub fn level() -> Level {
+    BATTERY.try_get().map_or(Level::Normal, |battery| battery.level)
+}
+
+async fn measure(adc: &mut Adc<'static, Async>, vbat: &mut Channel<'static>) -> Option<u16> {
+    let mut total = 0;
+    for _ in 0..SAMPLES {
+        match adc.read(vbat).await {
+            Ok(counts) => total += u32::from(counts),
+            Err(e) => {
+                log::error!("battery read failed: {:?}", e);
+                return None;
+            }
+        }
+    }
+    Some(battery::millivolts((total / SAMPLES) as u16))
+}
+
+/// Measures the battery and applies the low-battery policy: the scheduler stretches its intervals
+/// and stops reading the PMSA003I while `Low`, and everything stops at `Critical`
+#[embassy_executor::task]
+pub async fn power(
+    mut adc: Adc<'static, Async>,
+    mut vbat: Channel<'static>,
+    control: Sender<'static, CriticalSectionRawMutex, Event, 64>,
+) {
+    let policy = Policy::default();
+    let mut level = Level::Normal;
+    loop {
+        if let Some(millivolts) = measure(&mut adc, &mut vbat).await {
+            let percent = battery::percent(millivolts);
+            let next = policy.level(level, millivolts);
+            BATTERY.sender().send(BatteryStatus { millivolts, percent, level: next });
+            let _ = control.try_send(Event::Battery(percent));
+            if next != level {
+                log::warn!("battery {}mV ({}%), {} -> {}", millivolts, percent, level.name(), next.name());
+                indicator::update(Update::LowBattery(next != Level::Normal));
+                match (level, next) {
+                    (_, Level::Critical) => {
+                        // the scheduler stops at its next tick and the radio is left asleep
+                        watchdog::shut_down();
+                        control.send(Event::Shutdown).await;
+                        indicator::shut_down();
+                        alarm::shut_down();
+                    }
+                    // charging brought it back; start afresh rather than restart each task
+                    (Level::Critical, _) => watchdog::reboot(),
+                    _ => {}
+                }
+                level = next;
+            }
+        }
+        Timer::after(POLL).await;
+    }
+}
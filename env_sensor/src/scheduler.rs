@@ -0,0 +1,331 @@
+use core::cell::RefCell;
+use core::future::Future;
+use embassy_futures::join::{join, join4};
+use embassy_rp::gpio::Output;
+use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+use embassy_sync::mutex::Mutex;
+use embassy_sync::watch::Watch;
+use embassy_time::{Duration, Instant, Ticker};
+use portable_atomic::{AtomicU16, Ordering};
+use air_quality::{AQSensor, AirQualityError, AirQualityReading};
+use battery::Level;
+use display::status_bar::Sensor;
+use i2c_recovery::Device;
+use lora_radio::radio_tx;
+use node_config::Intervals;
+use psychrometrics::Psychrometrics;
+use sht30::{Sht30, Sht30Error, Sht30Reading};
+use supervisor::Task;
+use telemetry::{Battery, Body, BusFaults, Derived, EnvReading, Packet};
+use crate::bus::{self, BusDevice, BusError};
+use crate::indicator::{self, Update};
+use crate::power::{self, BATTERY, LOW_BATTERY_STRETCH};
+use crate::sleep::{self, Managed};
+use crate::watchdog::check_in;
+use crate::{Event, I2c1Bus, Uplink, CHANNEL, READINGS, SAMPLE_NOW, UPLINK_DERIVED};
+
+static PACKET_SEQ: AtomicU16 = AtomicU16::new(0);
+
+type AirQualitySensor = Mutex<NoopRawMutex, Managed<AQSensor<BusDevice, Output<'static>>>>;
+type TempHumiditySensor = Mutex<NoopRawMutex, Managed<Sht30<BusDevice>>>;
+
+/// Run counts and timings for one job, for the shell's `status`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JobStats {
+    pub runs: u32,
+    /// Runs that took longer than the interval; the ticks they missed are skipped
+    pub overruns: u32,
+    pub last_ms: u64,
+    pub max_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScheduleStats {
+    pub temp_humidity: JobStats,
+    pub air_quality: JobStats,
+    pub uplink: JobStats,
+}
+pub static SCHEDULE_STATS: Watch<CriticalSectionRawMutex, ScheduleStats, 1> = Watch::new();
+
+/// A zero interval would run the job back to back
+pub fn interval(secs: u16) -> Duration {
+    Duration::from_secs(secs.max(1).into())
+}
+
+/// The most recent result of each sensor
+#[derive(Default)]
+struct Latest {
+    // a failed read clears its sensor's value, so a reading is never built from an old one
+    temp_humidity: Option<Sht30Reading>,
+    air_quality: Option<AirQualityReading>,
+    /// Which sensors have read since the last publish
+    temp_humidity_read: bool,
+    air_quality_read: bool,
+    /// The newest reading the uplink hasn't sent
+    unsent: Option<EnvReading>,
+}
+
+impl Latest {
+    /// Hands a reading to the display and shell once both sensors have read since the last one,
+    /// so sensors ticking together publish once, as a matched pair. With `air_quality_off` the
+    /// PMSA003I isn't being read and the reading carries its last values.
+    fn publish(&mut self, air_quality_off: bool) {
+        if !self.temp_humidity_read || !(self.air_quality_read || air_quality_off) {
+            return;
+        }
+        self.temp_humidity_read = false;
+        self.air_quality_read = false;
+        let (Some(th), Some(aq)) = (&self.temp_humidity, &self.air_quality) else {
+            return;
+        };
+        let reading = EnvReading {
+            aq_pm2_5: aq.pm2_5,
+            aq_pm10: aq.pm10,
+            aq_pm1_0: aq.pm1_0,
+            humidity: th.humidity,
+            temperature: th.temperature_f,
+        };
+        READINGS.sender().send(reading.clone());
+        indicator::update(Update::Event(status_led::Event::Sampling));
+        self.unsent = Some(reading);
+    }
+}
+
+/// A job the scheduler runs on its own interval
+struct Job {
+    task: Task,
+    secs: u16,
+    stats: fn(&mut ScheduleStats) -> &mut JobStats,
+}
+
+/// Runs `work` now and then on every tick, checking in with the supervisor after each run.
+/// Stops for good once the battery is critical.
+async fn run<F: Future<Output = ()>>(job: Job, stats: &RefCell<ScheduleStats>, mut work: impl FnMut() -> F) {
+    let period = interval(job.secs);
+    let mut ticker = Ticker::every(period);
+    let mut ticks: u32 = 0;
+    loop {
+        let skip = match power::level() {
+            Level::Normal => false,
+            Level::Low => !ticks.is_multiple_of(LOW_BATTERY_STRETCH),
+            Level::Critical => {
+                log::info!("{} job stopped for shutdown", job.task.name());
+                return;
+            }
+        };
+        ticks = ticks.wrapping_add(1);
+        // the tick still counts as progress, so the supervisor's deadlines don't need stretching too
+        if skip {
+            check_in(job.task);
+            ticker.next().await;
+            continue;
+        }
+
+        let started = Instant::now();
+        work().await;
+        check_in(job.task);
+
+        let elapsed = started.elapsed();
+        let mut stats = stats.borrow_mut();
+        let job_stats = (job.stats)(&mut stats);
+        job_stats.runs += 1;
+        job_stats.last_ms = elapsed.as_millis();
+        job_stats.max_ms = job_stats.max_ms.max(elapsed.as_millis());
+        if elapsed > period {
+            job_stats.overruns += 1;
+            log::warn!("{} job took {}ms, past its {}s interval", job.task.name(), elapsed.as_millis(), job.secs);
+            // the ticker would otherwise fire for each missed tick back to back
+            ticker.reset();
+        }
+        SCHEDULE_STATS.sender().send(*stats);
+        drop(stats);
+        ticker.next().await;
+    }
+}
+
+async fn air_quality(sensor: &AirQualitySensor) -> Result<AirQualityReading, AirQualityError<BusError>> {
+    sensor.lock().await.get().await.read().await
+}
+
+async fn temp_humidity(sensor: &TempHumiditySensor) -> Result<Sht30Reading, Sht30Error<BusError>> {
+    sensor.lock().await.get().await.read().await
+}
+
+// never block sampling on the display; a full channel drops the update. Each sensor is
+// responsible for logging its errors.
+async fn read_air_quality(sensor: &AirQualitySensor, latest: &RefCell<Latest>) {
+    let result = air_quality(sensor).await;
+    let _ = CHANNEL.try_send(Event::Sensor(Sensor::AirQuality, result.is_ok()));
+    indicator::update(Update::Sensor(Sensor::AirQuality, result.is_ok()));
+    let mut latest = latest.borrow_mut();
+    latest.air_quality = result.ok();
+    latest.air_quality_read = true;
+}
+
+async fn read_temp_humidity(sensor: &TempHumiditySensor, latest: &RefCell<Latest>) {
+    let result = temp_humidity(sensor).await;
+    let _ = CHANNEL.try_send(Event::Sensor(Sensor::TempHumidity, result.is_ok()));
+    indicator::update(Update::Sensor(Sensor::TempHumidity, result.is_ok()));
+    let mut latest = latest.borrow_mut();
+    latest.temp_humidity = result.ok();
+    latest.temp_humidity_read = true;
+}
+
+async fn send(uplink: Uplink, node_id: u16, reading: EnvReading) {
+    let battery = BATTERY.try_get().map(|battery| Battery { millivolts: battery.millivolts, percent: battery.percent });
+    let body = if UPLINK_DERIVED {
+        // with the battery when there's one to measure
+        let derived = Psychrometrics::new(reading.temperature, reading.humidity);
+        Body::EnvReadingDerived(reading, battery, Derived {
+            dew_point_f: derived.dew_point_f,
+            heat_index_f: derived.heat_index_f,
+            absolute_humidity_dg_m3: derived.absolute_humidity_dg_m3,
+        })
+    } else {
+        match battery {
+            Some(battery) => Body::EnvReadingBattery(reading, battery),
+            None => Body::EnvReading(reading),
+        }
+    };
+    transmit(uplink, node_id, body).await;
+}
+
+/// Follows an uplink with the bus's fault counts when they've changed since the last report
+async fn report_bus_faults(uplink: Uplink, node_id: u16, reading: EnvReading, reported: &RefCell<BusFaults>) {
+    let faults = bus::faults().report();
+    if faults == *reported.borrow() {
+        return;
+    }
+    transmit(uplink, node_id, Body::BusFaults(reading, faults.clone())).await;
+    *reported.borrow_mut() = faults;
+}
+
+/// Sends `body` as the node's next packet, reporting the result to the display and status LEDs
+pub async fn transmit(uplink: Uplink, node_id: u16, body: Body) {
+    let seq = PACKET_SEQ.fetch_add(1, Ordering::Relaxed);
+    let payload = Packet::new(node_id, seq, body).encode().unwrap();
+    let sent = match uplink {
+        Uplink::Raw(radio) => {
+            let result = radio_tx(radio, &payload).await;
+            match &result {
+                Ok(_) => log::debug!("radio tx succeeded: {:?}", payload),
+                Err(e) => log::error!("radio tx failed: {:?}", e),
+            }
+            log::debug!("radio lbt: {:?}", radio.lock().await.lbt_stats());
+            result.is_ok()
+        },
+        Uplink::LoRaWan(node) => match node.lock().await.send(&payload).await {
+            Ok(_) => {
+                log::debug!("lorawan uplink succeeded: {:?}", payload);
+                true
+            },
+            Err(e) => {
+                log::error!("lorawan uplink failed: {:?}", e);
+                false
+            },
+        },
+    };
+    let _ = CHANNEL.try_send(Event::Tx(sent));
+    indicator::update(Update::Event(status_led::Event::Tx(sent)));
+}
+
+/// Reads each sensor and sends the latest reading, each on its own interval, for as long as the
+/// node runs. `SAMPLE_NOW` reads both sensors straight away.
+///
+/// In low-power mode each sensor is suspended between reads when they're far enough apart to
+/// be worth it; `aq_set` drives the PMSA003I's SET pin.
+#[embassy_executor::task]
+pub async fn scheduler(
+    i2c_bus: &'static I2c1Bus,
+    aq_set: Output<'static>,
+    uplink: Uplink,
+    node_id: u16,
+    intervals: Intervals,
+    low_power: bool,
+) {
+    let latest = &RefCell::new(Latest::default());
+    let stats = &RefCell::new(ScheduleStats::default());
+    let reported_faults = &RefCell::new(BusFaults::default());
+    let temp_humidity_sensor =
+        &Mutex::new(Managed::new("temp/humidity", Sht30::new(BusDevice::new(i2c_bus, Device::TempHumidity))));
+    let air_quality_sensor = &Mutex::new(Managed::new(
+        "air quality",
+        AQSensor::with_set_pin(BusDevice::new(i2c_bus, Device::AirQuality), aq_set),
+    ));
+    let temp_humidity_between_uses =
+        sleep::between_uses::<Sht30<BusDevice>>(low_power, interval(intervals.temp_humidity_secs));
+    let air_quality_between_uses =
+        sleep::between_uses::<AQSensor<BusDevice, Output<'static>>>(low_power, interval(intervals.air_quality_secs));
+
+    let temp_humidity_job = Job {
+        task: Task::TempHumidity,
+        secs: intervals.temp_humidity_secs,
+        stats: |s| &mut s.temp_humidity,
+    };
+    let temp_humidity_runs = run(temp_humidity_job, stats, move || async move {
+        read_temp_humidity(temp_humidity_sensor, latest).await;
+        if temp_humidity_between_uses {
+            temp_humidity_sensor.lock().await.suspend().await;
+        }
+        latest.borrow_mut().publish(power::level() == Level::Low);
+    });
+    let air_quality_job = Job {
+        task: Task::AirQuality,
+        secs: intervals.air_quality_secs,
+        stats: |s| &mut s.air_quality,
+    };
+    let air_quality_runs = async {
+        run(air_quality_job, stats, move || async move {
+            // its fan draws more than the rest of the node, so it stays off on a low battery and
+            // readings carry its last values
+            if power::level() == Level::Low {
+                air_quality_sensor.lock().await.suspend().await;
+                return;
+            }
+            // resuming waits out the fan's warm-up, so each reading comes that long after its tick
+            read_air_quality(air_quality_sensor, latest).await;
+            if air_quality_between_uses {
+                air_quality_sensor.lock().await.suspend().await;
+            }
+            latest.borrow_mut().publish(false);
+        })
+        .await;
+        // stopped for shutdown
+        air_quality_sensor.lock().await.suspend().await;
+    };
+    // the first run, alongside the first sensor reads, finds nothing to send yet
+    let uplink_job = Job {
+        task: Task::Radio,
+        secs: intervals.uplink_secs,
+        stats: |s| &mut s.uplink,
+    };
+    let uplink_runs = run(uplink_job, stats, move || async move {
+        // skipped, leaving the radio asleep, when no reading has arrived since the last uplink
+        let reading = latest.borrow_mut().unsent.take();
+        if let Some(reading) = reading {
+            send(uplink, node_id, reading.clone()).await;
+            report_bus_faults(uplink, node_id, reading, reported_faults).await;
+        }
+    });
+    let on_demand = async {
+        loop {
+            SAMPLE_NOW.wait().await;
+            let level = power::level();
+            if level == Level::Critical {
+                continue;
+            }
+            // rather than wait out its warm-up, a suspended PMSA003I contributes its last values
+            let air_quality_off = air_quality_between_uses || level == Level::Low;
+            if air_quality_off {
+                read_temp_humidity(temp_humidity_sensor, latest).await;
+            } else {
+                join(read_temp_humidity(temp_humidity_sensor, latest), read_air_quality(air_quality_sensor, latest)).await;
+            }
+            if temp_humidity_between_uses {
+                temp_humidity_sensor.lock().await.suspend().await;
+            }
+            latest.borrow_mut().publish(air_quality_off);
+        }
+    };
+    join4(temp_humidity_runs, air_quality_runs, uplink_runs, on_demand).await;
+}
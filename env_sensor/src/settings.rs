@@ -0,0 +1,77 @@
+use core::ops::Range;
+use embassy_embedded_hal::adapter::BlockingAsync;
+use embassy_rp::flash::{Blocking, Flash, ERASE_SIZE};
+use embassy_rp::peripherals::FLASH;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::mutex::Mutex;
+use alerts::{Rule, Rules};
+use display::config::DisplayConfig;
+use lora_radio::RadioParams;
+use node_config::{AlertRule, Config};
+
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+// reserved in memory.x
+const STORAGE_SECTORS: usize = 5;
+/// The map holding the config and the LoRaWAN session, at the end of flash
+pub const STORAGE_RANGE: Range<u32> = (FLASH_SIZE - ERASE_SIZE * STORAGE_SECTORS) as u32..FLASH_SIZE as u32;
+
+/// The on-board flash, shared by the config and the LoRaWAN session
+pub type SharedFlash = Mutex<NoopRawMutex, BlockingAsync<Flash<'static, FLASH, Blocking, FLASH_SIZE>>>;
+
+/// The stored config, or the defaults if there isn't one or it can't be read
+pub async fn load(flash: &SharedFlash) -> Config {
+    match node_config::store::load(&mut *flash.lock().await, STORAGE_RANGE).await {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            log::info!("no stored config, using defaults");
+            Config::default()
+        }
+        Err(e) => {
+            log::error!("config load failed, using defaults: {:?}", e);
+            Config::default()
+        }
+    }
+}
+
+/// Returns whether the config is now in flash
+pub async fn save(flash: &SharedFlash, config: &Config) -> bool {
+    match node_config::store::save(&mut *flash.lock().await, STORAGE_RANGE, config).await {
+        Ok(written) => {
+            log::info!("config {}", if written { "saved" } else { "unchanged" });
+            true
+        }
+        Err(e) => {
+            log::error!("config save failed: {:?}", e);
+            false
+        }
+    }
+}
+
+pub fn radio_params(config: &Config) -> RadioParams {
+    RadioParams {
+        frequency: config.radio.frequency_hz,
+        output_power: config.radio.output_power_dbm.into(),
+    }
+}
+
+/// `base` with the stored timeouts and thresholds
+pub fn display_config(config: &Config, base: DisplayConfig) -> DisplayConfig {
+    DisplayConfig {
+        dim_after_secs: config.display.dim_after_secs.map(u64::from),
+        off_after_secs: config.display.off_after_secs.map(u64::from),
+        thresholds: config.thresholds,
+        ..base
+    }
+}
+
+pub fn alert_rules(config: &Config) -> Rules {
+    let rule = |rule: AlertRule| Rule {
+        low: rule.low,
+        high: rule.high,
+        hysteresis: rule.hysteresis,
+        min_duration_secs: rule.min_duration_secs,
+        cooldown_secs: rule.cooldown_secs,
+    };
+    let alerts = config.alerts;
+    Rules { pm2_5: rule(alerts.pm2_5), temperature: rule(alerts.temperature), humidity: rule(alerts.humidity) }
+}
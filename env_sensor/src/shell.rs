@@ -0,0 +1,269 @@
+use core::fmt::Write;
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::watch::Receiver;
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+use embassy_usb::class::cdc_acm::CdcAcmClass;
+use embassy_usb::driver::EndpointError;
+use embedded_hal_async::i2c::I2c;
+use heapless::String;
+use display::status_bar::SensorHealth;
+use i2c_recovery::Device;
+use lora_radio::{radio_cad, radio_sleep};
+use node_config::Config;
+use node_shell::{parse, Command, Edit, Key, LineBuffer, ParseError, HELP, MAX_LINE, PROMPT};
+use supervisor::ResetCause;
+use telemetry::EnvReading;
+use crate::bus;
+use crate::power::BATTERY;
+use crate::scheduler::{JobStats, SCHEDULE_STATS};
+use crate::settings::{self, SharedFlash};
+use crate::watchdog;
+use crate::{I2c1Bus, Uplink, UsbDriver, DIAGNOSTICS, READINGS, READING_SUBSCRIBERS, SAMPLE_NOW, USB_MAX_PACKET_SIZE};
+
+// room for the longest reply, `get` listing every key
+type Reply = String<1024>;
+
+// a sample normally lands within a second; the radio tx after it isn't waited for
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+// long enough for the host to collect the reply before the port disappears
+const RESET_DELAY: Duration = Duration::from_millis(100);
+// an empty address NACKs straight away; a stuck bus shouldn't hang the scan
+const PROBE_TIMEOUT: Duration = Duration::from_millis(10);
+
+// what the node's own hardware answers on, to label `i2c scan`
+const KNOWN_DEVICES: [(u8, &str); 3] = [
+    (0x12, "PMSA003I air quality"),
+    (0x3c, "OLED"),
+    (0x44, "SHT30 temp/humidity"),
+];
+
+async fn write(class: &mut CdcAcmClass<'static, UsbDriver>, text: &[u8]) -> Result<(), EndpointError> {
+    for chunk in text.chunks(USB_MAX_PACKET_SIZE as usize) {
+        class.write_packet(chunk).await?;
+    }
+    // a full-size final packet needs a ZLP to end the transfer
+    if text.len().is_multiple_of(USB_MAX_PACKET_SIZE as usize) {
+        class.write_packet(&[]).await?;
+    }
+    Ok(())
+}
+
+fn sensor(reply: &mut Reply, name: &str, health: SensorHealth) {
+    let state = match health.last_ok {
+        None => "not read yet",
+        Some(true) => "ok",
+        Some(false) => "failing",
+    };
+    let _ = write!(reply, "{}: {}, {} errors\r\n", name, state, health.errors);
+}
+
+fn job(reply: &mut Reply, name: &str, stats: JobStats) {
+    let _ = write!(
+        reply,
+        "{} job: {} runs, {} overruns, last {}ms, max {}ms\r\n",
+        name, stats.runs, stats.overruns, stats.last_ms, stats.max_ms
+    );
+}
+
+struct Shell {
+    /// The stored config, which `set` changes; the running node keeps what it booted with
+    config: Config,
+    flash: &'static SharedFlash,
+    i2c_bus: &'static I2c1Bus,
+    uplink: Uplink,
+    readings: Receiver<'static, CriticalSectionRawMutex, EnvReading, READING_SUBSCRIBERS>,
+    last_reset: ResetCause,
+}
+
+impl Shell {
+    /// Carries out `command`, leaving what to print in `reply`
+    async fn run(&mut self, command: Command<'_>, reply: &mut Reply) {
+        match command {
+            Command::Help => {
+                let _ = reply.push_str(HELP);
+            }
+            Command::Get(key) => {
+                let keys = match key {
+                    Some(ref key) => core::slice::from_ref(key),
+                    None => &Key::ALL[..],
+                };
+                for key in keys {
+                    let _ = write!(reply, "{} = ", key.name());
+                    let _ = key.write_value(&self.config, reply);
+                    let _ = reply.push_str("\r\n");
+                }
+            }
+            Command::Set(key, value) => {
+                if let Err(e) = key.set(&mut self.config, value) {
+                    let _ = write!(reply, "{}: {}\r\n", key.name(), e);
+                } else if settings::save(self.flash, &self.config).await {
+                    let _ = reply.push_str("saved, reboot to apply\r\n");
+                } else {
+                    let _ = reply.push_str("save failed, see the log\r\n");
+                }
+            }
+            Command::Read => {
+                // only a reading taken after the request counts
+                let _ = self.readings.try_changed();
+                SAMPLE_NOW.signal(());
+                match select(self.readings.changed(), Timer::after(READ_TIMEOUT)).await {
+                    Either::First(r) => {
+                        let _ = write!(
+                            reply,
+                            "{}F {}%RH PM1.0 {} PM2.5 {} PM10 {}\r\n",
+                            r.temperature, r.humidity, r.aq_pm1_0, r.aq_pm2_5, r.aq_pm10
+                        );
+                    }
+                    Either::Second(_) => {
+                        let _ = reply.push_str("no reading, check `status`\r\n");
+                    }
+                }
+            }
+            Command::Status => {
+                let _ = write!(reply, "node {}, up {}s\r\n", self.config.node_id, Instant::now().as_secs());
+                let _ = write!(reply, "last reset: {}\r\n", self.last_reset);
+                let diagnostics = DIAGNOSTICS.try_get().unwrap_or_default();
+                sensor(reply, "temp/humidity", diagnostics.health.temp_humidity);
+                sensor(reply, "air quality", diagnostics.health.air_quality);
+                let radio = diagnostics.radio;
+                let last = match radio.last_tx_ok {
+                    None => "none yet",
+                    Some(true) => "ok",
+                    Some(false) => "failed",
+                };
+                let _ = write!(reply, "radio: {} sent, {} failed, last {}\r\n", radio.sent, radio.failed, last);
+                match BATTERY.try_get() {
+                    Some(battery) => {
+                        let _ = write!(reply, "battery: {}mV {}%, {}\r\n", battery.millivolts, battery.percent, battery.level.name());
+                    }
+                    None => {
+                        let _ = reply.push_str("battery: not measured yet\r\n");
+                    }
+                }
+                let schedule = SCHEDULE_STATS.try_get().unwrap_or_default();
+                job(reply, "temp/humidity", schedule.temp_humidity);
+                job(reply, "air quality", schedule.air_quality);
+                job(reply, "uplink", schedule.uplink);
+                let faults = bus::faults();
+                for device in Device::ALL {
+                    let counts = faults.counts(device);
+                    let _ = write!(reply, "i2c {}: {} timeouts, {} errors\r\n", device.name(), counts.timeouts, counts.errors);
+                }
+                let _ = write!(reply, "i2c bus: {} recoveries\r\n", faults.recoveries());
+            }
+            Command::I2cScan => {
+                let mut bus = self.i2c_bus.lock().await;
+                let mut found = false;
+                // 0x00-0x07 and 0x78-0x7f are reserved
+                for address in 0x08..=0x77 {
+                    if let Ok(Ok(())) = with_timeout(PROBE_TIMEOUT, bus.read(address, &mut [0])).await {
+                        let name = KNOWN_DEVICES.iter().find(|(a, _)| *a == address).map_or("", |(_, name)| name);
+                        let _ = write!(reply, "0x{:02x} {}\r\n", address, name);
+                        found = true;
+                    }
+                }
+                if !found {
+                    let _ = reply.push_str("no devices\r\n");
+                }
+            }
+            Command::RadioTest => match self.uplink {
+                Uplink::Raw(radio) => {
+                    match radio_cad(radio).await {
+                        Ok(busy) => {
+                            let _ = write!(reply, "radio ok, channel {}\r\n", if busy { "busy" } else { "clear" });
+                        }
+                        Err(e) => {
+                            let _ = write!(reply, "radio failed: {:?}\r\n", e);
+                        }
+                    }
+                    // CAD leaves the radio in standby
+                    if let Err(e) = radio_sleep(radio).await {
+                        log::error!("radio sleep failed: {:?}", e);
+                    }
+                    let _ = write!(reply, "{:?}\r\n", radio.lock().await.lbt_stats());
+                }
+                Uplink::LoRaWan(_) => {
+                    let _ = reply.push_str("the LoRaWAN MAC owns the radio; test it in raw mode\r\n");
+                }
+            },
+            Command::Reboot => {
+                let _ = reply.push_str("rebooting\r\n");
+            }
+            Command::Bootsel => {
+                let _ = reply.push_str("restarting into the USB bootloader\r\n");
+            }
+        }
+    }
+}
+
+/// Config and diagnostics console on the second USB serial port
+#[embassy_executor::task]
+pub async fn shell(
+    mut class: CdcAcmClass<'static, UsbDriver>,
+    config: Config,
+    flash: &'static SharedFlash,
+    i2c_bus: &'static I2c1Bus,
+    uplink: Uplink,
+    last_reset: ResetCause,
+) {
+    let mut shell = Shell { config, flash, i2c_bus, uplink, readings: READINGS.receiver().unwrap(), last_reset };
+    let mut line = LineBuffer::<MAX_LINE>::new();
+    let mut packet = [0u8; USB_MAX_PACKET_SIZE as usize];
+    loop {
+        class.wait_connection().await;
+        line.clear();
+        let _ = write(&mut class, PROMPT.as_bytes()).await;
+        'connected: while let Ok(len) = class.read_packet(&mut packet).await {
+            for &byte in &packet[..len] {
+                let edit = line.push(byte);
+                let echo: &[u8] = match edit {
+                    Edit::Ignored => continue,
+                    Edit::Echo(_) => core::slice::from_ref(&byte),
+                    Edit::Erase => b"\x08 \x08",
+                    Edit::Submit => b"\r\n",
+                };
+                if write(&mut class, echo).await.is_err() {
+                    break 'connected;
+                }
+                if edit != Edit::Submit {
+                    continue;
+                }
+
+                let mut reply = Reply::new();
+                let command = match line.line().map(parse) {
+                    None => {
+                        let _ = write!(reply, "longer than {} characters\r\n", MAX_LINE);
+                        None
+                    }
+                    Some(Ok(command)) => Some(command),
+                    Some(Err(ParseError::Empty)) => None,
+                    Some(Err(e)) => {
+                        let _ = write!(reply, "{}\r\n", e);
+                        None
+                    }
+                };
+                if let Some(command) = command {
+                    shell.run(command, &mut reply).await;
+                }
+                let _ = reply.push_str(PROMPT);
+                if write(&mut class, reply.as_bytes()).await.is_err() {
+                    break 'connected;
+                }
+
+                match command {
+                    Some(Command::Reboot) => {
+                        Timer::after(RESET_DELAY).await;
+                        watchdog::reboot();
+                    }
+                    Some(Command::Bootsel) => {
+                        Timer::after(RESET_DELAY).await;
+                        embassy_rp::rom_data::reset_to_usb_boot(0, 0);
+                    }
+                    _ => {}
+                }
+                line.clear();
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use core::fmt::Debug;
+use cortex_m::peripheral::SCB;
+use embassy_rp::pac;
+use embassy_time::{Duration, Timer};
+use suspend::{worth_suspending, Suspend};
+
+/// A device whose power state is tracked here, so it's only resumed when something needs it and
+/// every driver's failures are logged the same way
+pub struct Managed<D> {
+    name: &'static str,
+    device: D,
+    suspended: bool,
+}
+
+impl<D: Suspend> Managed<D>
+where
+    D::Error: Debug,
+{
+    /// `device` starts out powered up
+    pub fn new(name: &'static str, device: D) -> Self {
+        Self { name, device, suspended: false }
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    pub async fn suspend(&mut self) {
+        if self.suspended {
+            return;
+        }
+        match self.device.suspend().await {
+            Ok(()) => self.suspended = true,
+            Err(e) => log::error!("{} suspend failed: {:?}", self.name, e),
+        }
+    }
+
+    /// The device, resumed and warmed up first if it was suspended. A failed resume is logged and
+    /// the device handed over anyway, so its own error surfaces wherever it's used.
+    pub async fn get(&mut self) -> &mut D {
+        if self.suspended {
+            match self.device.resume().await {
+                Ok(()) => {
+                    self.suspended = false;
+                    Timer::after(Duration::from_millis(D::WARM_UP_MS.into())).await;
+                }
+                Err(e) => log::error!("{} resume failed: {:?}", self.name, e),
+            }
+        }
+        &mut self.device
+    }
+}
+
+/// Whether a device of type `D` used every `interval` is suspended between uses
+pub fn between_uses<D: Suspend>(low_power: bool, interval: Duration) -> bool {
+    low_power && worth_suspending(interval.as_millis(), D::WARM_UP_MS)
+}
+
+/// Lets the RP2040 drop into its sleep state whenever the executor waits, instead of only gating
+/// the core's clock. Dormant would stop the timer too, and with no external 32kHz clock on the
+/// Feather, nothing but a GPIO could wake it.
+pub fn enable(scb: &mut SCB) {
+    // clocks left off while asleep; anything with a transfer in flight, the timer, the GPIO
+    // interrupts for the buttons and USB keep theirs
+    pac::CLOCKS.sleep_en0().modify(|w| {
+        w.set_clk_sys_i2c0(false);
+        w.set_clk_sys_jtag(false);
+        w.set_clk_sys_pio1(false);
+        w.set_clk_sys_pwm(false);
+        w.set_clk_rtc_rtc(false);
+        w.set_clk_sys_rtc(false);
+        w.set_clk_peri_spi0(false);
+        w.set_clk_sys_spi0(false);
+    });
+    pac::CLOCKS.sleep_en1().modify(|w| {
+        w.set_clk_sys_sysinfo(false);
+        w.set_clk_sys_tbman(false);
+        w.set_clk_peri_uart0(false);
+        w.set_clk_sys_uart0(false);
+        w.set_clk_peri_uart1(false);
+        w.set_clk_sys_uart1(false);
+    });
+    scb.set_sleepdeep();
+}
@@ -0,0 +1,112 @@
+use embassy_futures::select::{select, select3, Either, Either3};
+use embassy_rp::watchdog::{ResetReason, Watchdog};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use supervisor::{Deadlines, Liveness, ResetCause, Task};
+
+// close to the RP2040's longest period, ~8.3s
+const PERIOD: Duration = Duration::from_secs(8);
+// check-ins are only collected this often, so they count up to a second late
+const FEED_EVERY: Duration = Duration::from_secs(1);
+/// How often tasks that otherwise sit idle check in
+pub const CHECK_IN_EVERY: Duration = Duration::from_secs(5);
+// flash and radio setup take a second or two; longer means a hung flash, SPI bus or radio
+const BOOT_DEADLINE: Duration = Duration::from_secs(30);
+// 0-3 are free; the bootrom uses 4-7 to reboot into a given address
+const SCRATCH: [usize; 2] = [0, 1];
+
+// room for every task to check in a few times between collections
+static CHECK_INS: Channel<CriticalSectionRawMutex, Task, 16> = Channel::new();
+static BOOTED: Signal<CriticalSectionRawMutex, Deadlines> = Signal::new();
+static REBOOT: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+static SHUT_DOWN: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Tells the supervisor the task is still making progress
+pub fn check_in(task: Task) {
+    // a full channel means the supervisor is behind, and it already has recent check-ins
+    let _ = CHECK_INS.try_send(task);
+}
+
+/// Ends startup: from here the supervisor holds each critical task to its deadline
+pub fn booted(deadlines: Deadlines) {
+    BOOTED.signal(deadlines);
+}
+
+/// Resets the node through the watchdog, so the next boot knows it was asked for
+pub fn reboot() {
+    REBOOT.signal(());
+}
+
+/// Stops the watchdog for a deliberate shutdown, after which the tasks stop checking in
+pub fn shut_down() {
+    SHUT_DOWN.signal(());
+}
+
+fn record(watchdog: &mut Watchdog, cause: ResetCause) {
+    for (index, word) in SCRATCH.into_iter().zip(cause.to_scratch()) {
+        watchdog.set_scratch(index, word);
+    }
+}
+
+/// Why the node last reset; clears the record so a later reset isn't blamed on the same cause
+pub fn last_reset(watchdog: &mut Watchdog) -> ResetCause {
+    let scratch = SCRATCH.map(|index| watchdog.get_scratch(index));
+    let timed_out = watchdog.reset_reason() == Some(ResetReason::TimedOut);
+    record(watchdog, ResetCause::PowerOn);
+    ResetCause::from_scratch(scratch, timed_out)
+}
+
+/// Feeds the watchdog until [`booted`] within BOOT_DEADLINE, then while every critical task keeps
+/// checking in
+#[embassy_executor::task]
+pub async fn supervisor(mut watchdog: Watchdog) {
+    // a debugger halting the core shouldn't reset it
+    watchdog.pause_on_debug(true);
+    watchdog.start(PERIOD);
+    let boot_deadline = Instant::now() + BOOT_DEADLINE;
+    let deadlines = loop {
+        if let Either::First(deadlines) = select(BOOTED.wait(), Timer::after(FEED_EVERY)).await {
+            break deadlines;
+        }
+        if Instant::now() > boot_deadline {
+            log::error!("startup missed its deadline, resetting");
+            record(&mut watchdog, ResetCause::BootStalled);
+            return;
+        }
+        watchdog.feed();
+    };
+
+    let mut liveness = Liveness::new(Instant::now().as_millis(), deadlines);
+    loop {
+        match select3(REBOOT.wait(), SHUT_DOWN.wait(), Timer::after(FEED_EVERY)).await {
+            Either3::First(_) => {
+                record(&mut watchdog, ResetCause::Reboot);
+                watchdog.trigger_reset();
+            }
+            Either3::Second(_) => {
+                // the driver has no stop, but the enable bit is all `start` sets
+                embassy_rp::pac::WATCHDOG.ctrl().modify(|w| w.set_enable(false));
+                log::info!("watchdog stopped for shutdown");
+                // a reboot still goes through the watchdog
+                REBOOT.wait().await;
+                record(&mut watchdog, ResetCause::Reboot);
+                watchdog.trigger_reset();
+            }
+            Either3::Third(_) => {}
+        }
+
+        let now = Instant::now().as_millis();
+        while let Ok(task) = CHECK_INS.try_receive() {
+            liveness.check_in(task, now);
+        }
+        if let Some(task) = liveness.overdue(now) {
+            log::error!("{} task missed its deadline, resetting", task.name());
+            record(&mut watchdog, ResetCause::Stalled(task));
+            // left unfed, the watchdog resets the node within PERIOD, time enough to get the log out
+            return;
+        }
+        watchdog.feed();
+    }
+}
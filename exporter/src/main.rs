@@ -0,0 +1,84 @@
+mod metrics;
+
+use std::io::{self, BufRead, BufReader};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+use shop_cli::input::{for_each_line, parse_line, Event};
+use shop_cli::record::{now_millis, Decoded};
+use tiny_http::{Header, Method, Response, Server};
+use crate::metrics::{Metrics, CONTENT_TYPE};
+
+/// Serve smart-shop node readings from the gateway as Prometheus/OpenMetrics metrics
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The gateway's JSON serial port, e.g. /dev/ttyACM1, or - for gateway lines on stdin
+    source: String,
+    #[arg(long, default_value_t = 115_200)]
+    baud: u32,
+    /// Address to serve /metrics on
+    #[arg(long, default_value = "0.0.0.0:9185")]
+    listen: String,
+}
+
+fn ingest(reader: impl BufRead, metrics: &Mutex<Metrics>) -> Result<()> {
+    for_each_line(reader, |line| {
+        let mut metrics = metrics.lock().map_err(|_| anyhow!("metrics lock poisoned"))?;
+        match parse_line(line) {
+            Ok(Some(Event::Frame(frame))) => match Decoded::decode(&frame, Some(now_millis())) {
+                Ok(decoded) => metrics.record(&decoded),
+                Err(e) => {
+                    metrics.decode_error();
+                    eprintln!("{}: {}", e, line.trim());
+                }
+            },
+            Ok(Some(Event::CrcError)) => metrics.crc_error(),
+            Ok(None) => {}
+            Err(e) => {
+                metrics.decode_error();
+                eprintln!("{}: {}", e, line.trim());
+            }
+        }
+        Ok(())
+    })
+}
+
+fn serve(server: Server, metrics: Arc<Mutex<Metrics>>) {
+    let content_type = Header::from_bytes("Content-Type", CONTENT_TYPE).expect("valid header");
+    for request in server.incoming_requests() {
+        let response = match (request.method(), request.url()) {
+            (Method::Get, "/metrics") => match metrics.lock().map(|m| m.encode()) {
+                Ok(Ok(body)) => Response::from_string(body).with_header(content_type.clone()),
+                _ => Response::from_string("encoding failed").with_status_code(500),
+            },
+            _ => Response::from_string("not found").with_status_code(404),
+        };
+        if let Err(e) = request.respond(response) {
+            eprintln!("http: {}", e);
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let metrics = Arc::new(Mutex::new(Metrics::new()));
+
+    let server = Server::http(&args.listen).map_err(|e| anyhow!("listen on {}: {}", args.listen, e))?;
+    let server_metrics = metrics.clone();
+    let server = thread::spawn(move || serve(server, server_metrics));
+
+    if args.source == "-" {
+        ingest(io::stdin().lock(), &metrics)?;
+        // keep serving a replayed capture until interrupted
+        server.join().map_err(|_| anyhow!("http server panicked"))?;
+        Ok(())
+    } else {
+        // a long timeout; for_each_line retries on expiry so this only bounds each blocking read
+        let port = serialport::new(&args.source, args.baud).timeout(Duration::from_secs(60)).open()?;
+        ingest(BufReader::new(port), &metrics)?;
+        bail!("serial port closed")
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::fmt;
+use air_quality::aqi::aqi;
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{linear_buckets, Histogram};
+use prometheus_client::registry::Registry;
+use shop_cli::record::{Decoded, Record};
+use shop_cli::summary::missed_packets;
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct NodeLabels {
+    pub node: u16,
+}
+
+type HistogramFamily = Family<NodeLabels, Histogram, fn() -> Histogram>;
+
+fn rssi_histogram() -> Histogram {
+    // -130 dBm is below the SX1276's sensitivity floor
+    Histogram::new(linear_buckets(-130.0, 10.0, 10))
+}
+
+fn snr_histogram() -> Histogram {
+    Histogram::new(linear_buckets(-20.0, 5.0, 8))
+}
+
+/// Per-node metrics for every decoded packet, served in the OpenMetrics text format
+pub struct Metrics {
+    registry: Registry,
+    temperature: Family<NodeLabels, Gauge>,
+    humidity: Family<NodeLabels, Gauge>,
+    pm1_0: Family<NodeLabels, Gauge>,
+    pm2_5: Family<NodeLabels, Gauge>,
+    pm10: Family<NodeLabels, Gauge>,
+    aqi: Family<NodeLabels, Gauge>,
+    battery_mv: Family<NodeLabels, Gauge>,
+    battery_percent: Family<NodeLabels, Gauge>,
+    packets: Family<NodeLabels, Counter>,
+    missed: Family<NodeLabels, Counter>,
+    crc_errors: Counter,
+    decode_errors: Counter,
+    rssi: HistogramFamily,
+    snr: HistogramFamily,
+    last_seq: HashMap<u16, u16>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut metrics = Self {
+            registry: Registry::with_prefix("smart_shop"),
+            temperature: Family::default(),
+            humidity: Family::default(),
+            pm1_0: Family::default(),
+            pm2_5: Family::default(),
+            pm10: Family::default(),
+            aqi: Family::default(),
+            battery_mv: Family::default(),
+            battery_percent: Family::default(),
+            packets: Family::default(),
+            missed: Family::default(),
+            crc_errors: Counter::default(),
+            decode_errors: Counter::default(),
+            rssi: Family::new_with_constructor(rssi_histogram),
+            snr: Family::new_with_constructor(snr_histogram),
+            last_seq: HashMap::new(),
+        };
+
+        let registry = &mut metrics.registry;
+        registry.register("temperature_fahrenheit", "Air temperature", metrics.temperature.clone());
+        registry.register("humidity_percent", "Relative humidity", metrics.humidity.clone());
+        registry.register("pm1_0_micrograms_per_cubic_meter", "PM1.0 concentration", metrics.pm1_0.clone());
+        registry.register("pm2_5_micrograms_per_cubic_meter", "PM2.5 concentration", metrics.pm2_5.clone());
+        registry.register("pm10_micrograms_per_cubic_meter", "PM10 concentration", metrics.pm10.clone());
+        registry.register("aqi", "US EPA Air Quality Index from PM2.5 and PM10", metrics.aqi.clone());
+        registry.register("battery_millivolts", "Battery voltage, from nodes that measure it", metrics.battery_mv.clone());
+        registry.register("battery_percent", "Approximate battery state of charge", metrics.battery_percent.clone());
+        registry.register("packets_received", "Packets decoded from the node", metrics.packets.clone());
+        registry.register("packets_missed", "Packets inferred lost from sequence number gaps", metrics.missed.clone());
+        // a corrupt packet can't be attributed to a node, so these are gateway-wide
+        registry.register("crc_errors", "Packets received with a bad CRC", metrics.crc_errors.clone());
+        registry.register("decode_errors", "Lines or packets that failed to decode", metrics.decode_errors.clone());
+        registry.register("rssi_dbm", "Received signal strength of the node's packets", metrics.rssi.clone());
+        registry.register("snr_db", "Signal to noise ratio of the node's packets", metrics.snr.clone());
+        metrics
+    }
+
+    /// Every kind of packet counts towards the node's packets and signal; only readings set the
+    /// gauges
+    pub fn record(&mut self, decoded: &Decoded) {
+        if let Decoded::Reading(record) = decoded {
+            self.reading(record);
+        }
+        let (node, seq) = (decoded.node(), decoded.seq());
+        let labels = NodeLabels { node };
+        self.packets.get_or_create(&labels).inc();
+        // created at zero on a node's first packet so the series exists before any loss
+        let missed = self.last_seq.insert(node, seq).map_or(0, |last| missed_packets(last, seq));
+        self.missed.get_or_create(&labels).inc_by(missed.into());
+        let (rssi, snr) = decoded.signal();
+        if let Some(rssi) = rssi {
+            self.rssi.get_or_create(&labels).observe(rssi.into());
+        }
+        if let Some(snr) = snr {
+            self.snr.get_or_create(&labels).observe(snr.into());
+        }
+    }
+
+    fn reading(&mut self, record: &Record) {
+        let labels = NodeLabels { node: record.node };
+        self.temperature.get_or_create(&labels).set(record.temperature_f.into());
+        self.humidity.get_or_create(&labels).set(record.humidity.into());
+        self.pm1_0.get_or_create(&labels).set(record.pm1_0.into());
+        self.pm2_5.get_or_create(&labels).set(record.pm2_5.into());
+        self.pm10.get_or_create(&labels).set(record.pm10.into());
+        self.aqi.get_or_create(&labels).set(aqi(record.pm2_5, record.pm10).value.into());
+        if let Some(millivolts) = record.battery_mv {
+            self.battery_mv.get_or_create(&labels).set(millivolts.into());
+        }
+        if let Some(percent) = record.battery_percent {
+            self.battery_percent.get_or_create(&labels).set(percent.into());
+        }
+    }
+
+    pub fn crc_error(&self) {
+        self.crc_errors.inc();
+    }
+
+    pub fn decode_error(&self) {
+        self.decode_errors.inc();
+    }
+
+    pub fn encode(&self) -> Result<String, fmt::Error> {
+        let mut body = String::new();
+        encode(&mut body, &self.registry)?;
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shop_cli::record::AlertRecord;
+
+    fn record(node: u16, seq: u16) -> Record {
+        Record {
+            received_at: None,
+            node,
+            seq,
+            temperature_f: 68,
+            humidity: 48,
+            pm1_0: 8,
+            pm2_5: 12,
+            pm10: 15,
+            battery_mv: None,
+            battery_percent: None,
+            rssi: Some(-87),
+            snr: Some(7),
+        }
+    }
+
+    fn lines(metrics: &Metrics) -> Vec<String> {
+        metrics.encode().unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn gauges_labeled_by_node() {
+        let mut metrics = Metrics::new();
+        metrics.record(&Decoded::Reading(record(3, 1)));
+        let lines = lines(&metrics);
+        for expected in [
+            "smart_shop_temperature_fahrenheit{node=\"3\"} 68",
+            "smart_shop_humidity_percent{node=\"3\"} 48",
+            "smart_shop_pm1_0_micrograms_per_cubic_meter{node=\"3\"} 8",
+            "smart_shop_pm2_5_micrograms_per_cubic_meter{node=\"3\"} 12",
+            "smart_shop_pm10_micrograms_per_cubic_meter{node=\"3\"} 15",
+            "smart_shop_aqi{node=\"3\"} 56",
+            "smart_shop_packets_received_total{node=\"3\"} 1",
+        ] {
+            assert!(lines.iter().any(|l| l == expected), "missing {}", expected);
+        }
+        assert_eq!(lines.last().unwrap(), "# EOF");
+    }
+
+    #[test]
+    fn sequence_gaps_counted() {
+        let mut metrics = Metrics::new();
+        for seq in [1, 2, 5, 6] {
+            metrics.record(&Decoded::Reading(record(3, seq)));
+        }
+        metrics.record(&Decoded::Reading(record(4, 9)));
+        let lines = lines(&metrics);
+        assert!(lines.iter().any(|l| l == "smart_shop_packets_missed_total{node=\"3\"} 2"));
+        assert!(lines.iter().any(|l| l == "smart_shop_packets_missed_total{node=\"4\"} 0"));
+    }
+
+    #[test]
+    fn radio_histograms() {
+        let mut metrics = Metrics::new();
+        metrics.record(&Decoded::Reading(record(3, 1)));
+        metrics.record(&Decoded::Reading(Record { rssi: Some(-112), ..record(3, 2) }));
+        let lines = lines(&metrics);
+        assert!(lines.iter().any(|l| l == "smart_shop_rssi_dbm_count{node=\"3\"} 2"));
+        assert!(lines.iter().any(|l| l == "smart_shop_rssi_dbm_bucket{le=\"-110.0\",node=\"3\"} 1"));
+        assert!(lines.iter().any(|l| l == "smart_shop_snr_db_sum{node=\"3\"} 14.0"));
+    }
+
+    #[test]
+    fn alerts_count_as_packets() {
+        let mut metrics = Metrics::new();
+        metrics.record(&Decoded::Reading(record(3, 1)));
+        let alert = AlertRecord {
+            received_at: None,
+            node: 3,
+            seq: 2,
+            metric: "pm2_5",
+            state: "high",
+            value: 40,
+            limit: 35,
+            rssi: None,
+            snr: None,
+        };
+        metrics.record(&Decoded::Alert(alert));
+        metrics.record(&Decoded::Reading(Record { pm2_5: 40, ..record(3, 3) }));
+        let lines = lines(&metrics);
+        assert!(lines.iter().any(|l| l == "smart_shop_packets_received_total{node=\"3\"} 3"));
+        assert!(lines.iter().any(|l| l == "smart_shop_packets_missed_total{node=\"3\"} 0"));
+        assert!(lines.iter().any(|l| l == "smart_shop_pm2_5_micrograms_per_cubic_meter{node=\"3\"} 40"));
+    }
+
+    #[test]
+    fn gateway_wide_errors() {
+        let metrics = Metrics::new();
+        metrics.crc_error();
+        metrics.crc_error();
+        metrics.decode_error();
+        let lines = lines(&metrics);
+        assert!(lines.iter().any(|l| l == "smart_shop_crc_errors_total 2"));
+        assert!(lines.iter().any(|l| l == "smart_shop_decode_errors_total 1"));
+    }
+}
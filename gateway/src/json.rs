@@ -0,0 +1,86 @@
+use core::fmt::Write;
+use heapless::String;
+use telemetry::{Body, Packet};
+
+/// One JSON object per line, newline terminated; room for any packet with every field at full
+/// width, the longest being a bus faults packet
+pub type Line = String<416>;
+
+fn write_hex(line: &mut Line, bytes: &[u8]) -> core::fmt::Result {
+    for byte in bytes {
+        core::write!(line, "{:02x}", byte)?;
+    }
+    Ok(())
+}
+
+fn write_packet(line: &mut Line, raw: &[u8], rssi: i16, snr: i16) -> core::fmt::Result {
+    match Packet::decode(raw) {
+        Ok(packet) => {
+            let reading = packet.body.reading();
+            if let Body::LegacyEnvReading(_) = packet.body {
+                // no node, sequence number or PM1.0 to report
+                core::write!(
+                    line,
+                    "{{\"kind\":\"legacy\",\"temperature_f\":{},\"humidity\":{},\"pm2_5\":{},\"pm10\":{},",
+                    reading.temperature, reading.humidity, reading.aq_pm2_5, reading.aq_pm10
+                )?;
+            } else {
+                core::write!(line, "{{\"node\":{},\"seq\":{},", packet.node_id, packet.seq)?;
+                core::write!(
+                    line,
+                    "\"kind\":\"env\",\"temperature_f\":{},\"humidity\":{},\"pm1_0\":{},\"pm2_5\":{},\"pm10\":{},",
+                    reading.temperature, reading.humidity, reading.aq_pm1_0, reading.aq_pm2_5, reading.aq_pm10
+                )?;
+            }
+            if let Some(battery) = packet.body.battery() {
+                core::write!(line, "\"battery_mv\":{},\"battery_percent\":{},", battery.millivolts, battery.percent)?;
+            }
+            if let Some(alert) = packet.body.alert() {
+                core::write!(
+                    line,
+                    "\"alert\":\"{}\",\"alert_state\":\"{}\",\"alert_limit\":{},",
+                    alert.metric.name(), alert.state.name(), alert.limit
+                )?;
+            }
+            if let Some(derived) = packet.body.derived() {
+                core::write!(
+                    line,
+                    "\"dew_point_f\":{},\"heat_index_f\":{},\"absolute_humidity\":{}.{},",
+                    derived.dew_point_f, derived.heat_index_f,
+                    derived.absolute_humidity_dg_m3 / 10, derived.absolute_humidity_dg_m3 % 10
+                )?;
+            }
+            if let Some(faults) = packet.body.bus_faults() {
+                core::write!(
+                    line,
+                    "\"temp_humidity_timeouts\":{},\"temp_humidity_errors\":{},\"air_quality_timeouts\":{},\
+                     \"air_quality_errors\":{},\"display_timeouts\":{},\"display_errors\":{},\"i2c_recoveries\":{},",
+                    faults.temp_humidity_timeouts, faults.temp_humidity_errors, faults.air_quality_timeouts,
+                    faults.air_quality_errors, faults.display_timeouts, faults.display_errors, faults.recoveries
+                )?;
+            }
+        }
+        Err(e) => core::write!(line, "{{\"error\":\"decode\",\"detail\":\"{:?}\",", e)?,
+    }
+    core::write!(line, "\"rssi\":{},\"snr\":{},\"raw\":\"", rssi, snr)?;
+    write_hex(line, raw)?;
+    line.write_str("\"}\n")
+}
+
+/// Describe a received packet, decoded if possible, always with the raw bytes so host tools can
+/// decode it themselves
+pub fn packet(raw: &[u8], rssi: i16, snr: i16) -> Line {
+    let mut line = Line::new();
+    if write_packet(&mut line, raw, rssi, snr).is_err() {
+        line.clear();
+        let _ = line.write_str("{\"error\":\"overflow\"}\n");
+    }
+    line
+}
+
+/// A packet that was heard but failed the PHY CRC
+pub fn crc_error() -> Line {
+    let mut line = Line::new();
+    let _ = line.write_str("{\"error\":\"crc\"}\n");
+    line
+}
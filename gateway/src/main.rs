@@ -0,0 +1,223 @@
+#![no_std]
+#![no_main]
+
+mod board;
+mod json;
+
+use core::fmt::Write;
+use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
+use embassy_executor::Spawner;
+use embassy_rp::bind_interrupts;
+use embassy_rp::i2c;
+use embassy_rp::i2c::{Async, I2c};
+use embassy_rp::peripherals::{I2C1, SPI1, USB};
+use embassy_rp::spi::Spi;
+use embassy_rp::usb::Driver;
+use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+use embassy_sync::channel::Channel;
+use embassy_sync::mutex::Mutex;
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use embassy_usb::driver::EndpointError;
+use embassy_usb::{Builder, UsbDevice};
+use heapless::{String, Vec};
+use panic_halt as _;
+use portable_atomic::{AtomicU32, Ordering};
+use static_cell::StaticCell;
+use display::{DEFAULT_ADDRESS, Display, Rotation};
+use display::sh1107::Sh1107;
+use display::config::DisplayConfig;
+use lora_radio::{radio_rx, LoraRadio, RadioError, RadioParams};
+use telemetry::{EnvReading, Packet};
+use crate::board::Board;
+use crate::json::Line;
+
+pub type I2c1Bus = Mutex<NoopRawMutex, I2c<'static, I2C1, Async>>;
+pub type LoRaRadio = Mutex<NoopRawMutex, LoraRadio>;
+pub type Spi1Bus = Mutex<NoopRawMutex, Spi<'static, SPI1, embassy_rp::spi::Async>>;
+type UsbDriver = Driver<'static, USB>;
+
+struct NodeReading {
+    node_id: u16,
+    reading: EnvReading,
+    rssi: i16,
+}
+
+// lines are dropped rather than stalling the radio when no host is reading
+static LINES: Channel<CriticalSectionRawMutex, Line, 16> = Channel::new();
+static NODE_READINGS: Channel<CriticalSectionRawMutex, NodeReading, 8> = Channel::new();
+static DROPPED_LINES: AtomicU32 = AtomicU32::new(0);
+
+const USB_MAX_PACKET_SIZE: u16 = 64;
+// 4 rows of FONT_7X13 fit on the 128x64 panel
+const DISPLAY_NODES: usize = 4;
+
+bind_interrupts!(struct Irqs {
+    I2C1_IRQ => i2c::InterruptHandler<I2C1>;
+    USBCTRL_IRQ => embassy_rp::usb::InterruptHandler<USB>;
+});
+
+fn emit(line: Line) {
+    if LINES.try_send(line).is_err() {
+        DROPPED_LINES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[embassy_executor::task]
+async fn display(i2c_bus: &'static I2c1Bus) {
+    let i2c_device = I2cDevice::new(i2c_bus);
+    // nothing wakes the gateway's panel, so it stays on
+    let config = DisplayConfig { dim_after_secs: None, off_after_secs: None, ..Default::default() };
+    let panel = Sh1107::new(i2c_device, DEFAULT_ADDRESS, Rotation::Rotate90);
+    let mut oled = Display::new(panel, config);
+    if let Err(e) = oled.draw("Listening...").await {
+        log::error!("display failed: {:?}", e);
+    }
+
+    // most recently heard node first
+    let mut nodes: Vec<NodeReading, DISPLAY_NODES> = Vec::new();
+    loop {
+        let latest = NODE_READINGS.receive().await;
+        if let Some(i) = nodes.iter().position(|n| n.node_id == latest.node_id) {
+            nodes.remove(i);
+        } else if nodes.is_full() {
+            nodes.pop();
+        }
+        // there is always room after the remove/pop above
+        let _ = nodes.insert(0, latest);
+
+        let mut msg: String<128> = String::new();
+        for node in nodes.iter() {
+            let _ = core::writeln!(
+                &mut msg,
+                "#{} {}F {}% {} {}",
+                node.node_id, node.reading.temperature, node.reading.humidity, node.reading.aq_pm2_5, node.rssi
+            );
+        }
+        // a missing or flaky panel costs a frame, not the gateway
+        if let Err(e) = oled.draw(&msg).await {
+            log::error!("display failed: {:?}", e);
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn logger(class: CdcAcmClass<'static, UsbDriver>) {
+    embassy_usb_logger::with_class!(1024, log::LevelFilter::Info, class).await;
+}
+
+#[embassy_executor::task]
+async fn receiver(radio: &'static LoRaRadio) {
+    let mut buffer = [0u8; 255];
+    loop {
+        match radio_rx(radio, &mut buffer).await {
+            Ok(rx) => {
+                let raw = &buffer[..rx.len];
+                log::debug!("radio rx: {:?} rssi={} snr={}", raw, rx.rssi, rx.snr);
+                if let Ok(Packet { node_id, body, .. }) = Packet::decode(raw) {
+                    let _ = NODE_READINGS.try_send(NodeReading { node_id, reading: body.reading().clone(), rssi: rx.rssi });
+                }
+                emit(json::packet(raw, rx.rssi, rx.snr));
+            }
+            Err(RadioError::CRCErrorOnReceive) => emit(json::crc_error()),
+            Err(e) => log::error!("radio rx failed: {:?}", e),
+        }
+    }
+}
+
+async fn write_line(class: &mut CdcAcmClass<'static, UsbDriver>, line: &[u8]) -> Result<(), EndpointError> {
+    for chunk in line.chunks(USB_MAX_PACKET_SIZE as usize) {
+        class.write_packet(chunk).await?;
+    }
+    // a full-size final packet needs a ZLP to end the transfer
+    if line.len() % USB_MAX_PACKET_SIZE as usize == 0 {
+        class.write_packet(&[]).await?;
+    }
+    Ok(())
+}
+
+#[embassy_executor::task]
+async fn serial(mut class: CdcAcmClass<'static, UsbDriver>) {
+    loop {
+        class.wait_connection().await;
+        log::info!("serial connected, {} lines dropped", DROPPED_LINES.load(Ordering::Relaxed));
+        loop {
+            let line = LINES.receive().await;
+            if write_line(&mut class, line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn usb(mut device: UsbDevice<'static, UsbDriver>) {
+    device.run().await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let board = Board::default();
+
+    // composite device: one CDC-ACM port for logs, one for the JSON lines
+    let usb_driver = Driver::new(board.usb, Irqs);
+    let mut config = embassy_usb::Config::new(0x2e8a, 0x000a);
+    config.manufacturer = Some("ardentTech");
+    config.product = Some("smart-shop gateway");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+    config.device_class = 0xef;
+    config.device_sub_class = 0x02;
+    config.device_protocol = 0x01;
+    config.composite_with_iads = true;
+
+    static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONTROL_BUF: StaticCell<[u8; 64]> = StaticCell::new();
+    let mut builder = Builder::new(
+        usb_driver,
+        config,
+        CONFIG_DESCRIPTOR.init([0; 256]),
+        BOS_DESCRIPTOR.init([0; 256]),
+        &mut [],
+        CONTROL_BUF.init([0; 64]),
+    );
+    static LOGGER_STATE: StaticCell<State> = StaticCell::new();
+    let logger_class = CdcAcmClass::new(&mut builder, LOGGER_STATE.init(State::new()), USB_MAX_PACKET_SIZE);
+    static SERIAL_STATE: StaticCell<State> = StaticCell::new();
+    let serial_class = CdcAcmClass::new(&mut builder, SERIAL_STATE.init(State::new()), USB_MAX_PACKET_SIZE);
+    spawner.must_spawn(usb(builder.build()));
+    spawner.must_spawn(logger(logger_class));
+    spawner.must_spawn(serial(serial_class));
+
+    let spi = Spi::new(
+        board.spi.bus,
+        board.spi.sck,
+        board.spi.mosi,
+        board.spi.miso,
+        board.dma.ch0,
+        board.dma.ch1,
+        embassy_rp::spi::Config::default()
+    );
+    static SPI_BUS: StaticCell<Spi1Bus> = StaticCell::new();
+    let spi_bus = SPI_BUS.init(Mutex::new(spi));
+
+    // a node configured off the default frequency won't be heard
+    let params = RadioParams::default();
+    let mut radio = LoraRadio::new(spi_bus, board.lora.nss, board.lora.reset, board.lora.dio0, params).await;
+    // the gateway is mains powered and never stops listening
+    radio.set_auto_sleep(false);
+    static RADIO: StaticCell<LoRaRadio> = StaticCell::new();
+    let radio = RADIO.init(Mutex::new(radio));
+    spawner.must_spawn(receiver(radio));
+
+    let i2c = i2c::I2c::new_async(
+        board.i2c.bus,
+        board.i2c.scl,
+        board.i2c.sda,
+        Irqs,
+        i2c::Config::default()
+    );
+    static I2C_BUS: StaticCell<I2c1Bus> = StaticCell::new();
+    let i2c_bus = I2C_BUS.init(Mutex::new(i2c));
+    spawner.must_spawn(display(i2c_bus));
+}
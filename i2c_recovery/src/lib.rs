@@ -0,0 +1,213 @@
+#![no_std]
+
+//! Keeps one misbehaving device from taking down the I2C bus it shares with the others.
+//!
+//! The firmware gives each device's transactions their own timeout and records every outcome in
+//! [`Faults`]. A device reset or brown-out mid-transfer can leave it holding SDA low, waiting to
+//! clock out the rest of a byte, and then every transaction on the bus times out. A timed-out
+//! transfer is also abandoned rather than aborted, leaving the controller and the device part way
+//! through it, so every timeout recovers the bus: [`clock_out`] frees SDA and the firmware then
+//! resets the controller.
+
+#[cfg(test)]
+extern crate std;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use telemetry::BusFaults;
+
+/// Enough for a device to finish shifting out a byte and see the NACK after it
+pub const SCL_PULSES: usize = 9;
+// half an SCL period at 100 kHz
+const HALF_PERIOD_US: u32 = 5;
+
+/// The devices on the bus
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Device {
+    TempHumidity,
+    AirQuality,
+    Display,
+}
+
+impl Device {
+    pub const ALL: [Device; 3] = [Device::TempHumidity, Device::AirQuality, Device::Display];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Device::TempHumidity => "temp/humidity",
+            Device::AirQuality => "air quality",
+            Device::Display => "display",
+        }
+    }
+}
+
+/// How a transaction ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Ok,
+    /// Finished, but with an error such as a NACK
+    Error,
+    /// Didn't finish within the device's timeout
+    Timeout,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Counts {
+    pub timeouts: u16,
+    pub errors: u16,
+}
+
+/// Failed transactions per device since boot, and the bus recoveries they led to
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Faults {
+    counts: [Counts; Device::ALL.len()],
+    recoveries: u16,
+}
+
+impl Faults {
+    pub const fn new() -> Self {
+        Self { counts: [Counts { timeouts: 0, errors: 0 }; Device::ALL.len()], recoveries: 0 }
+    }
+
+    /// Counts a transaction's outcome, returning whether the bus should be recovered: after any
+    /// timeout, since the abandoned transfer may have left it mid-byte
+    pub fn record(&mut self, device: Device, outcome: Outcome) -> bool {
+        let counts = &mut self.counts[device as usize];
+        match outcome {
+            Outcome::Ok => false,
+            // an absent device NACKs promptly and the transfer ends cleanly, so the bus is left
+            // as it was
+            Outcome::Error => {
+                counts.errors = counts.errors.saturating_add(1);
+                false
+            }
+            Outcome::Timeout => {
+                counts.timeouts = counts.timeouts.saturating_add(1);
+                true
+            }
+        }
+    }
+
+    pub fn recovered(&mut self) {
+        self.recoveries = self.recoveries.saturating_add(1);
+    }
+
+    pub fn counts(&self, device: Device) -> Counts {
+        self.counts[device as usize]
+    }
+
+    pub fn recoveries(&self) -> u16 {
+        self.recoveries
+    }
+
+    pub fn report(&self) -> BusFaults {
+        let [temp_humidity, air_quality, display] = self.counts;
+        BusFaults {
+            temp_humidity_timeouts: temp_humidity.timeouts,
+            temp_humidity_errors: temp_humidity.errors,
+            air_quality_timeouts: air_quality.timeouts,
+            air_quality_errors: air_quality.errors,
+            display_timeouts: display.timeouts,
+            display_errors: display.errors,
+            recoveries: self.recoveries,
+        }
+    }
+}
+
+/// Frees a bus that a device is holding SDA low on: [`SCL_PULSES`] clocks let it finish the byte
+/// it was sending, and a STOP then resets every device's state machine. `scl` and `sda` must be
+/// open-drain GPIOs, released high, with the I2C controller disconnected from them. Returns
+/// whether SDA is free afterwards.
+pub fn clock_out<E, SCL, SDA>(scl: &mut SCL, sda: &mut SDA, delay: &mut impl DelayNs) -> Result<bool, E>
+where
+    SCL: OutputPin<Error = E>,
+    SDA: OutputPin<Error = E> + InputPin<Error = E>,
+{
+    for _ in 0..SCL_PULSES {
+        scl.set_low()?;
+        delay.delay_us(HALF_PERIOD_US);
+        scl.set_high()?;
+        delay.delay_us(HALF_PERIOD_US);
+    }
+    // STOP: SDA rises while SCL is high
+    scl.set_low()?;
+    delay.delay_us(HALF_PERIOD_US);
+    sda.set_low()?;
+    delay.delay_us(HALF_PERIOD_US);
+    scl.set_high()?;
+    delay.delay_us(HALF_PERIOD_US);
+    sda.set_high()?;
+    delay.delay_us(HALF_PERIOD_US);
+    sda.is_high()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction as PinTransaction};
+
+    #[test]
+    fn recovers_after_every_timeout() {
+        let mut faults = Faults::new();
+        assert!(faults.record(Device::TempHumidity, Outcome::Timeout));
+        faults.recovered();
+        assert!(!faults.record(Device::AirQuality, Outcome::Ok));
+        assert!(!faults.record(Device::AirQuality, Outcome::Error));
+        // however recent the last recovery, and whichever device
+        assert!(faults.record(Device::Display, Outcome::Timeout));
+        faults.recovered();
+        assert!(faults.record(Device::Display, Outcome::Timeout));
+        assert_eq!(faults.recoveries(), 2);
+    }
+
+    #[test]
+    fn counts_per_device() {
+        let mut faults = Faults::new();
+        faults.record(Device::TempHumidity, Outcome::Timeout);
+        faults.record(Device::TempHumidity, Outcome::Error);
+        faults.record(Device::Display, Outcome::Error);
+        faults.record(Device::Display, Outcome::Error);
+        faults.record(Device::AirQuality, Outcome::Ok);
+        faults.recovered();
+        assert_eq!(faults.counts(Device::TempHumidity), Counts { timeouts: 1, errors: 1 });
+        assert_eq!(faults.counts(Device::AirQuality), Counts::default());
+        assert_eq!(
+            faults.report(),
+            BusFaults {
+                temp_humidity_timeouts: 1,
+                temp_humidity_errors: 1,
+                display_errors: 2,
+                recoveries: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    // each pulse, then the STOP's own low and high
+    fn scl() -> PinMock {
+        let pulse = [PinTransaction::set(State::Low), PinTransaction::set(State::High)];
+        let expectations: std::vec::Vec<_> = (0..=SCL_PULSES).flat_map(|_| pulse.clone()).collect();
+        PinMock::new(&expectations)
+    }
+
+    fn sda(released: State) -> PinMock {
+        PinMock::new(&[PinTransaction::set(State::Low), PinTransaction::set(State::High), PinTransaction::get(released)])
+    }
+
+    #[test]
+    fn clocks_out_then_stops() {
+        let (mut scl, mut sda) = (scl(), sda(State::High));
+        assert_eq!(clock_out(&mut scl, &mut sda, &mut NoopDelay::new()), Ok(true));
+        scl.done();
+        sda.done();
+    }
+
+    #[test]
+    fn reports_sda_still_held() {
+        let (mut scl, mut sda) = (scl(), sda(State::Low));
+        assert_eq!(clock_out(&mut scl, &mut sda, &mut NoopDelay::new()), Ok(false));
+        scl.done();
+        sda.done();
+    }
+}
@@ -0,0 +1,108 @@
+//! Listen-before-talk policy for the raw LoRa TX path.
+//!
+//! Before transmitting, the radio runs Channel Activity Detection. If another node is mid-packet,
+//! the transmission backs off for a random, exponentially growing window and tries again, giving
+//! up after `max_attempts` busy detections.
+
+use embassy_time::Duration;
+use rand_core::RngCore;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ListenBeforeTalk {
+    /// Busy CAD results tolerated before the transmission is abandoned
+    pub max_attempts: u8,
+    /// Upper bound of the first backoff window; doubles after each busy CAD
+    pub initial_window: Duration,
+    /// Cap on the backoff window
+    pub max_window: Duration,
+}
+
+impl Default for ListenBeforeTalk {
+    fn default() -> Self {
+        // the node's largest packet, a 29 byte bus faults report, is 56 payload symbols at SF10,
+        // 250kHz and 4/8 with a CRC, plus 8.25 of preamble: ~263ms on air, so start a little above
+        Self {
+            max_attempts: 5,
+            initial_window: Duration::from_millis(300),
+            max_window: Duration::from_secs(2),
+        }
+    }
+}
+
+impl ListenBeforeTalk {
+    /// How long to wait after the `attempt`th (zero-based) busy CAD, or `None` to give up
+    pub fn backoff(&self, attempt: u8, rng: &mut impl RngCore) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let window = self.initial_window.as_millis()
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_window.as_millis())
+            .max(1);
+        Some(Duration::from_millis(rng.next_u64() % window + 1))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LbtStats {
+    /// CAD runs that detected activity on the channel
+    pub busy: u32,
+    /// Transmissions that went out after backing off at least once
+    pub deferred: u32,
+    /// Transmissions dropped because the channel never cleared
+    pub abandoned: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StepRng(u64);
+
+    impl RngCore for StepRng {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(7_919);
+            self.0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            rand_core::impls::fill_bytes_via_next(self, dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn backoff_within_doubling_window() {
+        let policy = ListenBeforeTalk::default();
+        let mut rng = StepRng(0);
+        for attempt in 0..policy.max_attempts {
+            let window = (300u64 << attempt).min(2_000);
+            for _ in 0..100 {
+                let backoff = policy.backoff(attempt, &mut rng).unwrap().as_millis();
+                assert!(backoff >= 1 && backoff <= window, "attempt {}: {}ms", attempt, backoff);
+            }
+        }
+    }
+
+    #[test]
+    fn backoff_gives_up_after_max_attempts() {
+        let policy = ListenBeforeTalk { max_attempts: 2, ..Default::default() };
+        let mut rng = StepRng(0);
+        assert!(policy.backoff(1, &mut rng).is_some());
+        assert!(policy.backoff(2, &mut rng).is_none());
+    }
+
+    #[test]
+    fn backoff_zero_attempts_never_transmits_over_busy_channel() {
+        let policy = ListenBeforeTalk { max_attempts: 0, ..Default::default() };
+        assert!(policy.backoff(0, &mut StepRng(0)).is_none());
+    }
+}
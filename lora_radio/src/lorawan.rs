@@ -0,0 +1,406 @@
+//! LoRaWAN 1.0.x Class A end-device built on `lorawan-device`.
+//!
+//! The MAC is generic over the PHY, timer and RNG so it can drive the SX1276 on target or a
+//! simulated radio on the host. RX1/RX2 window timing and MAC commands (including ADR
+//! `LinkADRReq`) are handled by `lorawan-device`; this module adds activation, the uplink port
+//! and persistence of the session so frame counters survive a reboot.
+
+use core::future::Future;
+use lorawan_device::async_device::radio::{PhyRxTx, Timer};
+use lorawan_device::async_device::{region, Device, Error, JoinMode, JoinResponse, SendResponse, Timings};
+use lorawan_device::default_crypto::DefaultFactory;
+use lorawan_device::{AppEui, AppKey, AppSKey, DevAddr, DevEui, NwkSKey};
+use rand_core::RngCore;
+
+pub use lorawan_device::async_device::{EmbassyTimer, Session};
+pub use lorawan_device::region::{Subband, DR};
+
+/// Number of uplinks between session writes.
+///
+/// Writing the session after every uplink would wear out flash, so it is only saved every
+/// `SESSION_SAVE_INTERVAL` uplinks and the uplink counter is advanced by this much on restore.
+/// The network server rejects repeated counters, so skipping ahead is always safe. The advanced
+/// counter is saved straight away, so a second reboot before the next save skips ahead again
+/// rather than reusing the same counters.
+pub const SESSION_SAVE_INTERVAL: u32 = 32;
+
+#[derive(Clone, Debug)]
+pub enum Activation {
+    /// Over-the-air activation; a new session is negotiated with the network server
+    Otaa {
+        dev_eui: [u8; 8],
+        app_eui: [u8; 8],
+        app_key: [u8; 16],
+    },
+    /// Activation by personalization; the session keys are provisioned ahead of time
+    Abp {
+        dev_addr: u32,
+        nwk_skey: [u8; 16],
+        app_skey: [u8; 16],
+    },
+}
+
+impl Activation {
+    fn join_mode(&self) -> JoinMode {
+        match self {
+            Activation::Otaa { dev_eui, app_eui, app_key } => JoinMode::OTAA {
+                deveui: DevEui::from(*dev_eui),
+                appeui: AppEui::from(*app_eui),
+                appkey: AppKey::from(*app_key),
+            },
+            Activation::Abp { dev_addr, nwk_skey, app_skey } => JoinMode::ABP {
+                devaddr: DevAddr::from(*dev_addr),
+                nwkskey: NwkSKey::from(*nwk_skey),
+                appskey: AppSKey::from(*app_skey),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LorawanConfig {
+    pub activation: Activation,
+    /// Application port every uplink is sent on, 1..=223
+    pub fport: u8,
+    /// Request an acknowledgement from the network server for each uplink
+    pub confirmed: bool,
+    /// US915 sub-band the join request is biased towards, matching the gateway's channel plan
+    pub subband: Subband,
+    /// Data rate used until the network server adjusts it via ADR
+    pub data_rate: DR,
+}
+
+/// Persists the LoRaWAN session across reboots
+pub trait SessionStore {
+    fn load(&mut self) -> impl Future<Output = Option<Session>>;
+    fn save(&mut self, session: &Session) -> impl Future<Output = ()>;
+}
+
+#[derive(Debug)]
+pub enum LorawanError<E> {
+    Device(Error<E>),
+    NoJoinAccept,
+    NoAck,
+    SessionExpired,
+}
+
+impl<E> From<Error<E>> for LorawanError<E> {
+    fn from(e: Error<E>) -> Self {
+        LorawanError::Device(e)
+    }
+}
+
+pub struct LorawanNode<R, T, G, S>
+where
+    R: PhyRxTx + Timings,
+    T: Timer,
+    G: RngCore,
+    S: SessionStore,
+{
+    device: Device<R, DefaultFactory, T, G>,
+    config: LorawanConfig,
+    store: S,
+    unsaved_uplinks: u32,
+}
+
+impl<R, T, G, S> LorawanNode<R, T, G, S>
+where
+    R: PhyRxTx + Timings,
+    T: Timer,
+    G: RngCore,
+    S: SessionStore,
+{
+    /// Create the node, resuming the session from `store` if one was saved
+    pub async fn new(radio: R, timer: T, rng: G, config: LorawanConfig, mut store: S) -> Self {
+        let mut us915 = region::US915::new();
+        us915.set_join_bias(config.subband);
+
+        let session = store.load().await.map(|mut session| {
+            session.fcnt_up += SESSION_SAVE_INTERVAL;
+            session
+        });
+        let restored = session.is_some();
+        let mut device = Device::new_with_session(us915.into(), radio, timer, rng, session);
+        device.set_datarate(config.data_rate);
+
+        let mut node = Self { device, config, store, unsaved_uplinks: 0 };
+        if restored {
+            node.save_session().await;
+        }
+        node
+    }
+
+    pub fn is_joined(&self) -> bool {
+        self.device.is_joined()
+    }
+
+    /// Activate with the network; a no-op beyond storing the keys for ABP
+    pub async fn join(&mut self) -> Result<(), LorawanError<R::PhyError>> {
+        match self.device.join(&self.config.activation.join_mode()).await? {
+            JoinResponse::JoinSuccess => {
+                self.save_session().await;
+                Ok(())
+            }
+            JoinResponse::NoJoinAccept => Err(LorawanError::NoJoinAccept),
+        }
+    }
+
+    /// Send `payload` on the configured port, joining first if there is no session
+    ///
+    /// The call returns once the RX1/RX2 windows have closed or a downlink has been received.
+    pub async fn send(&mut self, payload: &[u8]) -> Result<(), LorawanError<R::PhyError>> {
+        if !self.is_joined() {
+            self.join().await?;
+        }
+
+        let response = self.device.send(payload, self.config.fport, self.config.confirmed).await?;
+        self.unsaved_uplinks += 1;
+        if self.unsaved_uplinks >= SESSION_SAVE_INTERVAL {
+            self.save_session().await;
+        }
+
+        match response {
+            SendResponse::DownlinkReceived(_) | SendResponse::RxComplete => Ok(()),
+            SendResponse::NoAck => Err(LorawanError::NoAck),
+            SendResponse::SessionExpired => Err(LorawanError::SessionExpired),
+        }
+    }
+
+    async fn save_session(&mut self) {
+        if let Some(session) = self.device.get_session() {
+            self.store.save(session).await;
+        }
+        self.unsaved_uplinks = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use lorawan_device::async_device::radio::{RxConfig, RxQuality, RxStatus, TxConfig};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    const FPORT: u8 = 7;
+    const DEV_ADDR: u32 = 0x2601_1a2b;
+
+    #[derive(Default)]
+    struct Air {
+        uplinks: Vec<Vec<u8>>,
+        rx_windows: usize,
+    }
+
+    /// A radio that records every uplink and never hears a downlink
+    struct SimRadio {
+        air: Rc<RefCell<Air>>,
+    }
+
+    impl PhyRxTx for SimRadio {
+        type PhyError = ();
+        const MAX_RADIO_POWER: u8 = 20;
+
+        async fn tx(&mut self, _config: TxConfig, buf: &[u8]) -> Result<u32, Self::PhyError> {
+            self.air.borrow_mut().uplinks.push(buf.to_vec());
+            Ok(0)
+        }
+
+        async fn setup_rx(&mut self, _config: RxConfig) -> Result<(), Self::PhyError> {
+            self.air.borrow_mut().rx_windows += 1;
+            Ok(())
+        }
+
+        async fn rx_single(&mut self, _buf: &mut [u8]) -> Result<RxStatus, Self::PhyError> {
+            Ok(RxStatus::RxTimeout)
+        }
+
+        async fn rx_continuous(&mut self, _buf: &mut [u8]) -> Result<(usize, RxQuality), Self::PhyError> {
+            Err(())
+        }
+
+        async fn low_power(&mut self) -> Result<(), Self::PhyError> {
+            Ok(())
+        }
+    }
+
+    impl Timings for SimRadio {
+        fn get_rx_window_offset_ms(&self) -> i32 {
+            0
+        }
+
+        fn get_rx_window_duration_ms(&self) -> u32 {
+            100
+        }
+    }
+
+    /// Windows open and close immediately
+    struct SimTimer;
+
+    impl Timer for SimTimer {
+        fn reset(&mut self) {}
+
+        async fn at(&mut self, _millis: u64) {}
+
+        async fn delay_ms(&mut self, _millis: u64) {}
+    }
+
+    struct SimRng(u32);
+
+    impl RngCore for SimRng {
+        fn next_u32(&mut self) -> u32 {
+            self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            self.0
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            (self.next_u32() as u64) << 32 | self.next_u32() as u64
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            rand_core::impls::fill_bytes_via_next(self, dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct MemoryStore {
+        session: Rc<RefCell<Option<Session>>>,
+    }
+
+    impl SessionStore for MemoryStore {
+        async fn load(&mut self) -> Option<Session> {
+            self.session.borrow().clone()
+        }
+
+        async fn save(&mut self, session: &Session) {
+            *self.session.borrow_mut() = Some(session.clone());
+        }
+    }
+
+    fn config(activation: Activation) -> LorawanConfig {
+        LorawanConfig {
+            activation,
+            fport: FPORT,
+            confirmed: false,
+            subband: Subband::_2,
+            data_rate: DR::_3,
+        }
+    }
+
+    fn abp() -> Activation {
+        Activation::Abp { dev_addr: DEV_ADDR, nwk_skey: [0x11; 16], app_skey: [0x22; 16] }
+    }
+
+    async fn node(air: &Rc<RefCell<Air>>, activation: Activation, store: MemoryStore)
+        -> LorawanNode<SimRadio, SimTimer, SimRng, MemoryStore> {
+        LorawanNode::new(SimRadio { air: air.clone() }, SimTimer, SimRng(1), config(activation), store).await
+    }
+
+    // MHDR | DevAddr (4) | FCtrl | FCnt (2) | FPort | FRMPayload | MIC (4)
+    fn fcnt(frame: &[u8]) -> u16 {
+        u16::from_le_bytes([frame[6], frame[7]])
+    }
+
+    #[tokio::test]
+    async fn otaa_join_listens_in_rx1_and_rx2() {
+        let air = Rc::new(RefCell::new(Air::default()));
+        let otaa = Activation::Otaa { dev_eui: [0x01; 8], app_eui: [0x02; 8], app_key: [0x03; 16] };
+        let store = MemoryStore::default();
+        let mut node = node(&air, otaa, store.clone()).await;
+
+        let err = node.join().await.unwrap_err();
+        assert!(matches!(err, LorawanError::NoJoinAccept));
+        assert!(!node.is_joined());
+        assert!(store.session.borrow().is_none());
+
+        let air = air.borrow();
+        assert_eq!(air.uplinks.len(), 1);
+        // join request: MHDR | AppEUI | DevEUI | DevNonce | MIC
+        assert_eq!(air.uplinks[0].len(), 23);
+        assert_eq!(air.uplinks[0][0], 0x00);
+        assert_eq!(air.rx_windows, 2);
+    }
+
+    #[tokio::test]
+    async fn send_uses_configured_fport() {
+        let air = Rc::new(RefCell::new(Air::default()));
+        let mut node = node(&air, abp(), MemoryStore::default()).await;
+
+        node.send(&[0xaa; 8]).await.unwrap();
+
+        let air = air.borrow();
+        let frame = &air.uplinks[0];
+        // unconfirmed data up
+        assert_eq!(frame[0], 0x40);
+        assert_eq!(&frame[1..5], &DEV_ADDR.to_le_bytes());
+        assert_eq!(fcnt(frame), 0);
+        assert_eq!(frame[8], FPORT);
+        assert_eq!(frame.len(), 9 + 8 + 4);
+    }
+
+    #[tokio::test]
+    async fn send_joins_once() {
+        let air = Rc::new(RefCell::new(Air::default()));
+        let mut node = node(&air, abp(), MemoryStore::default()).await;
+
+        node.send(&[0xaa; 8]).await.unwrap();
+        node.send(&[0xaa; 8]).await.unwrap();
+
+        let air = air.borrow();
+        assert_eq!(air.uplinks.len(), 2);
+        assert_eq!(fcnt(&air.uplinks[1]), 1);
+    }
+
+    #[tokio::test]
+    async fn restored_session_skips_unsaved_frame_counts() {
+        let air = Rc::new(RefCell::new(Air::default()));
+        let store = MemoryStore::default();
+        let mut first_boot = node(&air, abp(), store.clone()).await;
+        first_boot.send(&[0xaa; 8]).await.unwrap();
+        first_boot.send(&[0xaa; 8]).await.unwrap();
+
+        let mut second_boot = node(&air, abp(), store.clone()).await;
+        assert!(second_boot.is_joined());
+        second_boot.send(&[0xaa; 8]).await.unwrap();
+
+        let air = air.borrow();
+        assert_eq!(fcnt(&air.uplinks[2]), SESSION_SAVE_INTERVAL as u16);
+    }
+
+    #[tokio::test]
+    async fn reboots_before_a_save_never_reuse_frame_counts() {
+        let air = Rc::new(RefCell::new(Air::default()));
+        let store = MemoryStore::default();
+        let mut first_boot = node(&air, abp(), store.clone()).await;
+        first_boot.send(&[0xaa; 8]).await.unwrap();
+
+        let mut second_boot = node(&air, abp(), store.clone()).await;
+        second_boot.send(&[0xaa; 8]).await.unwrap();
+        let mut third_boot = node(&air, abp(), store.clone()).await;
+        third_boot.send(&[0xaa; 8]).await.unwrap();
+
+        let air = air.borrow();
+        assert_eq!(fcnt(&air.uplinks[1]), SESSION_SAVE_INTERVAL as u16);
+        assert_eq!(fcnt(&air.uplinks[2]), 2 * SESSION_SAVE_INTERVAL as u16);
+    }
+
+    #[tokio::test]
+    async fn session_saved_every_interval() {
+        let air = Rc::new(RefCell::new(Air::default()));
+        let store = MemoryStore::default();
+        let mut node = node(&air, abp(), store.clone()).await;
+
+        for _ in 0..SESSION_SAVE_INTERVAL {
+            node.send(&[0xaa; 8]).await.unwrap();
+        }
+
+        let saved = store.session.borrow().clone().unwrap();
+        assert_eq!(saved.fcnt_up, SESSION_SAVE_INTERVAL);
+    }
+}
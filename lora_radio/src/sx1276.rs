@@ -0,0 +1,279 @@
+use embassy_embedded_hal::shared_bus::asynch::spi::SpiDevice;
+use embassy_rp::clocks::RoscRng;
+use embassy_rp::gpio::{Input, Output};
+use embassy_rp::peripherals::SPI1;
+use embassy_rp::spi::{Async, Spi};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Delay, Timer};
+use lora_phy::{sx127x, LoRa, RxMode};
+use lora_phy::iv::GenericSx127xInterfaceVariant;
+use lora_phy::lorawan_radio::LorawanRadio;
+use lora_phy::mod_params::{Bandwidth, CodingRate, ModulationParams, PacketParams, RadioError, SpreadingFactor};
+use lora_phy::sx127x::{Sx1276, Sx127x};
+use suspend::Suspend;
+use crate::lbt::{LbtStats, ListenBeforeTalk};
+
+const PREAMBLE_LENGTH: u16 = 4;
+const IMPLICIT_HEADER: bool = false;
+const CRC_ON: bool = true;
+const IQ_INVERTED: bool = false;
+const SPREADING_FACTOR: SpreadingFactor = SpreadingFactor::_10;
+const BANDWIDTH: Bandwidth = Bandwidth::_250KHz;
+const CODING_RATE: CodingRate = CodingRate::_4_8;
+// largest PHY payload the LoRaWAN MAC will hand to the radio
+const LORAWAN_MAX_PAYLOAD: usize = 255;
+const MAX_RX_PAYLOAD: u8 = 255;
+
+type Sx1276Kind = Sx127x<SpiDevice<'static, NoopRawMutex, Spi<'static, SPI1, Async>, Output<'static>>, GenericSx127xInterfaceVariant<Output<'static>, Input<'static>>, Sx1276>;
+
+/// SX1276 wired up as a LoRaWAN PHY, ready to be handed to [`crate::lorawan::LorawanNode`]
+pub type Sx1276LorawanRadio = LorawanRadio<Sx1276Kind, Delay, LORAWAN_MAX_PAYLOAD>;
+
+async fn sx1276(
+    spi_bus: &'static Mutex<NoopRawMutex, Spi<'static, SPI1, Async>>,
+    chip_select: Output<'static>,
+    reset: Output<'static>,
+    dio0: Input<'static>,
+) -> LoRa<Sx1276Kind, Delay> {
+    let spi_device = SpiDevice::new(spi_bus, chip_select);
+    let config = sx127x::Config {
+        chip: Sx1276,
+        tcxo_used: false,
+        tx_boost: false,
+        rx_boost: false,
+    };
+
+    let iv = GenericSx127xInterfaceVariant::new(reset, dio0, None, None).unwrap();
+    LoRa::new(Sx127x::new(spi_device, iv, config), true, Delay).await.unwrap()
+}
+
+#[derive(Debug)]
+pub enum TxError {
+    Radio(RadioError),
+    /// Listen-before-talk gave up waiting for the channel to clear
+    ChannelBusy,
+}
+
+impl From<RadioError> for TxError {
+    fn from(e: RadioError) -> Self {
+        TxError::Radio(e)
+    }
+}
+
+/// Carrier settings for raw mode. The modulation stays fixed so nodes and the gateway only have
+/// to agree on the frequency.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RadioParams {
+    pub frequency: u32,
+    /// dBm; the RFM95 drives PA_BOOST, so 2-20
+    pub output_power: i32,
+}
+
+impl Default for RadioParams {
+    fn default() -> Self {
+        Self {
+            frequency: 915_000_000,
+            output_power: 20,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerState {
+    Standby,
+    /// Registers are retained, so the radio only needs to be brought back to standby
+    WarmSleep,
+    /// Configuration is lost and the radio must be re-initialized on wake
+    ColdSleep,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RxPacket {
+    pub len: usize,
+    pub rssi: i16,
+    pub snr: i16,
+}
+
+pub struct LoraRadio {
+    lora: LoRa<Sx1276Kind, Delay>,
+    mod_params: ModulationParams,
+    packet_params: PacketParams,
+    rx_packet_params: PacketParams,
+    output_power: i32,
+    lbt: Option<ListenBeforeTalk>,
+    lbt_stats: LbtStats,
+    power: PowerState,
+    auto_sleep: bool,
+}
+
+impl LoraRadio {
+    pub async fn new(
+        spi_bus: &'static Mutex<NoopRawMutex, Spi<'static, SPI1, Async>>,
+        chip_select: Output<'static>,
+        reset: Output<'static>,
+        dio0: Input<'static>,
+        params: RadioParams,
+    ) -> Self {
+        let mut lora = sx1276(spi_bus, chip_select, reset, dio0).await;
+        let mod_params = lora.create_modulation_params(SPREADING_FACTOR, BANDWIDTH, CODING_RATE, params.frequency).unwrap();
+        let packet_params = lora.create_tx_packet_params(PREAMBLE_LENGTH, IMPLICIT_HEADER, CRC_ON, IQ_INVERTED, &mod_params).unwrap();
+        let rx_packet_params = lora.create_rx_packet_params(PREAMBLE_LENGTH, IMPLICIT_HEADER, MAX_RX_PAYLOAD, CRC_ON, IQ_INVERTED, &mod_params).unwrap();
+
+        LoraRadio {
+            lora,
+            mod_params,
+            packet_params,
+            rx_packet_params,
+            output_power: params.output_power,
+            lbt: None,
+            lbt_stats: LbtStats::default(),
+            power: PowerState::Standby,
+            auto_sleep: true,
+        }
+    }
+
+    /// Warm-sleep after every transmission (the default), or leave the radio in standby
+    pub fn set_auto_sleep(&mut self, auto_sleep: bool) {
+        self.auto_sleep = auto_sleep;
+    }
+
+    pub fn power_state(&self) -> PowerState {
+        self.power
+    }
+
+    /// Put the SX1276 to sleep, ~0.2uA vs ~1.6mA in standby
+    ///
+    /// A warm start keeps the modulation config so the next `wake()` is just a mode change.
+    pub async fn sleep(&mut self, warm_start: bool) -> Result<(), RadioError> {
+        self.lora.sleep(warm_start).await?;
+        self.power = if warm_start { PowerState::WarmSleep } else { PowerState::ColdSleep };
+        Ok(())
+    }
+
+    /// Bring the radio back to standby, re-initializing it after a cold sleep
+    pub async fn wake(&mut self) -> Result<(), RadioError> {
+        match self.power {
+            PowerState::Standby => {}
+            PowerState::WarmSleep => self.lora.enter_standby().await?,
+            PowerState::ColdSleep => self.lora.init().await?,
+        }
+        self.power = PowerState::Standby;
+        Ok(())
+    }
+
+    /// Run CAD before every transmission, or transmit blindly with `None`
+    pub fn set_listen_before_talk(&mut self, policy: Option<ListenBeforeTalk>) {
+        self.lbt = policy;
+    }
+
+    pub fn lbt_stats(&self) -> LbtStats {
+        self.lbt_stats
+    }
+
+    /// Channel Activity Detection; returns `true` if a LoRa preamble was heard
+    pub async fn cad(&mut self) -> Result<bool, RadioError> {
+        self.wake().await?;
+        self.lora.prepare_for_cad(&self.mod_params).await?;
+        self.lora.cad(&self.mod_params).await
+    }
+
+    /// Block until a packet is received, listening continuously with the same modulation params
+    /// the nodes transmit with
+    pub async fn rx(&mut self, buffer: &mut [u8]) -> Result<RxPacket, RadioError> {
+        self.wake().await?;
+        self.lora.prepare_for_rx(RxMode::Continuous, &self.mod_params, &self.rx_packet_params).await?;
+        let (len, status) = self.lora.rx(&self.rx_packet_params, buffer).await?;
+        Ok(RxPacket { len: len as usize, rssi: status.rssi, snr: status.snr })
+    }
+
+    async fn wait_for_clear_channel(&mut self, policy: ListenBeforeTalk) -> Result<(), TxError> {
+        let mut attempt = 0;
+        while self.cad().await? {
+            self.lbt_stats.busy += 1;
+            match policy.backoff(attempt, &mut RoscRng) {
+                Some(backoff) => Timer::after(backoff).await,
+                None => {
+                    self.lbt_stats.abandoned += 1;
+                    return Err(TxError::ChannelBusy);
+                }
+            }
+            attempt += 1;
+        }
+        if attempt > 0 {
+            self.lbt_stats.deferred += 1;
+        }
+        Ok(())
+    }
+
+    async fn tx(&mut self, buffer: &[u8]) -> Result<(), TxError> {
+        self.wake().await?;
+        let result = self.tx_awake(buffer).await;
+        // sleep even if the tx failed, otherwise a bad packet leaves the radio burning standby current.
+        // The packet's fate is what the caller needs; a failed sleep only costs standby current.
+        let slept = if self.auto_sleep { self.sleep(true).await } else { Ok(()) };
+        if let Err(e) = slept {
+            log::warn!("radio sleep after tx failed: {:?}", e);
+        }
+        result
+    }
+
+    async fn tx_awake(&mut self, buffer: &[u8]) -> Result<(), TxError> {
+        if let Some(policy) = self.lbt {
+            self.wait_for_clear_channel(policy).await?;
+        }
+        self.lora.prepare_for_tx(&self.mod_params, &mut self.packet_params, self.output_power, buffer).await?;
+        Ok(self.lora.tx().await?)
+    }
+}
+
+/// A warm sleep, so resuming is just a mode change
+impl Suspend for LoraRadio {
+    type Error = RadioError;
+
+    async fn suspend(&mut self) -> Result<(), RadioError> {
+        self.sleep(true).await
+    }
+
+    async fn resume(&mut self) -> Result<(), RadioError> {
+        self.wake().await
+    }
+}
+
+pub async fn radio_tx(radio: &'static Mutex<NoopRawMutex, LoraRadio>, data: &[u8]) -> Result<(), TxError> {
+    let mut radio = radio.lock().await;
+    radio.tx(data).await
+}
+
+pub async fn radio_rx(radio: &'static Mutex<NoopRawMutex, LoraRadio>, buffer: &mut [u8]) -> Result<RxPacket, RadioError> {
+    let mut radio = radio.lock().await;
+    radio.rx(buffer).await
+}
+
+pub async fn radio_sleep(radio: &'static Mutex<NoopRawMutex, LoraRadio>) -> Result<(), RadioError> {
+    let mut radio = radio.lock().await;
+    radio.sleep(true).await
+}
+
+pub async fn radio_wake(radio: &'static Mutex<NoopRawMutex, LoraRadio>) -> Result<(), RadioError> {
+    let mut radio = radio.lock().await;
+    radio.wake().await
+}
+
+pub async fn radio_cad(radio: &'static Mutex<NoopRawMutex, LoraRadio>) -> Result<bool, RadioError> {
+    let mut radio = radio.lock().await;
+    radio.cad().await
+}
+
+/// Bring up the SX1276 for LoRaWAN instead of raw point-to-point LoRa.
+///
+/// Modulation and packet params are owned by the MAC in this mode, so none of the raw-mode
+/// constants apply. The MAC puts the radio to sleep itself once the RX windows close.
+pub async fn lorawan_radio(
+    spi_bus: &'static Mutex<NoopRawMutex, Spi<'static, SPI1, Async>>,
+    chip_select: Output<'static>,
+    reset: Output<'static>,
+    dio0: Input<'static>
+) -> Sx1276LorawanRadio {
+    sx1276(spi_bus, chip_select, reset, dio0).await.into()
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Tracks which nodes have been heard from recently.
+///
+/// A node is online from its first packet until it has been silent for `timeout`.
+pub struct Availability {
+    timeout: Duration,
+    last_seen: HashMap<u16, Instant>,
+    online: HashMap<u16, bool>,
+}
+
+impl Availability {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout, last_seen: HashMap::new(), online: HashMap::new() }
+    }
+
+    /// Record a packet from `node`; returns true if it just came (back) online
+    pub fn seen(&mut self, node: u16, now: Instant) -> bool {
+        self.last_seen.insert(node, now);
+        !self.online.insert(node, true).unwrap_or(false)
+    }
+
+    /// Nodes that have just gone silent for longer than the timeout
+    pub fn expire(&mut self, now: Instant) -> Vec<u16> {
+        let mut expired: Vec<u16> = self.online.iter()
+            .filter(|&(node, online)| *online && now.duration_since(self.last_seen[node]) > self.timeout)
+            .map(|(node, _)| *node)
+            .collect();
+        expired.sort();
+        for node in &expired {
+            self.online.insert(*node, false);
+        }
+        expired
+    }
+
+    pub fn is_online(&self, node: u16) -> bool {
+        self.online.get(&node).copied().unwrap_or(false)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = u16> + '_ {
+        self.online.keys().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn first_packet_brings_node_online() {
+        let mut availability = Availability::new(TIMEOUT);
+        let now = Instant::now();
+        assert!(availability.seen(1, now));
+        assert!(!availability.seen(1, now + Duration::from_secs(3)));
+        assert!(availability.is_online(1));
+    }
+
+    #[test]
+    fn silent_node_expires_once() {
+        let mut availability = Availability::new(TIMEOUT);
+        let start = Instant::now();
+        availability.seen(1, start);
+        availability.seen(2, start + Duration::from_secs(30));
+
+        assert!(availability.expire(start + TIMEOUT).is_empty());
+        assert_eq!(availability.expire(start + TIMEOUT + Duration::from_secs(1)), vec![1]);
+        assert!(availability.expire(start + TIMEOUT + Duration::from_secs(2)).is_empty());
+        assert!(!availability.is_online(1));
+        assert!(availability.is_online(2));
+
+        // and comes back with its next packet
+        assert!(availability.seen(1, start + TIMEOUT * 2));
+    }
+}
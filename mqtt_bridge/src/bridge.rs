@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+use std::time::Instant;
+use anyhow::Result;
+use rumqttc::{Client, QoS};
+use shop_cli::record::Record;
+use crate::availability::Availability;
+use crate::topics::{Topics, METRICS};
+
+pub const ONLINE: &str = "online";
+pub const OFFLINE: &str = "offline";
+
+pub trait Publisher {
+    fn publish(&mut self, topic: String, payload: String, retain: bool) -> Result<()>;
+}
+
+impl Publisher for Client {
+    fn publish(&mut self, topic: String, payload: String, retain: bool) -> Result<()> {
+        Ok(Client::publish(self, topic, QoS::AtLeastOnce, retain, payload)?)
+    }
+}
+
+pub struct Bridge<P: Publisher> {
+    publisher: P,
+    topics: Topics,
+    availability: Availability,
+    announced: HashSet<u16>,
+}
+
+impl<P: Publisher> Bridge<P> {
+    pub fn new(publisher: P, topics: Topics, availability: Availability) -> Self {
+        Self { publisher, topics, availability, announced: HashSet::new() }
+    }
+
+    fn announce(&mut self, node: u16) -> Result<()> {
+        for metric in &METRICS {
+            let payload = self.topics.discovery_payload(node, metric).to_string();
+            self.publisher.publish(self.topics.discovery(node, metric), payload, true)?;
+        }
+        self.announced.insert(node);
+        Ok(())
+    }
+
+    fn publish_availability(&mut self, node: u16, online: bool) -> Result<()> {
+        let payload = if online { ONLINE } else { OFFLINE };
+        self.publisher.publish(self.topics.node_availability(node), payload.into(), true)
+    }
+
+    /// (Re)connected to the broker; a broker restart may have lost retained messages, so
+    /// everything known is published again
+    pub fn connected(&mut self) -> Result<()> {
+        self.publisher.publish(self.topics.bridge_availability(), ONLINE.into(), true)?;
+        let nodes: Vec<u16> = self.availability.nodes().collect();
+        for node in nodes {
+            self.announce(node)?;
+            self.publish_availability(node, self.availability.is_online(node))?;
+        }
+        Ok(())
+    }
+
+    pub fn record(&mut self, record: &Record, now: Instant) -> Result<()> {
+        if !self.announced.contains(&record.node) {
+            self.announce(record.node)?;
+        }
+        if self.availability.seen(record.node, now) {
+            self.publish_availability(record.node, true)?;
+        }
+        for metric in &METRICS {
+            if let Some(value) = (metric.value)(record) {
+                self.publisher.publish(self.topics.state(record.node, metric), value.to_string(), true)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Mark nodes that have gone silent as offline
+    pub fn tick(&mut self, now: Instant) -> Result<()> {
+        for node in self.availability.expire(now) {
+            self.publish_availability(node, false)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+
+    #[derive(Default)]
+    struct Recorder {
+        messages: Vec<(String, String, bool)>,
+    }
+
+    impl Publisher for &mut Recorder {
+        fn publish(&mut self, topic: String, payload: String, retain: bool) -> Result<()> {
+            self.messages.push((topic, payload, retain));
+            Ok(())
+        }
+    }
+
+    impl Recorder {
+        fn payload(&self, topic: &str) -> Option<&str> {
+            self.messages.iter().rev().find(|(t, _, _)| t == topic).map(|(_, p, _)| p.as_str())
+        }
+    }
+
+    fn record(node: u16) -> Record {
+        Record {
+            received_at: None,
+            node,
+            seq: 1,
+            temperature_f: 68,
+            humidity: 48,
+            pm1_0: 8,
+            pm2_5: 12,
+            pm10: 15,
+            battery_mv: None,
+            battery_percent: None,
+            rssi: Some(-87),
+            snr: None,
+        }
+    }
+
+    fn bridge(recorder: &mut Recorder) -> Bridge<&mut Recorder> {
+        let topics = Topics { prefix: "smart_shop".into(), discovery_prefix: "homeassistant".into() };
+        Bridge::new(recorder, topics, Availability::new(TIMEOUT))
+    }
+
+    #[test]
+    fn first_record_announces_node() {
+        let mut recorder = Recorder::default();
+        bridge(&mut recorder).record(&record(1), Instant::now()).unwrap();
+
+        assert!(recorder.payload("homeassistant/sensor/smart_shop_1_temperature/config").is_some());
+        assert_eq!(recorder.payload("smart_shop/1/availability"), Some(ONLINE));
+        assert_eq!(recorder.payload("smart_shop/1/temperature"), Some("68"));
+        assert_eq!(recorder.payload("smart_shop/1/rssi"), Some("-87"));
+        // no SNR in the record, so nothing is published for it
+        assert_eq!(recorder.payload("smart_shop/1/snr"), None);
+        assert!(recorder.messages.iter().all(|(_, _, retain)| *retain));
+    }
+
+    #[test]
+    fn discovery_published_once() {
+        let mut recorder = Recorder::default();
+        let mut bridge = bridge(&mut recorder);
+        let now = Instant::now();
+        bridge.record(&record(1), now).unwrap();
+        bridge.record(&record(1), now).unwrap();
+        drop(bridge);
+
+        let configs = recorder.messages.iter()
+            .filter(|(t, _, _)| t == "homeassistant/sensor/smart_shop_1_humidity/config")
+            .count();
+        assert_eq!(configs, 1);
+    }
+
+    #[test]
+    fn silent_node_goes_offline() {
+        let mut recorder = Recorder::default();
+        let mut bridge = bridge(&mut recorder);
+        let start = Instant::now();
+        bridge.record(&record(1), start).unwrap();
+        bridge.tick(start + TIMEOUT + Duration::from_secs(1)).unwrap();
+        drop(bridge);
+
+        assert_eq!(recorder.payload("smart_shop/1/availability"), Some(OFFLINE));
+    }
+
+    #[test]
+    fn reconnect_republishes_known_nodes() {
+        let mut recorder = Recorder::default();
+        let mut bridge = bridge(&mut recorder);
+        bridge.record(&record(2), Instant::now()).unwrap();
+        bridge.connected().unwrap();
+        drop(bridge);
+
+        let last = &recorder.messages[recorder.messages.len() - 1];
+        assert_eq!(last.0, "smart_shop/2/availability");
+        assert_eq!(recorder.payload("smart_shop/bridge/availability"), Some(ONLINE));
+    }
+
+    /// `mosquitto -p 1883` then `cargo test -p mqtt_bridge -- --ignored`
+    #[test]
+    #[ignore = "needs a local Mosquitto broker on localhost:1883"]
+    fn mosquitto_round_trip() {
+        use rumqttc::{Event, MqttOptions, Packet};
+
+        let (subscriber, mut incoming) = Client::new(MqttOptions::new("mqtt_bridge_test_sub", "localhost", 1883), 16);
+        subscriber.subscribe("smart_shop_test/#", QoS::AtLeastOnce).unwrap();
+
+        let (publisher, mut outgoing) = Client::new(MqttOptions::new("mqtt_bridge_test_pub", "localhost", 1883), 64);
+        std::thread::spawn(move || for _ in outgoing.iter() {});
+        let topics = Topics { prefix: "smart_shop_test".into(), discovery_prefix: "homeassistant_test".into() };
+        let mut bridge = Bridge::new(publisher, topics, Availability::new(TIMEOUT));
+
+        let mut published = false;
+        for notification in incoming.iter() {
+            match notification.unwrap() {
+                Event::Incoming(Packet::SubAck(_)) if !published => {
+                    bridge.record(&record(9), Instant::now()).unwrap();
+                    published = true;
+                }
+                Event::Incoming(Packet::Publish(p)) if p.topic == "smart_shop_test/9/temperature" => {
+                    assert_eq!(&p.payload[..], b"68");
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+mod availability;
+mod bridge;
+mod topics;
+
+use std::io::BufReader;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::{bail, Result};
+use clap::Parser;
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use shop_cli::input::{for_each_line, parse_line, Event as InputEvent};
+use shop_cli::record::{now_millis, Decoded, Record};
+use crate::availability::Availability;
+use crate::bridge::{Bridge, OFFLINE};
+use crate::topics::Topics;
+
+/// Publish smart-shop readings from the gateway's serial port to an MQTT broker
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The gateway's JSON serial port, e.g. /dev/ttyACM1
+    port: String,
+    #[arg(long, default_value_t = 115_200)]
+    baud: u32,
+    #[arg(long, default_value = "localhost")]
+    mqtt_host: String,
+    #[arg(long, default_value_t = 1883)]
+    mqtt_port: u16,
+    #[arg(long, default_value = "smart_shop_bridge")]
+    client_id: String,
+    #[arg(long)]
+    username: Option<String>,
+    #[arg(long, requires = "username")]
+    password: Option<String>,
+    #[arg(long, default_value = "smart_shop")]
+    prefix: String,
+    #[arg(long, default_value = "homeassistant")]
+    discovery_prefix: String,
+    /// Seconds of silence before a node is marked offline
+    #[arg(long, default_value_t = 60)]
+    offline_after: u64,
+}
+
+enum Message {
+    Record(Record),
+    Connected,
+    SerialClosed(Result<()>),
+}
+
+fn read_serial(port: String, baud: u32, tx: Sender<Message>) {
+    let result = serialport::new(&port, baud)
+        .timeout(Duration::from_secs(60))
+        .open()
+        .map_err(anyhow::Error::from)
+        .and_then(|port| for_each_line(BufReader::new(port), |line| {
+            match parse_line(line) {
+                Ok(Some(InputEvent::Frame(frame))) => match Decoded::decode(&frame, Some(now_millis())) {
+                    Ok(Decoded::Reading(record)) => tx.send(Message::Record(record))?,
+                    // only readings have entities to publish to
+                    Ok(_) => {}
+                    Err(e) => eprintln!("{}: {}", e, line.trim()),
+                },
+                Ok(_) => {}
+                Err(e) => eprintln!("{}: {}", e, line.trim()),
+            }
+            Ok(())
+        }));
+    let _ = tx.send(Message::SerialClosed(result));
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let topics = Topics { prefix: args.prefix, discovery_prefix: args.discovery_prefix };
+
+    let mut options = MqttOptions::new(args.client_id, args.mqtt_host, args.mqtt_port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(topics.bridge_availability(), OFFLINE, QoS::AtLeastOnce, true));
+    if let (Some(username), Some(password)) = (args.username, args.password) {
+        options.set_credentials(username, password);
+    }
+    let (client, mut connection) = Client::new(options, 64);
+
+    let (tx, rx) = mpsc::channel();
+    let connection_tx = tx.clone();
+    thread::spawn(move || {
+        // iterating drives the connection, including reconnects
+        for notification in connection.iter() {
+            match notification {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    if connection_tx.send(Message::Connected).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("mqtt: {}", e);
+                    thread::sleep(Duration::from_secs(5));
+                }
+            }
+        }
+    });
+    thread::spawn(move || read_serial(args.port, args.baud, tx));
+
+    let mut bridge = Bridge::new(client, topics, Availability::new(Duration::from_secs(args.offline_after)));
+    loop {
+        match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(Message::Record(record)) => bridge.record(&record, Instant::now())?,
+            Ok(Message::Connected) => bridge.connected()?,
+            Ok(Message::SerialClosed(Ok(()))) => bail!("serial port closed"),
+            Ok(Message::SerialClosed(Err(e))) => return Err(e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => bail!("reader threads exited"),
+        }
+        bridge.tick(Instant::now())?;
+    }
+}
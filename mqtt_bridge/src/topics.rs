@@ -0,0 +1,111 @@
+use serde_json::{json, Value};
+use shop_cli::record::Record;
+
+pub struct Metric {
+    pub key: &'static str,
+    pub name: &'static str,
+    pub unit: &'static str,
+    /// Home Assistant sensor device class
+    pub device_class: &'static str,
+    /// Radio and battery metrics are diagnostics rather than readings
+    pub diagnostic: bool,
+    pub value: fn(&Record) -> Option<i64>,
+}
+
+pub const METRICS: [Metric; 8] = [
+    Metric { key: "temperature", name: "Temperature", unit: "°F", device_class: "temperature", diagnostic: false, value: |r| Some(r.temperature_f.into()) },
+    Metric { key: "humidity", name: "Humidity", unit: "%", device_class: "humidity", diagnostic: false, value: |r| Some(r.humidity.into()) },
+    Metric { key: "pm1_0", name: "PM1.0", unit: "µg/m³", device_class: "pm1", diagnostic: false, value: |r| Some(r.pm1_0.into()) },
+    Metric { key: "pm2_5", name: "PM2.5", unit: "µg/m³", device_class: "pm25", diagnostic: false, value: |r| Some(r.pm2_5.into()) },
+    Metric { key: "pm10", name: "PM10", unit: "µg/m³", device_class: "pm10", diagnostic: false, value: |r| Some(r.pm10.into()) },
+    Metric { key: "rssi", name: "RSSI", unit: "dBm", device_class: "signal_strength", diagnostic: true, value: |r| r.rssi.map(i64::from) },
+    Metric { key: "snr", name: "SNR", unit: "dB", device_class: "signal_strength", diagnostic: true, value: |r| r.snr.map(i64::from) },
+    Metric { key: "battery", name: "Battery", unit: "%", device_class: "battery", diagnostic: true, value: |r| r.battery_percent.map(i64::from) },
+];
+
+/// Topic layout: `<prefix>/<node>/<metric>` for values, `<prefix>/<node>/availability` per node,
+/// `<prefix>/bridge/availability` for the bridge itself (its last will) and Home Assistant
+/// discovery under `<discovery_prefix>/sensor/...`
+pub struct Topics {
+    pub prefix: String,
+    pub discovery_prefix: String,
+}
+
+impl Topics {
+    pub fn state(&self, node: u16, metric: &Metric) -> String {
+        format!("{}/{}/{}", self.prefix, node, metric.key)
+    }
+
+    pub fn node_availability(&self, node: u16) -> String {
+        format!("{}/{}/availability", self.prefix, node)
+    }
+
+    pub fn bridge_availability(&self) -> String {
+        format!("{}/bridge/availability", self.prefix)
+    }
+
+    fn object_id(&self, node: u16, metric: &Metric) -> String {
+        format!("{}_{}_{}", self.prefix, node, metric.key)
+    }
+
+    pub fn discovery(&self, node: u16, metric: &Metric) -> String {
+        format!("{}/sensor/{}/config", self.discovery_prefix, self.object_id(node, metric))
+    }
+
+    pub fn discovery_payload(&self, node: u16, metric: &Metric) -> Value {
+        let mut payload = json!({
+            "name": metric.name,
+            "unique_id": self.object_id(node, metric),
+            "state_topic": self.state(node, metric),
+            "unit_of_measurement": metric.unit,
+            "device_class": metric.device_class,
+            "state_class": "measurement",
+            "availability": [
+                { "topic": self.bridge_availability() },
+                { "topic": self.node_availability(node) },
+            ],
+            "availability_mode": "all",
+            "device": {
+                "identifiers": [format!("{}_{}", self.prefix, node)],
+                "name": format!("Smart Shop node {}", node),
+                "manufacturer": "ardentTech",
+                "model": "Feather RP2040 RFM95 env_sensor",
+            },
+        });
+        if metric.diagnostic {
+            payload["entity_category"] = json!("diagnostic");
+        }
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topics() -> Topics {
+        Topics { prefix: "smart_shop".into(), discovery_prefix: "homeassistant".into() }
+    }
+
+    #[test]
+    fn topic_layout() {
+        let topics = topics();
+        assert_eq!(topics.state(3, &METRICS[0]), "smart_shop/3/temperature");
+        assert_eq!(topics.node_availability(3), "smart_shop/3/availability");
+        assert_eq!(topics.bridge_availability(), "smart_shop/bridge/availability");
+        assert_eq!(topics.discovery(3, &METRICS[3]), "homeassistant/sensor/smart_shop_3_pm2_5/config");
+    }
+
+    #[test]
+    fn discovery_payload() {
+        let payload = topics().discovery_payload(3, &METRICS[1]);
+        assert_eq!(payload["state_topic"], "smart_shop/3/humidity");
+        assert_eq!(payload["unique_id"], "smart_shop_3_humidity");
+        assert_eq!(payload["device"]["identifiers"][0], "smart_shop_3");
+        assert_eq!(payload["availability"][1]["topic"], "smart_shop/3/availability");
+        assert!(payload.get("entity_category").is_none());
+
+        let rssi = topics().discovery_payload(3, &METRICS[5]);
+        assert_eq!(rssi["entity_category"], "diagnostic");
+    }
+}
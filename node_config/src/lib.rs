@@ -0,0 +1,155 @@
+#![no_std]
+
+//! Settings a node keeps in flash, so they can change without reflashing.
+//!
+//! The [`Config`] is written as a versioned, CRC-checked [`record`] into a `sequential-storage`
+//! map, which spreads writes across the reserved sectors instead of erasing one sector every
+//! time. These types are the stored layout, so they only change together with
+//! [`record::VERSION`]; the firmware maps them onto its radio and display settings.
+
+pub mod record;
+pub mod store;
+
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// Identifies the node in every packet; unique per node
+    pub node_id: u16,
+    pub intervals: Intervals,
+    pub radio: RadioConfig,
+    pub display: DisplayTimeouts,
+    pub thresholds: Thresholds,
+    /// Power the sensors, radio and panel down between uses and let the RP2040 sleep; for nodes
+    /// running on a battery
+    pub low_power: bool,
+    pub alerts: AlertRules,
+    pub lorawan: LorawanKeys,
+}
+
+/// How often each job runs; the uplink sends the latest readings, so it's no use setting it
+/// shorter than the sensors
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Intervals {
+    /// SHT30
+    pub temp_humidity_secs: u16,
+    /// PMSA003I
+    pub air_quality_secs: u16,
+    pub uplink_secs: u16,
+}
+
+/// Raw LoRa carrier; the gateway has to listen on the same frequency
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RadioConfig {
+    pub frequency_hz: u32,
+    pub output_power_dbm: i8,
+}
+
+/// `None` never dims, or never turns the panel off
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DisplayTimeouts {
+    pub dim_after_secs: Option<u16>,
+    pub off_after_secs: Option<u16>,
+}
+
+/// Readings past these flash the display's alert; `None` disables a limit
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Thresholds {
+    pub aqi_max: Option<u16>,
+    pub pm2_5_max: Option<u16>,
+    /// Degrees F
+    pub temperature_min: Option<u16>,
+    pub temperature_max: Option<u16>,
+    /// %RH
+    pub humidity_max: Option<u16>,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            // past "Moderate", and the old 24 hour PM2.5 standard
+            aqi_max: Some(100),
+            pm2_5_max: Some(35),
+            temperature_min: Some(40),
+            temperature_max: Some(95),
+            // tools start to rust
+            humidity_max: Some(70),
+        }
+    }
+}
+
+/// Limits on one reading that raise a local alert; see the `alerts` crate
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    /// `None` disables a limit
+    pub low: Option<u16>,
+    pub high: Option<u16>,
+    pub hysteresis: u16,
+    pub min_duration_secs: u16,
+    pub cooldown_secs: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlertRules {
+    pub pm2_5: AlertRule,
+    /// Degrees F
+    pub temperature: AlertRule,
+    /// %RH
+    pub humidity: AlertRule,
+    /// Drive the alert output (a relay or buzzer) while any alert is raised
+    pub output: bool,
+}
+
+impl Default for AlertRules {
+    // every limit off, with the rest ready for when one is set
+    fn default() -> Self {
+        let rule = |hysteresis| AlertRule { low: None, high: None, hysteresis, min_duration_secs: 60, cooldown_secs: 300 };
+        Self { pm2_5: rule(5), temperature: rule(2), humidity: rule(3), output: false }
+    }
+}
+
+/// OTAA credentials; they must match the device registered with the network server. All zeros
+/// until the node is provisioned.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LorawanKeys {
+    pub dev_eui: [u8; 8],
+    pub app_eui: [u8; 8],
+    pub app_key: [u8; 16],
+}
+
+// the config is logged at boot; the AppKey stays out of the log
+impl fmt::Debug for LorawanKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LorawanKeys")
+            .field("dev_eui", &self.dev_eui)
+            .field("app_eui", &self.app_eui)
+            .finish_non_exhaustive()
+    }
+}
+
+// what the firmware was built with before any of this was configurable
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            node_id: 1,
+            intervals: Intervals {
+                temp_humidity_secs: 3,
+                air_quality_secs: 3,
+                uplink_secs: 3,
+            },
+            radio: RadioConfig {
+                frequency_hz: 915_000_000,
+                output_power_dbm: 20,
+            },
+            display: DisplayTimeouts {
+                dim_after_secs: Some(30),
+                off_after_secs: Some(120),
+            },
+            thresholds: Thresholds::default(),
+            low_power: false,
+            alerts: AlertRules::default(),
+            lorawan: LorawanKeys::default(),
+        }
+    }
+}
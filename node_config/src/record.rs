@@ -0,0 +1,578 @@
+//! The stored form of a [`Config`]: `[version][postcard body][CRC-32 of both, little endian]`.
+//!
+//! The map already checksums its items, but a record can outlive the firmware that wrote it, so
+//! it carries its own version and CRC. When `Config` changes, keep the old layout in this module,
+//! bump `VERSION` and teach `migrate` to upgrade it; a record from newer firmware is refused
+//! rather than misread.
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+use crate::{AlertRule, AlertRules, Config, DisplayTimeouts, Intervals, LorawanKeys, RadioConfig, Thresholds};
+
+pub const VERSION: u8 = 5;
+/// Room for the largest `Config`, with every option set and every varint at full width
+pub const MAX_RECORD_SIZE: usize = 160;
+
+const CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+const CRC_SIZE: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordError {
+    /// Too short to hold a version and CRC
+    Truncated,
+    Crc,
+    /// Written by firmware this one can't migrate from
+    Version(u8),
+    Decode,
+    Encode,
+}
+
+pub fn encode<'a>(config: &Config, buffer: &'a mut [u8; MAX_RECORD_SIZE]) -> Result<&'a [u8], RecordError> {
+    buffer[0] = VERSION;
+    let body_len = postcard::to_slice(config, &mut buffer[1..MAX_RECORD_SIZE - CRC_SIZE])
+        .map_err(|_| RecordError::Encode)?
+        .len();
+    let end = 1 + body_len;
+    let crc = CRC.checksum(&buffer[..end]);
+    buffer[end..end + CRC_SIZE].copy_from_slice(&crc.to_le_bytes());
+    Ok(&buffer[..end + CRC_SIZE])
+}
+
+pub fn decode(record: &[u8]) -> Result<Config, RecordError> {
+    if record.len() <= CRC_SIZE {
+        return Err(RecordError::Truncated);
+    }
+    let (data, crc) = record.split_at(record.len() - CRC_SIZE);
+    if CRC.checksum(data).to_le_bytes() != crc {
+        return Err(RecordError::Crc);
+    }
+    migrate(data[0], &data[1..])
+}
+
+// Each old layout is a verbatim copy, sub-structs included, so later changes to the live types
+// can't alter how an old record decodes.
+
+/// Version 1, with one interval for both sensors and the uplink
+mod v1 {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Config {
+        pub node_id: u16,
+        pub sample_interval_secs: u16,
+        pub radio: RadioConfig,
+        pub display: DisplayTimeouts,
+        pub thresholds: Thresholds,
+    }
+
+    #[derive(Deserialize)]
+    pub struct RadioConfig {
+        pub frequency_hz: u32,
+        pub output_power_dbm: i8,
+    }
+
+    #[derive(Deserialize)]
+    pub struct DisplayTimeouts {
+        pub dim_after_secs: Option<u16>,
+        pub off_after_secs: Option<u16>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Thresholds {
+        pub aqi_max: Option<u16>,
+        pub pm2_5_max: Option<u16>,
+        pub temperature_min: Option<u16>,
+        pub temperature_max: Option<u16>,
+        pub humidity_max: Option<u16>,
+    }
+}
+
+/// Version 2, before low-power mode
+mod v2 {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Config {
+        pub node_id: u16,
+        pub intervals: Intervals,
+        pub radio: RadioConfig,
+        pub display: DisplayTimeouts,
+        pub thresholds: Thresholds,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Intervals {
+        pub temp_humidity_secs: u16,
+        pub air_quality_secs: u16,
+        pub uplink_secs: u16,
+    }
+
+    #[derive(Deserialize)]
+    pub struct RadioConfig {
+        pub frequency_hz: u32,
+        pub output_power_dbm: i8,
+    }
+
+    #[derive(Deserialize)]
+    pub struct DisplayTimeouts {
+        pub dim_after_secs: Option<u16>,
+        pub off_after_secs: Option<u16>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Thresholds {
+        pub aqi_max: Option<u16>,
+        pub pm2_5_max: Option<u16>,
+        pub temperature_min: Option<u16>,
+        pub temperature_max: Option<u16>,
+        pub humidity_max: Option<u16>,
+    }
+}
+
+/// Version 3, before alert rules
+mod v3 {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Config {
+        pub node_id: u16,
+        pub intervals: Intervals,
+        pub radio: RadioConfig,
+        pub display: DisplayTimeouts,
+        pub thresholds: Thresholds,
+        pub low_power: bool,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Intervals {
+        pub temp_humidity_secs: u16,
+        pub air_quality_secs: u16,
+        pub uplink_secs: u16,
+    }
+
+    #[derive(Deserialize)]
+    pub struct RadioConfig {
+        pub frequency_hz: u32,
+        pub output_power_dbm: i8,
+    }
+
+    #[derive(Deserialize)]
+    pub struct DisplayTimeouts {
+        pub dim_after_secs: Option<u16>,
+        pub off_after_secs: Option<u16>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Thresholds {
+        pub aqi_max: Option<u16>,
+        pub pm2_5_max: Option<u16>,
+        pub temperature_min: Option<u16>,
+        pub temperature_max: Option<u16>,
+        pub humidity_max: Option<u16>,
+    }
+}
+
+/// Version 4, before the LoRaWAN keys
+mod v4 {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Config {
+        pub node_id: u16,
+        pub intervals: Intervals,
+        pub radio: RadioConfig,
+        pub display: DisplayTimeouts,
+        pub thresholds: Thresholds,
+        pub low_power: bool,
+        pub alerts: AlertRules,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Intervals {
+        pub temp_humidity_secs: u16,
+        pub air_quality_secs: u16,
+        pub uplink_secs: u16,
+    }
+
+    #[derive(Deserialize)]
+    pub struct RadioConfig {
+        pub frequency_hz: u32,
+        pub output_power_dbm: i8,
+    }
+
+    #[derive(Deserialize)]
+    pub struct DisplayTimeouts {
+        pub dim_after_secs: Option<u16>,
+        pub off_after_secs: Option<u16>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Thresholds {
+        pub aqi_max: Option<u16>,
+        pub pm2_5_max: Option<u16>,
+        pub temperature_min: Option<u16>,
+        pub temperature_max: Option<u16>,
+        pub humidity_max: Option<u16>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct AlertRule {
+        pub low: Option<u16>,
+        pub high: Option<u16>,
+        pub hysteresis: u16,
+        pub min_duration_secs: u16,
+        pub cooldown_secs: u16,
+    }
+
+    #[derive(Deserialize)]
+    pub struct AlertRules {
+        pub pm2_5: AlertRule,
+        pub temperature: AlertRule,
+        pub humidity: AlertRule,
+        pub output: bool,
+    }
+}
+
+impl From<v1::RadioConfig> for v2::RadioConfig {
+    fn from(old: v1::RadioConfig) -> Self {
+        v2::RadioConfig { frequency_hz: old.frequency_hz, output_power_dbm: old.output_power_dbm }
+    }
+}
+
+impl From<v1::DisplayTimeouts> for v2::DisplayTimeouts {
+    fn from(old: v1::DisplayTimeouts) -> Self {
+        v2::DisplayTimeouts { dim_after_secs: old.dim_after_secs, off_after_secs: old.off_after_secs }
+    }
+}
+
+impl From<v1::Thresholds> for v2::Thresholds {
+    fn from(old: v1::Thresholds) -> Self {
+        v2::Thresholds {
+            aqi_max: old.aqi_max,
+            pm2_5_max: old.pm2_5_max,
+            temperature_min: old.temperature_min,
+            temperature_max: old.temperature_max,
+            humidity_max: old.humidity_max,
+        }
+    }
+}
+
+impl From<v1::Config> for v2::Config {
+    fn from(old: v1::Config) -> Self {
+        let interval = old.sample_interval_secs;
+        v2::Config {
+            node_id: old.node_id,
+            intervals: v2::Intervals { temp_humidity_secs: interval, air_quality_secs: interval, uplink_secs: interval },
+            radio: old.radio.into(),
+            display: old.display.into(),
+            thresholds: old.thresholds.into(),
+        }
+    }
+}
+
+impl From<v2::Intervals> for v3::Intervals {
+    fn from(old: v2::Intervals) -> Self {
+        v3::Intervals {
+            temp_humidity_secs: old.temp_humidity_secs,
+            air_quality_secs: old.air_quality_secs,
+            uplink_secs: old.uplink_secs,
+        }
+    }
+}
+
+impl From<v2::RadioConfig> for v3::RadioConfig {
+    fn from(old: v2::RadioConfig) -> Self {
+        v3::RadioConfig { frequency_hz: old.frequency_hz, output_power_dbm: old.output_power_dbm }
+    }
+}
+
+impl From<v2::DisplayTimeouts> for v3::DisplayTimeouts {
+    fn from(old: v2::DisplayTimeouts) -> Self {
+        v3::DisplayTimeouts { dim_after_secs: old.dim_after_secs, off_after_secs: old.off_after_secs }
+    }
+}
+
+impl From<v2::Thresholds> for v3::Thresholds {
+    fn from(old: v2::Thresholds) -> Self {
+        v3::Thresholds {
+            aqi_max: old.aqi_max,
+            pm2_5_max: old.pm2_5_max,
+            temperature_min: old.temperature_min,
+            temperature_max: old.temperature_max,
+            humidity_max: old.humidity_max,
+        }
+    }
+}
+
+impl From<v2::Config> for v3::Config {
+    fn from(old: v2::Config) -> Self {
+        v3::Config {
+            node_id: old.node_id,
+            intervals: old.intervals.into(),
+            radio: old.radio.into(),
+            display: old.display.into(),
+            thresholds: old.thresholds.into(),
+            low_power: false,
+        }
+    }
+}
+
+impl From<v3::Intervals> for v4::Intervals {
+    fn from(old: v3::Intervals) -> Self {
+        v4::Intervals {
+            temp_humidity_secs: old.temp_humidity_secs,
+            air_quality_secs: old.air_quality_secs,
+            uplink_secs: old.uplink_secs,
+        }
+    }
+}
+
+impl From<v3::RadioConfig> for v4::RadioConfig {
+    fn from(old: v3::RadioConfig) -> Self {
+        v4::RadioConfig { frequency_hz: old.frequency_hz, output_power_dbm: old.output_power_dbm }
+    }
+}
+
+impl From<v3::DisplayTimeouts> for v4::DisplayTimeouts {
+    fn from(old: v3::DisplayTimeouts) -> Self {
+        v4::DisplayTimeouts { dim_after_secs: old.dim_after_secs, off_after_secs: old.off_after_secs }
+    }
+}
+
+impl From<v3::Thresholds> for v4::Thresholds {
+    fn from(old: v3::Thresholds) -> Self {
+        v4::Thresholds {
+            aqi_max: old.aqi_max,
+            pm2_5_max: old.pm2_5_max,
+            temperature_min: old.temperature_min,
+            temperature_max: old.temperature_max,
+            humidity_max: old.humidity_max,
+        }
+    }
+}
+
+impl From<v3::Config> for v4::Config {
+    // alert rules arrived with version 4, all off
+    fn from(old: v3::Config) -> Self {
+        let rule = |hysteresis| v4::AlertRule { low: None, high: None, hysteresis, min_duration_secs: 60, cooldown_secs: 300 };
+        v4::Config {
+            node_id: old.node_id,
+            intervals: old.intervals.into(),
+            radio: old.radio.into(),
+            display: old.display.into(),
+            thresholds: old.thresholds.into(),
+            low_power: old.low_power,
+            alerts: v4::AlertRules { pm2_5: rule(5), temperature: rule(2), humidity: rule(3), output: false },
+        }
+    }
+}
+
+impl From<v4::Intervals> for Intervals {
+    fn from(old: v4::Intervals) -> Self {
+        Intervals {
+            temp_humidity_secs: old.temp_humidity_secs,
+            air_quality_secs: old.air_quality_secs,
+            uplink_secs: old.uplink_secs,
+        }
+    }
+}
+
+impl From<v4::RadioConfig> for RadioConfig {
+    fn from(old: v4::RadioConfig) -> Self {
+        RadioConfig { frequency_hz: old.frequency_hz, output_power_dbm: old.output_power_dbm }
+    }
+}
+
+impl From<v4::DisplayTimeouts> for DisplayTimeouts {
+    fn from(old: v4::DisplayTimeouts) -> Self {
+        DisplayTimeouts { dim_after_secs: old.dim_after_secs, off_after_secs: old.off_after_secs }
+    }
+}
+
+impl From<v4::Thresholds> for Thresholds {
+    fn from(old: v4::Thresholds) -> Self {
+        Thresholds {
+            aqi_max: old.aqi_max,
+            pm2_5_max: old.pm2_5_max,
+            temperature_min: old.temperature_min,
+            temperature_max: old.temperature_max,
+            humidity_max: old.humidity_max,
+        }
+    }
+}
+
+impl From<v4::AlertRule> for AlertRule {
+    fn from(old: v4::AlertRule) -> Self {
+        AlertRule {
+            low: old.low,
+            high: old.high,
+            hysteresis: old.hysteresis,
+            min_duration_secs: old.min_duration_secs,
+            cooldown_secs: old.cooldown_secs,
+        }
+    }
+}
+
+impl From<v4::AlertRules> for AlertRules {
+    fn from(old: v4::AlertRules) -> Self {
+        AlertRules {
+            pm2_5: old.pm2_5.into(),
+            temperature: old.temperature.into(),
+            humidity: old.humidity.into(),
+            output: old.output,
+        }
+    }
+}
+
+impl From<v4::Config> for Config {
+    // unprovisioned until the keys are set
+    fn from(old: v4::Config) -> Self {
+        Config {
+            node_id: old.node_id,
+            intervals: old.intervals.into(),
+            radio: old.radio.into(),
+            display: old.display.into(),
+            thresholds: old.thresholds.into(),
+            low_power: old.low_power,
+            alerts: old.alerts.into(),
+            lorawan: LorawanKeys::default(),
+        }
+    }
+}
+
+/// Decodes a body written as `version`, upgrading older layouts to the current `Config`
+fn migrate(version: u8, body: &[u8]) -> Result<Config, RecordError> {
+    match version {
+        1 => postcard::from_bytes::<v1::Config>(body)
+            .map(|old| Config::from(v4::Config::from(v3::Config::from(v2::Config::from(old)))))
+            .map_err(|_| RecordError::Decode),
+        2 => postcard::from_bytes::<v2::Config>(body)
+            .map(|old| Config::from(v4::Config::from(v3::Config::from(old))))
+            .map_err(|_| RecordError::Decode),
+        3 => postcard::from_bytes::<v3::Config>(body)
+            .map(|old| Config::from(v4::Config::from(old)))
+            .map_err(|_| RecordError::Decode),
+        4 => postcard::from_bytes::<v4::Config>(body).map(Config::from).map_err(|_| RecordError::Decode),
+        VERSION => postcard::from_bytes(body).map_err(|_| RecordError::Decode),
+        other => Err(RecordError::Version(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn largest() -> Config {
+        let rule = AlertRule {
+            low: Some(u16::MAX),
+            high: Some(u16::MAX),
+            hysteresis: u16::MAX,
+            min_duration_secs: u16::MAX,
+            cooldown_secs: u16::MAX,
+        };
+        Config {
+            node_id: u16::MAX,
+            intervals: Intervals { temp_humidity_secs: u16::MAX, air_quality_secs: u16::MAX, uplink_secs: u16::MAX },
+            radio: RadioConfig { frequency_hz: u32::MAX, output_power_dbm: i8::MIN },
+            display: DisplayTimeouts { dim_after_secs: Some(u16::MAX), off_after_secs: Some(u16::MAX) },
+            thresholds: Thresholds {
+                aqi_max: Some(u16::MAX),
+                pm2_5_max: Some(u16::MAX),
+                temperature_min: Some(u16::MAX),
+                temperature_max: Some(u16::MAX),
+                humidity_max: Some(u16::MAX),
+            },
+            low_power: true,
+            alerts: AlertRules { pm2_5: rule, temperature: rule, humidity: rule, output: true },
+            lorawan: LorawanKeys { dev_eui: [0xff; 8], app_eui: [0xff; 8], app_key: [0xff; 16] },
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        for config in [Config::default(), largest()] {
+            let mut buffer = [0; MAX_RECORD_SIZE];
+            let record = encode(&config, &mut buffer).unwrap();
+            assert_eq!(decode(record), Ok(config));
+        }
+    }
+
+    #[test]
+    fn rejects_corruption() {
+        let mut buffer = [0; MAX_RECORD_SIZE];
+        let len = encode(&Config::default(), &mut buffer).unwrap().len();
+        buffer[3] ^= 0x01;
+        assert_eq!(decode(&buffer[..len]), Err(RecordError::Crc));
+        assert_eq!(decode(&buffer[..CRC_SIZE]), Err(RecordError::Truncated));
+    }
+
+    #[test]
+    fn migrates_version_1() {
+        // a version 1 record of node 4 sampling every 60s, otherwise the defaults
+        let defaults = Config::default();
+        let mut buffer = [0; MAX_RECORD_SIZE];
+        buffer[0] = 1;
+        let body = (4u16, 60u16, defaults.radio, defaults.display, defaults.thresholds);
+        let len = 1 + postcard::to_slice(&body, &mut buffer[1..]).unwrap().len();
+        let crc = CRC.checksum(&buffer[..len]);
+        buffer[len..len + CRC_SIZE].copy_from_slice(&crc.to_le_bytes());
+
+        let config = decode(&buffer[..len + CRC_SIZE]).unwrap();
+        assert_eq!(config.node_id, 4);
+        assert_eq!(config.intervals, Intervals { temp_humidity_secs: 60, air_quality_secs: 60, uplink_secs: 60 });
+        assert_eq!(config.thresholds, defaults.thresholds);
+    }
+
+    #[test]
+    fn migrates_version_2() {
+        let defaults = Config::default();
+        let mut buffer = [0; MAX_RECORD_SIZE];
+        buffer[0] = 2;
+        let intervals = Intervals { temp_humidity_secs: 30, air_quality_secs: 300, uplink_secs: 60 };
+        let body = (7u16, intervals, defaults.radio, defaults.display, defaults.thresholds);
+        let len = 1 + postcard::to_slice(&body, &mut buffer[1..]).unwrap().len();
+        let crc = CRC.checksum(&buffer[..len]);
+        buffer[len..len + CRC_SIZE].copy_from_slice(&crc.to_le_bytes());
+
+        let config = decode(&buffer[..len + CRC_SIZE]).unwrap();
+        assert_eq!(config, Config { node_id: 7, intervals, ..defaults });
+    }
+
+    #[test]
+    fn migrates_version_3() {
+        let defaults = Config::default();
+        let mut buffer = [0; MAX_RECORD_SIZE];
+        buffer[0] = 3;
+        let body = (9u16, defaults.intervals, defaults.radio, defaults.display, defaults.thresholds, true);
+        let len = 1 + postcard::to_slice(&body, &mut buffer[1..]).unwrap().len();
+        let crc = CRC.checksum(&buffer[..len]);
+        buffer[len..len + CRC_SIZE].copy_from_slice(&crc.to_le_bytes());
+
+        let config = decode(&buffer[..len + CRC_SIZE]).unwrap();
+        assert_eq!(config, Config { node_id: 9, low_power: true, ..defaults });
+    }
+
+    #[test]
+    fn migrates_version_4() {
+        let defaults = Config::default();
+        let alerts = AlertRules { output: true, ..defaults.alerts };
+        let mut buffer = [0; MAX_RECORD_SIZE];
+        buffer[0] = 4;
+        let body = (3u16, defaults.intervals, defaults.radio, defaults.display, defaults.thresholds, false, alerts);
+        let len = 1 + postcard::to_slice(&body, &mut buffer[1..]).unwrap().len();
+        let crc = CRC.checksum(&buffer[..len]);
+        buffer[len..len + CRC_SIZE].copy_from_slice(&crc.to_le_bytes());
+
+        let config = decode(&buffer[..len + CRC_SIZE]).unwrap();
+        assert_eq!(config, Config { node_id: 3, alerts, ..defaults });
+    }
+
+    #[test]
+    fn refuses_newer_versions() {
+        let mut buffer = [0; MAX_RECORD_SIZE];
+        let len = encode(&Config::default(), &mut buffer).unwrap().len();
+        buffer[0] = VERSION + 1;
+        let crc = CRC.checksum(&buffer[..len - CRC_SIZE]);
+        buffer[len - CRC_SIZE..len].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(decode(&buffer[..len]), Err(RecordError::Version(VERSION + 1)));
+    }
+}
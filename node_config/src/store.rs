@@ -0,0 +1,205 @@
+//! Loads and saves the [`Config`] record in a `sequential-storage` map, alongside the LoRaWAN
+//! session.
+//!
+//! `range` is the flash reserved for the map: whole erase sectors, at least two. Each save appends
+//! to the current sector and only erases once the sectors fill, cycling through them all, and a
+//! save that wouldn't change anything isn't written.
+
+use core::ops::Range;
+use embedded_storage_async::nor_flash::NorFlash;
+use sequential_storage::cache::Cache;
+use sequential_storage::map::{MapConfig, MapStorage};
+use crate::Config;
+use crate::record::{self, RecordError, MAX_RECORD_SIZE};
+
+const CONFIG_KEY: u8 = 0;
+const SESSION_KEY: u8 = 1;
+/// Room for an encoded LoRaWAN session
+pub const MAX_SESSION_SIZE: usize = 256;
+/// Every fetch reads each item it passes over, so this is the key and the larger of the two items,
+/// with room for sequential-storage to round up to the flash's word size
+pub const BUFFER_SIZE: usize = if MAX_SESSION_SIZE > MAX_RECORD_SIZE { MAX_SESSION_SIZE } else { MAX_RECORD_SIZE } + 16;
+
+#[derive(Debug)]
+pub enum StoreError<E> {
+    Storage(sequential_storage::Error<E>),
+    Record(RecordError),
+}
+
+impl<E> From<sequential_storage::Error<E>> for StoreError<E> {
+    fn from(e: sequential_storage::Error<E>) -> Self {
+        StoreError::Storage(e)
+    }
+}
+
+impl<E> From<RecordError> for StoreError<E> {
+    fn from(e: RecordError) -> Self {
+        StoreError::Record(e)
+    }
+}
+
+/// The stored config, or `None` if nothing has been saved yet
+///
+/// Panics if `range` isn't whole sectors, like a bad `memory.x`.
+pub async fn load<F: NorFlash>(flash: F, range: Range<u32>) -> Result<Option<Config>, StoreError<F::Error>> {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut map = MapStorage::<u8, _, _>::new(flash, MapConfig::new(range), Cache::new_uncached());
+    match map.fetch_item::<&[u8]>(&mut buffer, &CONFIG_KEY).await? {
+        Some(record) => Ok(Some(record::decode(record)?)),
+        None => Ok(None),
+    }
+}
+
+/// Stores `config`, returning whether flash was written
+pub async fn save<F: NorFlash>(flash: F, range: Range<u32>, config: &Config) -> Result<bool, StoreError<F::Error>> {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut map = MapStorage::<u8, _, _>::new(flash, MapConfig::new(range), Cache::new_uncached());
+    // an unreadable record is simply replaced
+    let unchanged = map.fetch_item::<&[u8]>(&mut buffer, &CONFIG_KEY).await?
+        .is_some_and(|stored| record::decode(stored) == Ok(*config));
+    if unchanged {
+        return Ok(false);
+    }
+
+    let mut record = [0; MAX_RECORD_SIZE];
+    let record = record::encode(config, &mut record)?;
+    map.store_item(&mut buffer, &CONFIG_KEY, &record).await?;
+    Ok(true)
+}
+
+/// The stored LoRaWAN session, read into `buffer`, or `None` if there isn't one. The session is
+/// opaque here; the firmware encodes it.
+pub async fn load_session<F: NorFlash>(
+    flash: F,
+    range: Range<u32>,
+    buffer: &mut [u8; BUFFER_SIZE],
+) -> Result<Option<&[u8]>, StoreError<F::Error>> {
+    let mut map = MapStorage::<u8, _, _>::new(flash, MapConfig::new(range), Cache::new_uncached());
+    Ok(map.fetch_item::<&[u8]>(buffer, &SESSION_KEY).await?)
+}
+
+/// Stores an encoded LoRaWAN session, at most [`MAX_SESSION_SIZE`] bytes
+pub async fn save_session<F: NorFlash>(flash: F, range: Range<u32>, session: &[u8]) -> Result<(), StoreError<F::Error>> {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut map = MapStorage::<u8, _, _>::new(flash, MapConfig::new(range), Cache::new_uncached());
+    Ok(map.store_item(&mut buffer, &SESSION_KEY, &session).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embassy_futures::block_on;
+    use embedded_storage_async::nor_flash::{ErrorType, NorFlashErrorKind, ReadNorFlash};
+
+    const SECTOR: usize = 1024;
+
+    /// NOR flash in RAM: writes can only clear bits, erases set whole sectors back to 0xff
+    struct RamFlash {
+        bytes: [u8; SECTOR * 4],
+        erases: usize,
+    }
+
+    impl RamFlash {
+        fn new() -> Self {
+            Self { bytes: [0xff; SECTOR * 4], erases: 0 }
+        }
+
+        fn range() -> Range<u32> {
+            0..(SECTOR * 4) as u32
+        }
+    }
+
+    impl ErrorType for RamFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for RamFlash {
+        const READ_SIZE: usize = 1;
+
+        async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let start = offset as usize;
+            bytes.copy_from_slice(&self.bytes[start..start + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.bytes.len()
+        }
+    }
+
+    impl NorFlash for RamFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = SECTOR;
+
+        async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            self.bytes[from as usize..to as usize].fill(0xff);
+            self.erases += 1;
+            Ok(())
+        }
+
+        async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            let start = offset as usize;
+            for (stored, byte) in self.bytes[start..start + bytes.len()].iter_mut().zip(bytes) {
+                *stored &= byte;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        block_on(async {
+            let mut flash = RamFlash::new();
+            assert_eq!(load(&mut flash, RamFlash::range()).await.unwrap(), None);
+
+            let config = Config { node_id: 7, ..Default::default() };
+            assert!(save(&mut flash, RamFlash::range(), &config).await.unwrap());
+            assert_eq!(load(&mut flash, RamFlash::range()).await.unwrap(), Some(config));
+        });
+    }
+
+    #[test]
+    fn skips_unchanged_saves() {
+        block_on(async {
+            let mut flash = RamFlash::new();
+            let config = Config::default();
+            assert!(save(&mut flash, RamFlash::range(), &config).await.unwrap());
+            let written = flash.bytes;
+            assert!(!save(&mut flash, RamFlash::range(), &config).await.unwrap());
+            assert_eq!(flash.bytes, written);
+        });
+    }
+
+    #[test]
+    fn spreads_saves_across_sectors() {
+        block_on(async {
+            let mut flash = RamFlash::new();
+            let mut config = Config::default();
+            for interval in 1..=40 {
+                config.intervals.uplink_secs = interval;
+                save(&mut flash, RamFlash::range(), &config).await.unwrap();
+            }
+            assert_eq!(load(&mut flash, RamFlash::range()).await.unwrap(), Some(config));
+            // each sector holds several records, so 40 saves erase far fewer than 40 times
+            assert!(flash.erases < 10, "{} erases", flash.erases);
+        });
+    }
+
+    #[test]
+    fn keeps_the_session_beside_the_config() {
+        block_on(async {
+            let mut flash = RamFlash::new();
+            let mut buffer = [0; BUFFER_SIZE];
+            assert_eq!(load_session(&mut flash, RamFlash::range(), &mut buffer).await.unwrap(), None);
+
+            let config = Config { node_id: 3, ..Default::default() };
+            save(&mut flash, RamFlash::range(), &config).await.unwrap();
+            for fcnt in 0..40u8 {
+                save_session(&mut flash, RamFlash::range(), &[fcnt; 32]).await.unwrap();
+            }
+            let session = load_session(&mut flash, RamFlash::range(), &mut buffer).await.unwrap();
+            assert_eq!(session, Some(&[39; 32][..]));
+            assert_eq!(load(&mut flash, RamFlash::range()).await.unwrap(), Some(config));
+        });
+    }
+}
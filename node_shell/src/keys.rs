@@ -0,0 +1,385 @@
+//! Config fields by name, for `get` and `set`.
+
+use core::fmt;
+use core::str::FromStr;
+use node_config::{AlertRule, AlertRules, Config};
+
+// the US ISM band the nodes and gateway are built for
+const FREQUENCY_HZ: (u32, u32) = (902_000_000, 928_000_000);
+// the RFM95 transmits through PA_BOOST
+const OUTPUT_POWER_DBM: (i8, i8) = (2, 20);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    NodeId,
+    TempHumidityIntervalSecs,
+    AirQualityIntervalSecs,
+    UplinkIntervalSecs,
+    FrequencyHz,
+    OutputPowerDbm,
+    DimAfterSecs,
+    OffAfterSecs,
+    AqiMax,
+    Pm2_5Max,
+    TemperatureMin,
+    TemperatureMax,
+    HumidityMax,
+    LowPower,
+    Alert(Rule, RuleField),
+    AlertOutput,
+    DevEui,
+    AppEui,
+    AppKey,
+}
+
+/// The reading an `alerts.` key's rule is for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rule {
+    Pm2_5,
+    Temperature,
+    Humidity,
+}
+
+impl Rule {
+    fn of(self, alerts: &AlertRules) -> &AlertRule {
+        match self {
+            Rule::Pm2_5 => &alerts.pm2_5,
+            Rule::Temperature => &alerts.temperature,
+            Rule::Humidity => &alerts.humidity,
+        }
+    }
+
+    fn of_mut(self, alerts: &mut AlertRules) -> &mut AlertRule {
+        match self {
+            Rule::Pm2_5 => &mut alerts.pm2_5,
+            Rule::Temperature => &mut alerts.temperature,
+            Rule::Humidity => &mut alerts.humidity,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RuleField {
+    Low,
+    High,
+    Hysteresis,
+    MinDurationSecs,
+    CooldownSecs,
+}
+
+// indexed by Rule, then RuleField
+const ALERT_NAMES: [[&str; 5]; 3] = [
+    [
+        "alerts.pm2_5.low",
+        "alerts.pm2_5.high",
+        "alerts.pm2_5.hysteresis",
+        "alerts.pm2_5.min_duration_secs",
+        "alerts.pm2_5.cooldown_secs",
+    ],
+    [
+        "alerts.temperature.low",
+        "alerts.temperature.high",
+        "alerts.temperature.hysteresis",
+        "alerts.temperature.min_duration_secs",
+        "alerts.temperature.cooldown_secs",
+    ],
+    [
+        "alerts.humidity.low",
+        "alerts.humidity.high",
+        "alerts.humidity.hysteresis",
+        "alerts.humidity.min_duration_secs",
+        "alerts.humidity.cooldown_secs",
+    ],
+];
+
+/// Why `set` refused a value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InvalidValue {
+    NotANumber,
+    NotOnOff,
+    /// Not this many hex digits
+    NotHex(usize),
+    /// Outside the inclusive range
+    OutOfRange(i64, i64),
+}
+
+impl fmt::Display for InvalidValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidValue::NotANumber => write!(f, "not a number"),
+            InvalidValue::NotOnOff => write!(f, "must be on or off"),
+            InvalidValue::NotHex(digits) => write!(f, "must be {} hex digits", digits),
+            InvalidValue::OutOfRange(min, max) => write!(f, "must be {}-{}", min, max),
+        }
+    }
+}
+
+/// A value that `off` clears
+struct Optional(Option<u16>);
+
+impl fmt::Display for Optional {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(value) => write!(f, "{}", value),
+            None => write!(f, "off"),
+        }
+    }
+}
+
+struct OnOff(bool);
+
+impl fmt::Display for OnOff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0 { "on" } else { "off" })
+    }
+}
+
+/// Bytes written most significant first, the way the network server shows them
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+fn number<T: Copy + FromStr + Into<i64>>(value: &str, (min, max): (T, T)) -> Result<T, InvalidValue> {
+    let value: T = value.parse().map_err(|_| InvalidValue::NotANumber)?;
+    let (min, max) = (min.into(), max.into());
+    if !(min..=max).contains(&value.into()) {
+        return Err(InvalidValue::OutOfRange(min, max));
+    }
+    Ok(value)
+}
+
+// 0 would run the job continuously
+fn interval(value: &str) -> Result<u16, InvalidValue> {
+    number(value, (1, u16::MAX))
+}
+
+fn optional(value: &str) -> Result<Option<u16>, InvalidValue> {
+    match value {
+        "off" => Ok(None),
+        _ => number(value, (0, u16::MAX)).map(Some),
+    }
+}
+
+fn on_off(value: &str) -> Result<bool, InvalidValue> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(InvalidValue::NotOnOff),
+    }
+}
+
+fn hex<const N: usize>(value: &str) -> Result<[u8; N], InvalidValue> {
+    // from_str_radix would take a sign
+    if value.len() != N * 2 || !value.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return Err(InvalidValue::NotHex(N * 2));
+    }
+    let mut bytes = [0; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[i * 2..i * 2 + 2], 16).map_err(|_| InvalidValue::NotHex(N * 2))?;
+    }
+    Ok(bytes)
+}
+
+impl Key {
+    pub const ALL: [Key; 33] = [
+        Key::NodeId,
+        Key::TempHumidityIntervalSecs,
+        Key::AirQualityIntervalSecs,
+        Key::UplinkIntervalSecs,
+        Key::FrequencyHz,
+        Key::OutputPowerDbm,
+        Key::DimAfterSecs,
+        Key::OffAfterSecs,
+        Key::AqiMax,
+        Key::Pm2_5Max,
+        Key::TemperatureMin,
+        Key::TemperatureMax,
+        Key::HumidityMax,
+        Key::LowPower,
+        Key::Alert(Rule::Pm2_5, RuleField::Low),
+        Key::Alert(Rule::Pm2_5, RuleField::High),
+        Key::Alert(Rule::Pm2_5, RuleField::Hysteresis),
+        Key::Alert(Rule::Pm2_5, RuleField::MinDurationSecs),
+        Key::Alert(Rule::Pm2_5, RuleField::CooldownSecs),
+        Key::Alert(Rule::Temperature, RuleField::Low),
+        Key::Alert(Rule::Temperature, RuleField::High),
+        Key::Alert(Rule::Temperature, RuleField::Hysteresis),
+        Key::Alert(Rule::Temperature, RuleField::MinDurationSecs),
+        Key::Alert(Rule::Temperature, RuleField::CooldownSecs),
+        Key::Alert(Rule::Humidity, RuleField::Low),
+        Key::Alert(Rule::Humidity, RuleField::High),
+        Key::Alert(Rule::Humidity, RuleField::Hysteresis),
+        Key::Alert(Rule::Humidity, RuleField::MinDurationSecs),
+        Key::Alert(Rule::Humidity, RuleField::CooldownSecs),
+        Key::AlertOutput,
+        Key::DevEui,
+        Key::AppEui,
+        Key::AppKey,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Key::NodeId => "node_id",
+            Key::TempHumidityIntervalSecs => "intervals.temp_humidity_secs",
+            Key::AirQualityIntervalSecs => "intervals.air_quality_secs",
+            Key::UplinkIntervalSecs => "intervals.uplink_secs",
+            Key::FrequencyHz => "radio.frequency_hz",
+            Key::OutputPowerDbm => "radio.output_power_dbm",
+            Key::DimAfterSecs => "display.dim_after_secs",
+            Key::OffAfterSecs => "display.off_after_secs",
+            Key::AqiMax => "thresholds.aqi_max",
+            Key::Pm2_5Max => "thresholds.pm2_5_max",
+            Key::TemperatureMin => "thresholds.temperature_min",
+            Key::TemperatureMax => "thresholds.temperature_max",
+            Key::HumidityMax => "thresholds.humidity_max",
+            Key::LowPower => "low_power",
+            Key::Alert(rule, field) => ALERT_NAMES[*rule as usize][*field as usize],
+            Key::AlertOutput => "alerts.output",
+            Key::DevEui => "lorawan.dev_eui",
+            Key::AppEui => "lorawan.app_eui",
+            Key::AppKey => "lorawan.app_key",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Key> {
+        Key::ALL.into_iter().find(|key| key.name() == name)
+    }
+
+    /// Writes the key's value in `config` the way `set` accepts it
+    pub fn write_value(&self, config: &Config, f: &mut impl fmt::Write) -> fmt::Result {
+        let (i, t) = (&config.intervals, &config.thresholds);
+        match self {
+            Key::NodeId => write!(f, "{}", config.node_id),
+            Key::TempHumidityIntervalSecs => write!(f, "{}", i.temp_humidity_secs),
+            Key::AirQualityIntervalSecs => write!(f, "{}", i.air_quality_secs),
+            Key::UplinkIntervalSecs => write!(f, "{}", i.uplink_secs),
+            Key::FrequencyHz => write!(f, "{}", config.radio.frequency_hz),
+            Key::OutputPowerDbm => write!(f, "{}", config.radio.output_power_dbm),
+            Key::DimAfterSecs => write!(f, "{}", Optional(config.display.dim_after_secs)),
+            Key::OffAfterSecs => write!(f, "{}", Optional(config.display.off_after_secs)),
+            Key::AqiMax => write!(f, "{}", Optional(t.aqi_max)),
+            Key::Pm2_5Max => write!(f, "{}", Optional(t.pm2_5_max)),
+            Key::TemperatureMin => write!(f, "{}", Optional(t.temperature_min)),
+            Key::TemperatureMax => write!(f, "{}", Optional(t.temperature_max)),
+            Key::HumidityMax => write!(f, "{}", Optional(t.humidity_max)),
+            Key::LowPower => write!(f, "{}", OnOff(config.low_power)),
+            Key::Alert(rule, field) => {
+                let rule = rule.of(&config.alerts);
+                match field {
+                    RuleField::Low => write!(f, "{}", Optional(rule.low)),
+                    RuleField::High => write!(f, "{}", Optional(rule.high)),
+                    RuleField::Hysteresis => write!(f, "{}", rule.hysteresis),
+                    RuleField::MinDurationSecs => write!(f, "{}", rule.min_duration_secs),
+                    RuleField::CooldownSecs => write!(f, "{}", rule.cooldown_secs),
+                }
+            }
+            Key::AlertOutput => write!(f, "{}", OnOff(config.alerts.output)),
+            Key::DevEui => write!(f, "{}", Hex(&config.lorawan.dev_eui)),
+            Key::AppEui => write!(f, "{}", Hex(&config.lorawan.app_eui)),
+            Key::AppKey => write!(f, "{}", Hex(&config.lorawan.app_key)),
+        }
+    }
+
+    /// Parses `value` into `config`, leaving it untouched if the value is refused
+    pub fn set(&self, config: &mut Config, value: &str) -> Result<(), InvalidValue> {
+        let (i, t) = (&mut config.intervals, &mut config.thresholds);
+        match self {
+            Key::NodeId => config.node_id = number(value, (0, u16::MAX))?,
+            Key::TempHumidityIntervalSecs => i.temp_humidity_secs = interval(value)?,
+            Key::AirQualityIntervalSecs => i.air_quality_secs = interval(value)?,
+            Key::UplinkIntervalSecs => i.uplink_secs = interval(value)?,
+            Key::FrequencyHz => config.radio.frequency_hz = number(value, FREQUENCY_HZ)?,
+            Key::OutputPowerDbm => config.radio.output_power_dbm = number(value, OUTPUT_POWER_DBM)?,
+            Key::DimAfterSecs => config.display.dim_after_secs = optional(value)?,
+            Key::OffAfterSecs => config.display.off_after_secs = optional(value)?,
+            Key::AqiMax => t.aqi_max = optional(value)?,
+            Key::Pm2_5Max => t.pm2_5_max = optional(value)?,
+            Key::TemperatureMin => t.temperature_min = optional(value)?,
+            Key::TemperatureMax => t.temperature_max = optional(value)?,
+            Key::HumidityMax => t.humidity_max = optional(value)?,
+            Key::LowPower => config.low_power = on_off(value)?,
+            Key::Alert(rule, field) => {
+                let rule = rule.of_mut(&mut config.alerts);
+                match field {
+                    RuleField::Low => rule.low = optional(value)?,
+                    RuleField::High => rule.high = optional(value)?,
+                    RuleField::Hysteresis => rule.hysteresis = number(value, (0, u16::MAX))?,
+                    RuleField::MinDurationSecs => rule.min_duration_secs = number(value, (0, u16::MAX))?,
+                    RuleField::CooldownSecs => rule.cooldown_secs = number(value, (0, u16::MAX))?,
+                }
+            }
+            Key::AlertOutput => config.alerts.output = on_off(value)?,
+            Key::DevEui => config.lorawan.dev_eui = hex(value)?,
+            Key::AppEui => config.lorawan.app_eui = hex(value)?,
+            Key::AppKey => config.lorawan.app_key = hex(value)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String;
+
+    fn value(key: Key, config: &Config) -> String {
+        let mut out = String::new();
+        key.write_value(config, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for key in Key::ALL {
+            assert_eq!(Key::from_name(key.name()), Some(key));
+        }
+        assert_eq!(Key::from_name("radio"), None);
+    }
+
+    #[test]
+    fn set_then_get() {
+        let mut config = Config::default();
+        for (key, text) in [
+            (Key::NodeId, "12"),
+            (Key::AirQualityIntervalSecs, "60"),
+            (Key::FrequencyHz, "903900000"),
+            (Key::OutputPowerDbm, "14"),
+            (Key::DimAfterSecs, "off"),
+            (Key::HumidityMax, "55"),
+            (Key::LowPower, "on"),
+            (Key::Alert(Rule::Pm2_5, RuleField::High), "150"),
+            (Key::Alert(Rule::Temperature, RuleField::Low), "off"),
+            (Key::Alert(Rule::Humidity, RuleField::CooldownSecs), "0"),
+            (Key::AlertOutput, "on"),
+            (Key::DevEui, "70b3d57ed0051a2b"),
+            (Key::AppKey, "2b7e151628aed2a6abf7158809cf4f3c"),
+        ] {
+            key.set(&mut config, text).unwrap();
+            assert_eq!(value(key, &config), text);
+        }
+        assert_eq!(config.node_id, 12);
+        assert_eq!(config.display.dim_after_secs, None);
+        assert!(config.low_power);
+        assert_eq!(config.alerts.pm2_5.high, Some(150));
+        assert_eq!(config.lorawan.dev_eui, [0x70, 0xb3, 0xd5, 0x7e, 0xd0, 0x05, 0x1a, 0x2b]);
+        assert_eq!(Key::from_name("alerts.humidity.cooldown_secs"), Some(Key::Alert(Rule::Humidity, RuleField::CooldownSecs)));
+    }
+
+    #[test]
+    fn refuses_bad_values() {
+        let mut config = Config::default();
+        assert_eq!(Key::NodeId.set(&mut config, "-1"), Err(InvalidValue::NotANumber));
+        assert_eq!(Key::UplinkIntervalSecs.set(&mut config, "0"), Err(InvalidValue::OutOfRange(1, 65535)));
+        assert_eq!(Key::FrequencyHz.set(&mut config, "868000000"), Err(InvalidValue::OutOfRange(902_000_000, 928_000_000)));
+        assert_eq!(Key::OutputPowerDbm.set(&mut config, "21"), Err(InvalidValue::OutOfRange(2, 20)));
+        assert_eq!(Key::AqiMax.set(&mut config, "high"), Err(InvalidValue::NotANumber));
+        assert_eq!(Key::LowPower.set(&mut config, "1"), Err(InvalidValue::NotOnOff));
+        assert_eq!(Key::AppEui.set(&mut config, "70b3d57e"), Err(InvalidValue::NotHex(16)));
+        assert_eq!(Key::AppEui.set(&mut config, "70b3d57ed0051a2g"), Err(InvalidValue::NotHex(16)));
+        assert_eq!(config, Config::default());
+    }
+}
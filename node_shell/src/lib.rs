@@ -0,0 +1,138 @@
+#![no_std]
+
+//! Line-based command shell served on the node's USB serial console.
+//!
+//! Only the parsing lives here so it can be tested on the host; the firmware feeds typed bytes
+//! through a [`LineBuffer`], hands each line to [`parse`] and carries out the [`Command`].
+
+#[cfg(test)]
+extern crate std;
+
+pub mod keys;
+pub mod line;
+
+use core::fmt;
+pub use keys::{InvalidValue, Key, Rule, RuleField};
+pub use line::{Edit, LineBuffer};
+
+pub const PROMPT: &str = "> ";
+/// Longest line the shell accepts; `set` with the longest key and value fits comfortably
+pub const MAX_LINE: usize = 64;
+
+pub const HELP: &str = "\
+get [key]          show one config value, or all of them\r
+set <key> <value>  change a config value; `off` clears a limit. Applied on reboot\r
+read               sample the sensors now\r
+status             sensor, radio, battery, I2C and job health, uptime and why the node last reset\r
+i2c scan           list the devices answering on the I2C bus\r
+radio test         check the radio responds and whether the channel is busy\r
+reboot             restart the node\r
+bootsel            restart into the USB bootloader to flash new firmware\r
+";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command<'a> {
+    Help,
+    /// `None` lists every key
+    Get(Option<Key>),
+    Set(Key, &'a str),
+    Read,
+    Status,
+    I2cScan,
+    RadioTest,
+    Reboot,
+    Bootsel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParseError<'a> {
+    /// A blank line, which only reprints the prompt
+    Empty,
+    UnknownCommand(&'a str),
+    UnknownKey(&'a str),
+    /// Missing or extra arguments; holds the expected form
+    Usage(&'static str),
+}
+
+impl fmt::Display for ParseError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => Ok(()),
+            ParseError::UnknownCommand(command) => write!(f, "unknown command `{}`, try `help`", command),
+            ParseError::UnknownKey(key) => write!(f, "unknown key `{}`, `get` lists them", key),
+            ParseError::Usage(usage) => write!(f, "usage: {}", usage),
+        }
+    }
+}
+
+// each command's expected form, reported when its arguments don't fit
+const USAGE: [(&str, &str); 9] = [
+    ("help", "help"),
+    ("get", "get [key]"),
+    ("set", "set <key> <value>"),
+    ("read", "read"),
+    ("status", "status"),
+    ("i2c", "i2c scan"),
+    ("radio", "radio test"),
+    ("reboot", "reboot"),
+    ("bootsel", "bootsel"),
+];
+
+fn key(name: &str) -> Result<Key, ParseError<'_>> {
+    Key::from_name(name).ok_or(ParseError::UnknownKey(name))
+}
+
+pub fn parse(line: &str) -> Result<Command<'_>, ParseError<'_>> {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+        return Err(ParseError::Empty);
+    };
+    // no command takes more than two, so a third is always an error
+    match (command, (words.next(), words.next(), words.next())) {
+        ("help", (None, ..)) => Ok(Command::Help),
+        ("get", (None, ..)) => Ok(Command::Get(None)),
+        ("get", (Some(name), None, _)) => Ok(Command::Get(Some(key(name)?))),
+        ("set", (Some(name), Some(value), None)) => Ok(Command::Set(key(name)?, value)),
+        ("read", (None, ..)) => Ok(Command::Read),
+        ("status", (None, ..)) => Ok(Command::Status),
+        ("i2c", (Some("scan"), None, _)) => Ok(Command::I2cScan),
+        ("radio", (Some("test"), None, _)) => Ok(Command::RadioTest),
+        ("reboot", (None, ..)) => Ok(Command::Reboot),
+        ("bootsel", (None, ..)) => Ok(Command::Bootsel),
+        _ => match USAGE.iter().find(|(name, _)| *name == command) {
+            Some((_, usage)) => Err(ParseError::Usage(usage)),
+            None => Err(ParseError::UnknownCommand(command)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse("help"), Ok(Command::Help));
+        assert_eq!(parse("get"), Ok(Command::Get(None)));
+        assert_eq!(parse("get node_id"), Ok(Command::Get(Some(Key::NodeId))));
+        assert_eq!(parse("  set  thresholds.aqi_max   off "), Ok(Command::Set(Key::AqiMax, "off")));
+        assert_eq!(parse("read"), Ok(Command::Read));
+        assert_eq!(parse("status"), Ok(Command::Status));
+        assert_eq!(parse("i2c scan"), Ok(Command::I2cScan));
+        assert_eq!(parse("radio test"), Ok(Command::RadioTest));
+        assert_eq!(parse("reboot"), Ok(Command::Reboot));
+        assert_eq!(parse("bootsel"), Ok(Command::Bootsel));
+    }
+
+    #[test]
+    fn reports_mistakes() {
+        assert_eq!(parse(""), Err(ParseError::Empty));
+        assert_eq!(parse("   "), Err(ParseError::Empty));
+        assert_eq!(parse("format"), Err(ParseError::UnknownCommand("format")));
+        assert_eq!(parse("get node"), Err(ParseError::UnknownKey("node")));
+        assert_eq!(parse("set node_id"), Err(ParseError::Usage("set <key> <value>")));
+        assert_eq!(parse("set node_id 1 2"), Err(ParseError::Usage("set <key> <value>")));
+        assert_eq!(parse("i2c"), Err(ParseError::Usage("i2c scan")));
+        assert_eq!(parse("reboot now"), Err(ParseError::Usage("reboot")));
+    }
+}
@@ -0,0 +1,103 @@
+//! Collects typed bytes into lines, with the echo and backspace a serial terminal expects.
+
+use heapless::String;
+
+/// What the terminal should be sent back for a byte
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edit {
+    /// Nothing to show: a control character, or typing past a full line
+    Ignored,
+    Echo(u8),
+    /// Rub out the last character, e.g. with `\x08 \x08`
+    Erase,
+    /// Enter was pressed; the line is ready in `LineBuffer::line`
+    Submit,
+}
+
+#[derive(Debug, Default)]
+pub struct LineBuffer<const N: usize> {
+    line: String<N>,
+    overflowed: bool,
+    // terminals send CR LF, CR or LF for enter; the LF of a CR LF isn't a second enter
+    after_cr: bool,
+}
+
+impl<const N: usize> LineBuffer<N> {
+    pub fn new() -> Self {
+        Self { line: String::new(), overflowed: false, after_cr: false }
+    }
+
+    pub fn push(&mut self, byte: u8) -> Edit {
+        let after_cr = self.after_cr;
+        self.after_cr = byte == b'\r';
+        match byte {
+            b'\r' => Edit::Submit,
+            b'\n' if after_cr => Edit::Ignored,
+            b'\n' => Edit::Submit,
+            // backspace or delete, depending on the terminal
+            0x08 | 0x7f => {
+                if self.line.pop().is_some() { Edit::Erase } else { Edit::Ignored }
+            }
+            b' '..=b'~' => {
+                if self.line.push(byte as char).is_ok() {
+                    Edit::Echo(byte)
+                } else {
+                    self.overflowed = true;
+                    Edit::Ignored
+                }
+            }
+            _ => Edit::Ignored,
+        }
+    }
+
+    /// The submitted line, or `None` if it was longer than `N` and got cut short
+    pub fn line(&self) -> Option<&str> {
+        if self.overflowed { None } else { Some(self.line.trim()) }
+    }
+
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.overflowed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn typed<const N: usize>(buffer: &mut LineBuffer<N>, bytes: &[u8]) -> Vec<Edit> {
+        bytes.iter().map(|&b| buffer.push(b)).collect()
+    }
+
+    #[test]
+    fn edits_a_line() {
+        let mut buffer = LineBuffer::<16>::new();
+        let edits = typed(&mut buffer, b"gey\x7ft status\r\n");
+        assert_eq!(edits[..4], [Edit::Echo(b'g'), Edit::Echo(b'e'), Edit::Echo(b'y'), Edit::Erase]);
+        assert_eq!(edits[edits.len() - 2..], [Edit::Submit, Edit::Ignored]);
+        assert_eq!(buffer.line(), Some("get status"));
+    }
+
+    #[test]
+    fn lone_line_feeds_submit() {
+        let mut buffer = LineBuffer::<16>::new();
+        assert_eq!(typed(&mut buffer, b"read\n\n"), [
+            Edit::Echo(b'r'), Edit::Echo(b'e'), Edit::Echo(b'a'), Edit::Echo(b'd'), Edit::Submit, Edit::Submit,
+        ]);
+        // backspace on an empty line has nothing to erase
+        buffer.clear();
+        assert_eq!(buffer.push(0x08), Edit::Ignored);
+    }
+
+    #[test]
+    fn flags_overlong_lines() {
+        let mut buffer = LineBuffer::<4>::new();
+        let edits = typed(&mut buffer, b"reboot\r");
+        assert_eq!(edits[4..], [Edit::Ignored, Edit::Ignored, Edit::Submit]);
+        assert_eq!(buffer.line(), None);
+        buffer.clear();
+        typed(&mut buffer, b"help\r");
+        assert_eq!(buffer.line(), Some("help"));
+    }
+}
@@ -0,0 +1,193 @@
+#![no_std]
+
+//! Dew point, heat index and absolute humidity, derived from a temperature and relative humidity.
+//!
+//! Computed in `f32`. `core` has no `ln` or `exp`, so they're series here, accurate to about 1e-6
+//! and well inside the formulas' own error:
+//! * dew point: the Magnus formula with Sonntag's constants, within 0.35°C (0.6°F) from -45°C to
+//!   60°C
+//! * heat index: the NWS algorithm, Rothfusz's regression with its low and high humidity
+//!   adjustments, within 1.3°F. Below about 80°F it falls back to Steadman's simpler formula and
+//!   stays close to the air temperature.
+//! * absolute humidity: from the Magnus saturation vapor pressure and the ideal gas law, within
+//!   1% from -30°C to 35°C
+//!
+//! Kept apart from the `sht30` driver so the display and host tools can derive them from any reading.
+//!
+//! The SHT30 reading is whole degrees F and whole %RH, which costs more than any of these: a
+//! degree's rounding moves the dew point by up to 0.5°F.
+
+use core::f32::consts::{LN_2, SQRT_2};
+
+// Magnus coefficients over water, in °C
+const MAGNUS_A: f32 = 17.62;
+const MAGNUS_B: f32 = 243.12;
+/// Saturation vapor pressure at 0°C, in hPa
+const MAGNUS_E0: f32 = 6.112;
+/// g·K/(m³·hPa): 100 Pa/hPa × 1000 g/kg over water vapor's gas constant, 461.5 J/(kg·K)
+const VAPOR_DENSITY: f32 = 216.7;
+const KELVIN: f32 = 273.15;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Psychrometrics {
+    /// Below this, surfaces gather condensation
+    pub dew_point_f: i16,
+    /// How hot it feels, for worker safety
+    pub heat_index_f: i16,
+    /// Tenths of a g/m³
+    pub absolute_humidity_dg_m3: u16,
+}
+
+impl Psychrometrics {
+    pub fn new(temperature_f: u16, humidity: u16) -> Self {
+        let temperature_f = f32::from(temperature_f);
+        // ln(0) is undefined, and the SHT30 rounds down, so treat bone dry as 1%
+        let humidity = f32::from(humidity.clamp(1, 100));
+        Self {
+            dew_point_f: round(dew_point_f(temperature_f, humidity)) as i16,
+            heat_index_f: round(heat_index_f(temperature_f, humidity)) as i16,
+            absolute_humidity_dg_m3: round(absolute_humidity(temperature_f, humidity) * 10.0) as u16,
+        }
+    }
+}
+
+fn round(x: f32) -> i32 {
+    // the cast truncates toward zero
+    if x < 0.0 { (x - 0.5) as i32 } else { (x + 0.5) as i32 }
+}
+
+/// For `x > 0`: `x = m × 2^e` with `m` in [√½, √2), and ln(m) = 2 atanh((m - 1) / (m + 1)), whose
+/// series converges quickly with its argument under 0.18
+fn ln(x: f32) -> f32 {
+    let bits = x.to_bits();
+    let mut exponent = ((bits >> 23) & 0xff) as i32 - 127;
+    let mut m = f32::from_bits((bits & 0x007f_ffff) | 0x3f80_0000);
+    if m > SQRT_2 {
+        m *= 0.5;
+        exponent += 1;
+    }
+    let s = (m - 1.0) / (m + 1.0);
+    let s2 = s * s;
+    let atanh = s * (1.0 + s2 * (1.0 / 3.0 + s2 * (1.0 / 5.0 + s2 * (1.0 / 7.0 + s2 * (1.0 / 9.0)))));
+    exponent as f32 * LN_2 + 2.0 * atanh
+}
+
+/// `x = n ln2 + r` with |r| at most ln2 / 2, and e^r's Taylor series; for the small `x` here
+fn exp(x: f32) -> f32 {
+    let n = round(x / LN_2);
+    let r = x - n as f32 * LN_2;
+    let e_r = 1.0 + r * (1.0 + r * (1.0 / 2.0 + r * (1.0 / 6.0 + r * (1.0 / 24.0 + r * (1.0 / 120.0 + r / 720.0)))));
+    e_r * f32::from_bits(((n + 127) as u32) << 23)
+}
+
+/// Newton's method, for `x` in [0, 1]
+fn sqrt(x: f32) -> f32 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let mut y = 1.0;
+    for _ in 0..8 {
+        y = 0.5 * (y + x / y);
+    }
+    y
+}
+
+fn celsius(temperature_f: f32) -> f32 {
+    (temperature_f - 32.0) * 5.0 / 9.0
+}
+
+fn fahrenheit(temperature_c: f32) -> f32 {
+    temperature_c * 9.0 / 5.0 + 32.0
+}
+
+/// `humidity` in %RH
+pub fn dew_point_f(temperature_f: f32, humidity: f32) -> f32 {
+    let t = celsius(temperature_f);
+    let gamma = ln(humidity / 100.0) + MAGNUS_A * t / (MAGNUS_B + t);
+    fahrenheit(MAGNUS_B * gamma / (MAGNUS_A - gamma))
+}
+
+/// The NWS's, from <https://www.wpc.ncep.noaa.gov/html/heatindex_equation.shtml>
+pub fn heat_index_f(temperature_f: f32, humidity: f32) -> f32 {
+    let (t, rh) = (temperature_f, humidity);
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    if (simple + t) / 2.0 < 80.0 {
+        return simple;
+    }
+    let index = -42.379 + 2.049_015_2 * t + 10.143_331 * rh
+        - 0.224_755_4 * t * rh
+        - 0.006_837_83 * t * t
+        - 0.054_817_17 * rh * rh
+        + 0.001_228_74 * t * t * rh
+        + 0.000_852_82 * t * rh * rh
+        - 0.000_001_99 * t * t * rh * rh;
+    if rh < 13.0 && (80.0..=112.0).contains(&t) {
+        index - (13.0 - rh) / 4.0 * sqrt((17.0 - (t - 95.0).abs()) / 17.0)
+    } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+        index + (rh - 85.0) / 10.0 * ((87.0 - t) / 5.0)
+    } else {
+        index
+    }
+}
+
+/// In g/m³; `humidity` in %RH
+pub fn absolute_humidity(temperature_f: f32, humidity: f32) -> f32 {
+    let t = celsius(temperature_f);
+    let saturation_hpa = MAGNUS_E0 * exp(MAGNUS_A * t / (MAGNUS_B + t));
+    VAPOR_DENSITY * humidity / 100.0 * saturation_hpa / (t + KELVIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn series_match_std() {
+        for i in 1..=200 {
+            let x = i as f32 / 100.0;
+            assert!((ln(x) - x.ln()).abs() < 1e-6, "ln({})", x);
+            let y = x * 4.0 - 4.0;
+            assert!((exp(y) / y.exp() - 1.0).abs() < 1e-6, "exp({})", y);
+            let z = x / 2.0;
+            assert!((sqrt(z) - z.sqrt()).abs() < 1e-6, "sqrt({})", z);
+        }
+        assert_eq!((round(2.5), round(-2.5), round(-0.4)), (3, -3, 0));
+    }
+
+    // reference values from the same formulas in f64
+    #[test]
+    fn matches_reference_values() {
+        assert_eq!(
+            Psychrometrics::new(68, 50),
+            Psychrometrics { dew_point_f: 49, heat_index_f: 67, absolute_humidity_dg_m3: 86 }
+        );
+        assert_eq!(
+            Psychrometrics::new(90, 70),
+            Psychrometrics { dew_point_f: 79, heat_index_f: 106, absolute_humidity_dg_m3: 239 }
+        );
+        assert_eq!(
+            Psychrometrics::new(32, 80),
+            Psychrometrics { dew_point_f: 27, heat_index_f: 29, absolute_humidity_dg_m3: 39 }
+        );
+    }
+
+    #[test]
+    fn applies_the_heat_index_adjustments() {
+        // hot and dry, then warm and muggy
+        assert_eq!(Psychrometrics::new(95, 10).heat_index_f, 89);
+        assert_eq!(Psychrometrics::new(85, 90).heat_index_f, 102);
+    }
+
+    #[test]
+    fn saturated_air_is_at_its_dew_point() {
+        assert_eq!(Psychrometrics::new(50, 100).dew_point_f, 50);
+        assert_eq!(Psychrometrics::new(104, 100).dew_point_f, 104);
+    }
+
+    #[test]
+    fn survives_bone_dry_air() {
+        let dry = Psychrometrics::new(0, 0);
+        assert_eq!(dry.dew_point_f, -79);
+        assert_eq!(dry.absolute_humidity_dg_m3, 0);
+    }
+}
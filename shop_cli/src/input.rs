@@ -0,0 +1,134 @@
+//! Parsing of the line-oriented packet streams the host tools consume.
+//!
+//! Two formats are accepted, one packet per line:
+//! * the gateway's JSON lines, where the packet bytes are in the `raw` field
+//! * plain hex dumps, optionally separated by spaces or colons
+
+use std::io::{self, BufRead};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub raw: Vec<u8>,
+    pub rssi: Option<i16>,
+    pub snr: Option<i16>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Frame(Frame),
+    /// The gateway heard a packet that failed the PHY CRC
+    CrcError,
+}
+
+#[derive(Deserialize)]
+struct GatewayLine {
+    raw: Option<String>,
+    rssi: Option<i16>,
+    snr: Option<i16>,
+    error: Option<String>,
+}
+
+pub fn parse_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits: String = hex.trim()
+        .trim_start_matches("0x")
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect();
+    if !digits.is_ascii() {
+        bail!("non-hex characters");
+    }
+    if !digits.len().is_multiple_of(2) {
+        bail!("odd number of hex digits");
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).with_context(|| format!("invalid hex byte {:?}", &digits[i..i + 2])))
+        .collect()
+}
+
+/// Parse one line of input; blank lines and `#` comments yield `None`
+pub fn parse_line(line: &str) -> Result<Option<Event>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    if !line.starts_with('{') {
+        let raw = parse_hex(line)?;
+        return Ok(Some(Event::Frame(Frame { raw, rssi: None, snr: None })));
+    }
+
+    let gateway: GatewayLine = serde_json::from_str(line)?;
+    match (gateway.error.as_deref(), gateway.raw) {
+        (Some("crc"), _) => Ok(Some(Event::CrcError)),
+        (_, Some(raw)) => Ok(Some(Event::Frame(Frame { raw: parse_hex(&raw)?, rssi: gateway.rssi, snr: gateway.snr }))),
+        (Some(error), None) => Err(anyhow!("gateway error: {}", error)),
+        (None, None) => Err(anyhow!("line has no raw packet")),
+    }
+}
+
+/// Read lines from a source that may time out between lines, like a serial port.
+///
+/// Timeouts are retried without losing a partially read line; `f` is called once per complete line.
+pub fn for_each_line(mut reader: impl BufRead, mut f: impl FnMut(&str) -> Result<()>) -> Result<()> {
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => break,
+            Ok(_) if buf.ends_with(b"\n") => {
+                f(&String::from_utf8_lossy(&buf))?;
+                buf.clear();
+            }
+            // EOF mid-line; handled after the loop
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::TimedOut || e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if !buf.is_empty() {
+        f(&String::from_utf8_lossy(&buf))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hex_dump() {
+        assert_eq!(parse_hex("01 02:0a ff").unwrap(), vec![0x01, 0x02, 0x0a, 0xff]);
+        assert_eq!(parse_hex("0x0102").unwrap(), vec![0x01, 0x02]);
+        assert!(parse_hex("012").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+
+    #[test]
+    fn parse_gateway_line() {
+        let line = r#"{"node":1,"seq":2,"kind":"env","rssi":-87,"snr":9,"raw":"0102"}"#;
+        let event = parse_line(line).unwrap().unwrap();
+        assert_eq!(event, Event::Frame(Frame { raw: vec![1, 2], rssi: Some(-87), snr: Some(9) }));
+    }
+
+    #[test]
+    fn parse_gateway_crc_error() {
+        assert_eq!(parse_line(r#"{"error":"crc"}"#).unwrap(), Some(Event::CrcError));
+    }
+
+    #[test]
+    fn parse_skips_blank_and_comments() {
+        assert_eq!(parse_line("").unwrap(), None);
+        assert_eq!(parse_line("  # captured at the shop").unwrap(), None);
+    }
+
+    #[test]
+    fn for_each_line_handles_missing_trailing_newline() {
+        let mut lines = Vec::new();
+        for_each_line("a\nb".as_bytes(), |line| {
+            lines.push(line.trim().to_string());
+            Ok(())
+        }).unwrap();
+        assert_eq!(lines, vec!["a", "b"]);
+    }
+}
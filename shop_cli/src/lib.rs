@@ -0,0 +1,6 @@
+//! Host-side decoding of smart-shop packets, shared by the CLI and the bridge daemons.
+
+pub mod input;
+pub mod record;
+pub mod sink;
+pub mod summary;
@@ -0,0 +1,159 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use shop_cli::input::{for_each_line, parse_line, Event};
+use shop_cli::record::{now_millis, Decoded, Record};
+use shop_cli::sink::{CsvSink, JsonLinesSink, Sink, SqliteSink};
+use shop_cli::summary::Summary;
+
+/// Decode and log smart-shop sensor packets
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Decode packets live from the gateway's serial port
+    Tail {
+        /// e.g. /dev/ttyACM1, the gateway's second serial port
+        port: String,
+        #[arg(long, default_value_t = 115_200)]
+        baud: u32,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Decode a captured file of gateway JSON lines or hex dumps
+    Replay {
+        file: PathBuf,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Per-node packet counts, losses and min/mean/max of each metric
+    Summary {
+        file: PathBuf,
+    },
+}
+
+#[derive(clap::Args)]
+struct Output {
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+    /// Output file; required for sqlite, stdout otherwise
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Jsonl,
+    Sqlite,
+}
+
+impl Output {
+    fn sink(&self) -> Result<Box<dyn Sink>> {
+        let writer: Box<dyn io::Write> = match (&self.format, &self.output) {
+            (Format::Sqlite, Some(path)) => return Ok(Box::new(SqliteSink::open(path)?)),
+            (Format::Sqlite, None) => bail!("--output is required for sqlite"),
+            (_, Some(path)) => Box::new(File::create(path)?),
+            (_, None) => Box::new(io::stdout().lock()),
+        };
+        Ok(match self.format {
+            Format::Jsonl => Box::new(JsonLinesSink::new(writer)),
+            _ => Box::new(CsvSink::new(writer)),
+        })
+    }
+}
+
+fn decode_line(line: &str, received_at: Option<u64>, summary: &mut Summary) -> Option<Record> {
+    match parse_line(line) {
+        Ok(Some(Event::Frame(frame))) => match Decoded::decode(&frame, received_at) {
+            Ok(decoded) => {
+                summary.record(&decoded);
+                match decoded {
+                    Decoded::Reading(record) => Some(record),
+                    Decoded::Alert(alert) => {
+                        eprintln!("node {} alert: {} {} {}, limit {}", alert.node, alert.metric, alert.state, alert.value, alert.limit);
+                        None
+                    }
+                    Decoded::BusFaults(faults) => {
+                        eprintln!(
+                            "node {} bus faults: temp/humidity {}/{}, air quality {}/{}, display {}/{} timeouts/errors, {} recoveries",
+                            faults.node,
+                            faults.temp_humidity_timeouts, faults.temp_humidity_errors,
+                            faults.air_quality_timeouts, faults.air_quality_errors,
+                            faults.display_timeouts, faults.display_errors,
+                            faults.recoveries,
+                        );
+                        None
+                    }
+                }
+            }
+            Err(e) => {
+                summary.decode_errors += 1;
+                eprintln!("{}: {}", e, line.trim());
+                None
+            }
+        },
+        Ok(Some(Event::CrcError)) => {
+            summary.crc_errors += 1;
+            None
+        }
+        Ok(None) => None,
+        Err(e) => {
+            summary.decode_errors += 1;
+            eprintln!("{}: {}", e, line.trim());
+            None
+        }
+    }
+}
+
+fn tail(port: &str, baud: u32, output: &Output) -> Result<()> {
+    // a long timeout; for_each_line retries on expiry so this only bounds each blocking read
+    let port = serialport::new(port, baud).timeout(Duration::from_secs(60)).open()?;
+    let mut sink = output.sink()?;
+    let mut summary = Summary::default();
+    for_each_line(BufReader::new(port), |line| {
+        if let Some(record) = decode_line(line, Some(now_millis()), &mut summary) {
+            sink.write(&record)?;
+            sink.flush()?;
+        }
+        Ok(())
+    })
+}
+
+fn replay(file: &Path, output: &Output) -> Result<()> {
+    let mut sink = output.sink()?;
+    let mut summary = Summary::default();
+    for_each_line(BufReader::new(File::open(file)?), |line| {
+        if let Some(record) = decode_line(line, None, &mut summary) {
+            sink.write(&record)?;
+        }
+        Ok(())
+    })?;
+    sink.flush()
+}
+
+fn summary(file: &Path) -> Result<()> {
+    let mut summary = Summary::default();
+    for_each_line(BufReader::new(File::open(file)?), |line| {
+        decode_line(line, None, &mut summary);
+        Ok(())
+    })?;
+    println!("{}", summary);
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Tail { port, baud, output } => tail(&port, baud, &output),
+        Command::Replay { file, output } => replay(&file, &output),
+        Command::Summary { file } => summary(&file),
+    }
+}
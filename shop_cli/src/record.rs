@@ -0,0 +1,220 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use telemetry::{Body, Packet};
+use crate::input::Frame;
+
+/// One decoded reading, flattened for CSV/JSON/SQL output
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Record {
+    /// Unix time in milliseconds, when known
+    pub received_at: Option<u64>,
+    pub node: u16,
+    pub seq: u16,
+    pub temperature_f: u16,
+    pub humidity: u16,
+    pub pm1_0: u16,
+    pub pm2_5: u16,
+    pub pm10: u16,
+    /// Only from nodes that measure their battery
+    pub battery_mv: Option<u16>,
+    pub battery_percent: Option<u8>,
+    pub rssi: Option<i16>,
+    pub snr: Option<i16>,
+}
+
+/// An alert the node raised or cleared
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AlertRecord {
+    pub received_at: Option<u64>,
+    pub node: u16,
+    pub seq: u16,
+    /// Named as the reading's field, e.g. `pm2_5`
+    pub metric: &'static str,
+    /// `low`, `high` or `cleared`
+    pub state: &'static str,
+    /// The metric's value in the reading that raised or cleared it
+    pub value: u16,
+    pub limit: u16,
+    pub rssi: Option<i16>,
+    pub snr: Option<i16>,
+}
+
+/// The node's I2C fault counts since boot, sent when they change
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BusFaultsRecord {
+    pub received_at: Option<u64>,
+    pub node: u16,
+    pub seq: u16,
+    pub temp_humidity_timeouts: u16,
+    pub temp_humidity_errors: u16,
+    pub air_quality_timeouts: u16,
+    pub air_quality_errors: u16,
+    pub display_timeouts: u16,
+    pub display_errors: u16,
+    pub recoveries: u16,
+    pub rssi: Option<i16>,
+    pub snr: Option<i16>,
+}
+
+/// A decoded packet; only readings go to the sinks, the other kinds carry the node's latest reading
+/// just for context
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decoded {
+    Reading(Record),
+    Alert(AlertRecord),
+    BusFaults(BusFaultsRecord),
+}
+
+impl Decoded {
+    pub fn decode(frame: &Frame, received_at: Option<u64>) -> Result<Self> {
+        let packet = Packet::decode(&frame.raw).map_err(|e| anyhow!("undecodable packet: {:?}", e))?;
+        let (node, seq, rssi, snr) = (packet.node_id, packet.seq, frame.rssi, frame.snr);
+        Ok(match &packet.body {
+            Body::Alert(reading, alert) => Decoded::Alert(AlertRecord {
+                received_at,
+                node,
+                seq,
+                metric: alert.metric.name(),
+                state: alert.state.name(),
+                value: alert.metric.value(reading),
+                limit: alert.limit,
+                rssi,
+                snr,
+            }),
+            Body::BusFaults(_, faults) => Decoded::BusFaults(BusFaultsRecord {
+                received_at,
+                node,
+                seq,
+                temp_humidity_timeouts: faults.temp_humidity_timeouts,
+                temp_humidity_errors: faults.temp_humidity_errors,
+                air_quality_timeouts: faults.air_quality_timeouts,
+                air_quality_errors: faults.air_quality_errors,
+                display_timeouts: faults.display_timeouts,
+                display_errors: faults.display_errors,
+                recoveries: faults.recoveries,
+                rssi,
+                snr,
+            }),
+            // a legacy packet comes from LEGACY_NODE_ID, with a PM1.0 of 0
+            Body::EnvReading(_) | Body::EnvReadingBattery(..) | Body::EnvReadingDerived(..) | Body::LegacyEnvReading(_) => {
+                let (reading, battery) = (packet.body.reading(), packet.body.battery());
+                Decoded::Reading(Record {
+                    received_at,
+                    node,
+                    seq,
+                    temperature_f: reading.temperature,
+                    humidity: reading.humidity,
+                    pm1_0: reading.aq_pm1_0,
+                    pm2_5: reading.aq_pm2_5,
+                    pm10: reading.aq_pm10,
+                    battery_mv: battery.map(|b| b.millivolts),
+                    battery_percent: battery.map(|b| b.percent),
+                    rssi,
+                    snr,
+                })
+            }
+        })
+    }
+
+    pub fn node(&self) -> u16 {
+        match self {
+            Decoded::Reading(record) => record.node,
+            Decoded::Alert(alert) => alert.node,
+            Decoded::BusFaults(faults) => faults.node,
+        }
+    }
+
+    pub fn seq(&self) -> u16 {
+        match self {
+            Decoded::Reading(record) => record.seq,
+            Decoded::Alert(alert) => alert.seq,
+            Decoded::BusFaults(faults) => faults.seq,
+        }
+    }
+
+    /// The signal quality the gateway measured, as `(rssi, snr)`
+    pub fn signal(&self) -> (Option<i16>, Option<i16>) {
+        match self {
+            Decoded::Reading(record) => (record.rssi, record.snr),
+            Decoded::Alert(alert) => (alert.rssi, alert.snr),
+            Decoded::BusFaults(faults) => (faults.rssi, faults.snr),
+        }
+    }
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use telemetry::{Alert, AlertMetric, AlertState, Battery, BusFaults, EnvReading};
+
+    #[test]
+    fn decode_env_reading() {
+        let reading = EnvReading { aq_pm2_5: 12, aq_pm10: 15, humidity: 48, temperature: 68, aq_pm1_0: 8 };
+        let raw = Packet::new(3, 9, Body::EnvReading(reading)).encode().unwrap().to_vec();
+        let frame = Frame { raw, rssi: Some(-90), snr: Some(-2) };
+
+        let decoded = Decoded::decode(&frame, Some(1_000)).unwrap();
+        assert_eq!(decoded, Decoded::Reading(Record {
+            received_at: Some(1_000),
+            node: 3,
+            seq: 9,
+            temperature_f: 68,
+            humidity: 48,
+            pm1_0: 8,
+            pm2_5: 12,
+            pm10: 15,
+            battery_mv: None,
+            battery_percent: None,
+            rssi: Some(-90),
+            snr: Some(-2),
+        }));
+    }
+
+    #[test]
+    fn decode_battery() {
+        let reading = EnvReading { aq_pm2_5: 12, aq_pm10: 15, humidity: 48, temperature: 68, aq_pm1_0: 8 };
+        let battery = Battery { millivolts: 3_850, percent: 52 };
+        let raw = Packet::new(3, 9, Body::EnvReadingBattery(reading, battery)).encode().unwrap().to_vec();
+        let Decoded::Reading(record) = Decoded::decode(&Frame { raw, rssi: None, snr: None }, None).unwrap() else {
+            panic!("not a reading");
+        };
+        assert_eq!((record.battery_mv, record.battery_percent), (Some(3_850), Some(52)));
+        assert_eq!(record.pm2_5, 12);
+    }
+
+    #[test]
+    fn decode_alert_and_bus_faults() {
+        let reading = EnvReading { aq_pm2_5: 40, aq_pm10: 45, humidity: 48, temperature: 68, aq_pm1_0: 30 };
+        let alert = Alert { metric: AlertMetric::Pm2_5, state: AlertState::High, limit: 35 };
+        let raw = Packet::new(3, 10, Body::Alert(reading.clone(), alert)).encode().unwrap().to_vec();
+        assert_eq!(Decoded::decode(&Frame { raw, rssi: None, snr: None }, None).unwrap(), Decoded::Alert(AlertRecord {
+            received_at: None,
+            node: 3,
+            seq: 10,
+            metric: "pm2_5",
+            state: "high",
+            value: 40,
+            limit: 35,
+            rssi: None,
+            snr: None,
+        }));
+
+        let faults = BusFaults { air_quality_timeouts: 3, recoveries: 1, ..Default::default() };
+        let raw = Packet::new(3, 11, Body::BusFaults(reading, faults)).encode().unwrap().to_vec();
+        let Decoded::BusFaults(record) = Decoded::decode(&Frame { raw, rssi: None, snr: None }, None).unwrap() else {
+            panic!("not bus faults");
+        };
+        assert_eq!((record.seq, record.air_quality_timeouts, record.recoveries), (11, 3, 1));
+    }
+
+    #[test]
+    fn decode_garbage() {
+        let frame = Frame { raw: vec![0xee; 13], rssi: None, snr: None };
+        assert!(Decoded::decode(&frame, None).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::io::Write;
+use std::path::Path;
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use crate::record::Record;
+
+pub trait Sink {
+    fn write(&mut self, record: &Record) -> Result<()>;
+    fn flush(&mut self) -> Result<()>;
+}
+
+pub struct CsvSink<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer: csv::Writer::from_writer(writer) }
+    }
+}
+
+impl<W: Write> Sink for CsvSink<W> {
+    fn write(&mut self, record: &Record) -> Result<()> {
+        Ok(self.writer.serialize(record)?)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+pub struct JsonLinesSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> Sink for JsonLinesSink<W> {
+    fn write(&mut self, record: &Record) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        Ok(self.writer.write_all(b"\n")?)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+pub struct SqliteSink {
+    connection: Connection,
+}
+
+impl SqliteSink {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::new(Connection::open(path)?)
+    }
+
+    pub fn new(connection: Connection) -> Result<Self> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS readings (
+                id INTEGER PRIMARY KEY,
+                received_at INTEGER,
+                node INTEGER NOT NULL,
+                seq INTEGER NOT NULL,
+                temperature_f INTEGER NOT NULL,
+                humidity INTEGER NOT NULL,
+                pm1_0 INTEGER NOT NULL,
+                pm2_5 INTEGER NOT NULL,
+                pm10 INTEGER NOT NULL,
+                battery_mv INTEGER,
+                battery_percent INTEGER,
+                rssi INTEGER,
+                snr INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS readings_node ON readings (node, received_at);"
+        )?;
+        // databases written before nodes reported their battery lack its columns
+        let has_battery: bool = connection.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('readings') WHERE name = 'battery_mv'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_battery {
+            connection.execute_batch(
+                "ALTER TABLE readings ADD COLUMN battery_mv INTEGER;
+                ALTER TABLE readings ADD COLUMN battery_percent INTEGER;"
+            )?;
+        }
+        Ok(Self { connection })
+    }
+}
+
+impl Sink for SqliteSink {
+    fn write(&mut self, record: &Record) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO readings (received_at, node, seq, temperature_f, humidity, pm1_0, pm2_5, pm10, battery_mv,
+                 battery_percent, rssi, snr)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                record.received_at.map(|t| t as i64),
+                record.node,
+                record.seq,
+                record.temperature_f,
+                record.humidity,
+                record.pm1_0,
+                record.pm2_5,
+                record.pm10,
+                record.battery_mv,
+                record.battery_percent,
+                record.rssi,
+                record.snr,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> Record {
+        Record {
+            received_at: None,
+            node: 1,
+            seq: 2,
+            temperature_f: 68,
+            humidity: 48,
+            pm1_0: 8,
+            pm2_5: 12,
+            pm10: 15,
+            battery_mv: Some(3_850),
+            battery_percent: Some(52),
+            rssi: Some(-87),
+            snr: None,
+        }
+    }
+
+    #[test]
+    fn csv_has_header_and_empty_optionals() {
+        let mut out = Vec::new();
+        {
+            let mut sink = CsvSink::new(&mut out);
+            sink.write(&record()).unwrap();
+            sink.flush().unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "received_at,node,seq,temperature_f,humidity,pm1_0,pm2_5,pm10,battery_mv,battery_percent,rssi,snr\n,1,2,68,48,8,12,15,3850,52,-87,\n"
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let mut out = Vec::new();
+        JsonLinesSink::new(&mut out).write(&record()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"received_at\":null,\"node\":1,\"seq\":2,\"temperature_f\":68,\"humidity\":48,\"pm1_0\":8,\"pm2_5\":12,\"pm10\":15,\"battery_mv\":3850,\"battery_percent\":52,\"rssi\":-87,\"snr\":null}\n"
+        );
+    }
+
+    #[test]
+    fn sqlite_insert() {
+        let mut sink = SqliteSink::new(Connection::open_in_memory().unwrap()).unwrap();
+        sink.write(&record()).unwrap();
+        let (node, rssi, snr): (u16, Option<i16>, Option<i16>) = sink.connection
+            .query_row("SELECT node, rssi, snr FROM readings", [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap();
+        assert_eq!((node, rssi, snr), (1, Some(-87), None));
+    }
+
+    #[test]
+    fn sqlite_adds_battery_columns() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(
+            "CREATE TABLE readings (id INTEGER PRIMARY KEY, received_at INTEGER, node INTEGER NOT NULL,
+                seq INTEGER NOT NULL, temperature_f INTEGER NOT NULL, humidity INTEGER NOT NULL,
+                pm1_0 INTEGER NOT NULL, pm2_5 INTEGER NOT NULL, pm10 INTEGER NOT NULL, rssi INTEGER, snr INTEGER);"
+        ).unwrap();
+        let mut sink = SqliteSink::new(connection).unwrap();
+        sink.write(&record()).unwrap();
+        let battery: Option<u8> = sink.connection
+            .query_row("SELECT battery_percent FROM readings", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(battery, Some(52));
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::record::Decoded;
+
+/// Jumps larger than this are treated as the node rebooting (its sequence restarts at 0) rather
+/// than as tens of thousands of lost packets
+pub const MAX_SEQ_GAP: u16 = 1_000;
+
+/// Packets missed between `last` and `seq`, accounting for wrap-around, duplicates and reboots
+pub fn missed_packets(last: u16, seq: u16) -> u16 {
+    match seq.wrapping_sub(last) {
+        0 => 0,
+        step if step > MAX_SEQ_GAP => 0,
+        step => step - 1,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stat {
+    pub min: i64,
+    pub max: i64,
+    sum: i64,
+    pub count: u64,
+}
+
+impl Stat {
+    pub fn add(&mut self, value: i64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+}
+
+impl fmt::Display for Stat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mean() {
+            Some(mean) => write!(f, "{}/{:.1}/{}", self.min, mean, self.max),
+            None => write!(f, "-"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodeStats {
+    pub packets: u64,
+    pub missed: u64,
+    last_seq: Option<u16>,
+    pub temperature_f: Stat,
+    pub humidity: Stat,
+    pub pm2_5: Stat,
+    pub pm10: Stat,
+    pub rssi: Stat,
+    pub snr: Stat,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub nodes: BTreeMap<u16, NodeStats>,
+    pub crc_errors: u64,
+    pub decode_errors: u64,
+}
+
+impl Summary {
+    /// Every kind of packet counts towards the node's packets and signal; only readings towards its
+    /// metrics
+    pub fn record(&mut self, decoded: &Decoded) {
+        let node = self.nodes.entry(decoded.node()).or_default();
+        node.packets += 1;
+        if let Some(last) = node.last_seq {
+            node.missed += missed_packets(last, decoded.seq()) as u64;
+        }
+        node.last_seq = Some(decoded.seq());
+        if let Decoded::Reading(record) = decoded {
+            node.temperature_f.add(record.temperature_f.into());
+            node.humidity.add(record.humidity.into());
+            node.pm2_5.add(record.pm2_5.into());
+            node.pm10.add(record.pm10.into());
+        }
+        let (rssi, snr) = decoded.signal();
+        if let Some(rssi) = rssi {
+            node.rssi.add(rssi.into());
+        }
+        if let Some(snr) = snr {
+            node.snr.add(snr.into());
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>6} {:>8} {:>7} {:>16} {:>16} {:>16} {:>16} {:>16}",
+            "node", "packets", "missed", "temp F", "RH %", "PM 2.5", "PM 10", "RSSI")?;
+        for (id, node) in &self.nodes {
+            writeln!(f, "{:>6} {:>8} {:>7} {:>16} {:>16} {:>16} {:>16} {:>16}",
+                id, node.packets, node.missed,
+                node.temperature_f.to_string(), node.humidity.to_string(),
+                node.pm2_5.to_string(), node.pm10.to_string(), node.rssi.to_string())?;
+        }
+        write!(f, "crc errors: {}, undecodable: {}", self.crc_errors, self.decode_errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::{BusFaultsRecord, Record};
+
+    fn record(node: u16, seq: u16, temperature_f: u16) -> Decoded {
+        Decoded::Reading(Record {
+            received_at: None,
+            node,
+            seq,
+            temperature_f,
+            humidity: 40,
+            pm1_0: 4,
+            pm2_5: 5,
+            pm10: 6,
+            battery_mv: None,
+            battery_percent: None,
+            rssi: Some(-80),
+            snr: None,
+        })
+    }
+
+    #[test]
+    fn missed_packets_wraps() {
+        assert_eq!(missed_packets(1, 2), 0);
+        assert_eq!(missed_packets(1, 4), 2);
+        assert_eq!(missed_packets(65535, 1), 1);
+    }
+
+    #[test]
+    fn missed_packets_ignores_duplicates_and_reboots() {
+        assert_eq!(missed_packets(7, 7), 0);
+        assert_eq!(missed_packets(5_000, 0), 0);
+    }
+
+    #[test]
+    fn summary_per_node() {
+        let mut summary = Summary::default();
+        summary.record(&record(1, 10, 70));
+        summary.record(&record(1, 13, 66));
+        summary.record(&record(2, 0, 60));
+
+        let node = &summary.nodes[&1];
+        assert_eq!(node.packets, 2);
+        assert_eq!(node.missed, 2);
+        assert_eq!((node.temperature_f.min, node.temperature_f.max), (66, 70));
+        assert_eq!(node.temperature_f.mean(), Some(68.0));
+        assert_eq!(node.snr.mean(), None);
+        assert_eq!(summary.nodes[&2].packets, 1);
+    }
+
+    #[test]
+    fn bus_faults_fill_the_sequence() {
+        let mut summary = Summary::default();
+        summary.record(&record(1, 10, 70));
+        summary.record(&Decoded::BusFaults(BusFaultsRecord {
+            received_at: None,
+            node: 1,
+            seq: 11,
+            temp_humidity_timeouts: 0,
+            temp_humidity_errors: 0,
+            air_quality_timeouts: 3,
+            air_quality_errors: 0,
+            display_timeouts: 0,
+            display_errors: 0,
+            recoveries: 1,
+            rssi: Some(-80),
+            snr: None,
+        }));
+        summary.record(&record(1, 12, 66));
+
+        let node = &summary.nodes[&1];
+        assert_eq!((node.packets, node.missed), (3, 0));
+        // the reading the faults packet carried isn't counted twice
+        assert_eq!(node.temperature_f.count, 2);
+        assert_eq!(node.rssi.count, 3);
+    }
+}
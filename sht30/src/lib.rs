@@ -2,6 +2,8 @@
 
 use crc::{Crc, CRC_8_NRSC_5};
 use embedded_hal_async::i2c::{I2c, SevenBitAddress};
+use suspend::Suspend;
+pub use psychrometrics::Psychrometrics;
 // inspiration: https://gitlab.com/ghislainmary/embedded-sht3x
 // reasoning: i want a impl that is async-first and without default unit conversions
 
@@ -25,6 +27,10 @@ impl Sht30Reading {
     pub fn new(humidity: u16, temperature: u16) -> Self {
         Self { humidity, temperature_f: temperature }
     }
+
+    pub fn psychrometrics(&self) -> Psychrometrics {
+        Psychrometrics::new(self.temperature_f, self.humidity)
+    }
 }
 
 pub struct Sht30<I2C> {
@@ -82,6 +88,20 @@ impl<I2C: I2c> Sht30<I2C> {
     }
 }
 
+/// Single-shot mode already leaves the sensor idling at ~0.2uA between measurements, so there's
+/// nothing to power down
+impl<I2C: I2c> Suspend for Sht30<I2C> {
+    type Error = Sht30Error<I2C::Error>;
+
+    async fn suspend(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn resume(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::*;
@@ -0,0 +1,304 @@
+#![no_std]
+
+//! What the node's status LEDs show, as declarative patterns.
+//!
+//! A [`Pattern`] is a list of colors, each held for a time. The
+//! [`Indicator`] decides which one plays: an [`Event`] such as a transmission plays over whatever
+//! is showing, then the most important ongoing [`Conditions`] take over again. The firmware only
+//! pushes each [`Step`] to the NeoPixel and waits it out, so everything here can be tested on the
+//! host as a sequence of colors and timings.
+
+use air_quality::aqi::Category;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const OFF: Color = Color::new(0, 0, 0);
+    pub const WHITE: Color = Color::new(255, 255, 255);
+    pub const RED: Color = Color::new(255, 0, 0);
+    pub const GREEN: Color = Color::new(0, 255, 0);
+    pub const BLUE: Color = Color::new(0, 0, 255);
+    pub const AMBER: Color = Color::new(255, 120, 0);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Dimmed to `brightness` out of 255
+    pub fn scaled(self, brightness: u8) -> Color {
+        let scale = |channel: u8| (u16::from(channel) * u16::from(brightness) / 255) as u8;
+        Color::new(scale(self.r), scale(self.g), scale(self.b))
+    }
+}
+
+/// A color held for `ms`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    pub color: Color,
+    pub ms: u32,
+}
+
+const fn step(color: Color, ms: u32) -> Step {
+    Step { color, ms }
+}
+
+/// An [`Event`] plays its pattern once; a condition loops its own until something else takes over
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pattern {
+    pub steps: &'static [Step],
+}
+
+/// How long a steady color is held before the indicator is asked again; any update cuts it short
+pub const HOLD_MS: u32 = 60_000;
+
+pub const BOOT: Pattern = Pattern {
+    steps: &[step(Color::RED, 150), step(Color::GREEN, 150), step(Color::BLUE, 150), step(Color::OFF, 150)],
+};
+/// A blip for each new reading
+pub const SAMPLING: Pattern = Pattern { steps: &[step(Color::BLUE, 40), step(Color::OFF, 40)] };
+pub const TX_OK: Pattern = Pattern { steps: &[step(Color::GREEN, 120), step(Color::OFF, 40)] };
+pub const TX_FAILED: Pattern = Pattern {
+    steps: &[step(Color::RED, 120), step(Color::OFF, 120), step(Color::RED, 120), step(Color::OFF, 40)],
+};
+/// A double amber flash every 3s while a sensor's latest read failed
+pub const SENSOR_FAULT: Pattern = Pattern {
+    steps: &[step(Color::AMBER, 200), step(Color::OFF, 200), step(Color::AMBER, 200), step(Color::OFF, 2400)],
+};
+/// Rapid red flashes while an alert is raised
+pub const ALARM: Pattern = Pattern { steps: &[step(Color::RED, 250), step(Color::OFF, 250)] };
+/// A short red flash every 5s, brief to spare the battery it's warning about
+pub const LOW_BATTERY: Pattern = Pattern { steps: &[step(Color::RED, 80), step(Color::OFF, 4920)] };
+
+/// The EPA's color for each AQI category
+pub fn aqi_color(category: Category) -> Color {
+    match category {
+        Category::Good => Color::GREEN,
+        Category::Moderate => Color::new(255, 255, 0),
+        Category::UnhealthyForSensitiveGroups => Color::new(255, 126, 0),
+        Category::Unhealthy => Color::RED,
+        Category::VeryUnhealthy => Color::new(143, 63, 151),
+        Category::Hazardous => Color::new(126, 0, 35),
+    }
+}
+
+/// Something that just happened, shown once
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Boot,
+    Sampling,
+    /// Whether the uplink went out
+    Tx(bool),
+}
+
+impl Event {
+    fn pattern(&self) -> Pattern {
+        match self {
+            Event::Boot => BOOT,
+            Event::Sampling => SAMPLING,
+            Event::Tx(true) => TX_OK,
+            Event::Tx(false) => TX_FAILED,
+        }
+    }
+}
+
+/// What's shown between events, most important first
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Conditions {
+    /// An alert on a reading is raised
+    pub alarm: bool,
+    pub low_battery: bool,
+    /// A sensor's latest read failed
+    pub sensor_fault: bool,
+    /// The latest reading's category, held as a steady color when enabled and nothing else is on
+    pub aqi: Option<Category>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Background {
+    Pattern(Pattern),
+    Steady(Color),
+}
+
+/// Decides, one step at a time, what the NeoPixel shows
+#[derive(Debug)]
+pub struct Indicator {
+    brightness: u8,
+    aqi_colors: bool,
+    conditions: Conditions,
+    last_tx_ok: Option<bool>,
+    event: Option<Pattern>,
+    // the next step of the event, or of the background once the event is done
+    event_at: usize,
+    background_at: usize,
+}
+
+impl Indicator {
+    /// `brightness` out of 255 scales every color. With `aqi_colors`, the latest AQI category
+    /// shows as a steady color; otherwise the LED is off between events.
+    pub fn new(brightness: u8, aqi_colors: bool) -> Self {
+        Self {
+            brightness,
+            aqi_colors,
+            conditions: Conditions::default(),
+            last_tx_ok: None,
+            event: None,
+            event_at: 0,
+            background_at: 0,
+        }
+    }
+
+    /// Plays `event` straight away, cutting short any event still playing
+    pub fn event(&mut self, event: Event) {
+        if let Event::Tx(ok) = event {
+            self.last_tx_ok = Some(ok);
+        }
+        self.event = Some(event.pattern());
+        self.event_at = 0;
+    }
+
+    pub fn conditions(&self) -> Conditions {
+        self.conditions
+    }
+
+    /// Whether the background changed, in which case the step showing should be cut short. A
+    /// different background restarts from its first step.
+    pub fn set_conditions(&mut self, conditions: Conditions) -> bool {
+        let changed = self.background_for(conditions) != self.background_for(self.conditions);
+        if changed {
+            self.background_at = 0;
+        }
+        self.conditions = conditions;
+        changed
+    }
+
+    /// Whether something needs attention, for the red LED: an alert, a sensor fault or a failed
+    /// uplink
+    pub fn fault(&self) -> bool {
+        self.conditions.alarm || self.conditions.sensor_fault || self.last_tx_ok == Some(false)
+    }
+
+    fn background_for(&self, conditions: Conditions) -> Background {
+        if conditions.alarm {
+            Background::Pattern(ALARM)
+        } else if conditions.low_battery {
+            Background::Pattern(LOW_BATTERY)
+        } else if conditions.sensor_fault {
+            Background::Pattern(SENSOR_FAULT)
+        } else {
+            match conditions.aqi {
+                Some(category) if self.aqi_colors => Background::Steady(aqi_color(category)),
+                _ => Background::Steady(Color::OFF),
+            }
+        }
+    }
+
+    /// The step to show now; hold its color for its `ms`, or until the next update
+    pub fn step(&mut self) -> Step {
+        let next = match self.event.and_then(|pattern| pattern.steps.get(self.event_at)) {
+            Some(&next) => {
+                self.event_at += 1;
+                next
+            }
+            None => {
+                self.event = None;
+                match self.background_for(self.conditions) {
+                    Background::Pattern(pattern) => {
+                        let next = pattern.steps[self.background_at % pattern.steps.len()];
+                        self.background_at += 1;
+                        next
+                    }
+                    Background::Steady(color) => step(color, HOLD_MS),
+                }
+            }
+        };
+        Step { color: next.color.scaled(self.brightness), ..next }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(indicator: &mut Indicator, steps: usize) -> [Step; 8] {
+        let mut played = [step(Color::OFF, 0); 8];
+        for slot in played.iter_mut().take(steps) {
+            *slot = indicator.step();
+        }
+        played
+    }
+
+    #[test]
+    fn scales_colors() {
+        assert_eq!(Color::AMBER.scaled(255), Color::AMBER);
+        assert_eq!(Color::AMBER.scaled(128), Color::new(128, 60, 0));
+        assert_eq!(Color::WHITE.scaled(0), Color::OFF);
+    }
+
+    #[test]
+    fn plays_an_event_then_the_background() {
+        let mut indicator = Indicator::new(255, false);
+        indicator.set_conditions(Conditions { low_battery: true, ..Default::default() });
+        indicator.event(Event::Tx(false));
+        let played = play(&mut indicator, 7);
+        assert_eq!(played[..4], *TX_FAILED.steps);
+        assert_eq!(played[4..7], [LOW_BATTERY.steps[0], LOW_BATTERY.steps[1], LOW_BATTERY.steps[0]]);
+    }
+
+    #[test]
+    fn an_event_cuts_another_short() {
+        let mut indicator = Indicator::new(255, false);
+        indicator.event(Event::Boot);
+        assert_eq!(indicator.step(), BOOT.steps[0]);
+        indicator.event(Event::Tx(true));
+        assert_eq!(play(&mut indicator, 3)[..3], [TX_OK.steps[0], TX_OK.steps[1], step(Color::OFF, HOLD_MS)]);
+    }
+
+    #[test]
+    fn shows_the_most_important_condition() {
+        let mut indicator = Indicator::new(255, true);
+        assert_eq!(indicator.step(), step(Color::OFF, HOLD_MS));
+
+        indicator.set_conditions(Conditions { aqi: Some(Category::Moderate), ..Default::default() });
+        assert_eq!(indicator.step(), step(Color::new(255, 255, 0), HOLD_MS));
+
+        indicator.set_conditions(Conditions { sensor_fault: true, ..indicator.conditions() });
+        assert_eq!(play(&mut indicator, 2)[..2], SENSOR_FAULT.steps[..2]);
+        // the battery takes over from its first step
+        assert!(indicator.set_conditions(Conditions { low_battery: true, ..indicator.conditions() }));
+        assert_eq!(indicator.step(), LOW_BATTERY.steps[0]);
+        // a change it hides doesn't restart it
+        assert!(!indicator.set_conditions(Conditions { aqi: Some(Category::Good), ..indicator.conditions() }));
+        assert_eq!(indicator.step(), LOW_BATTERY.steps[1]);
+        // an alert outranks everything
+        assert!(indicator.set_conditions(Conditions { alarm: true, ..indicator.conditions() }));
+        assert_eq!(indicator.step(), ALARM.steps[0]);
+    }
+
+    #[test]
+    fn aqi_colors_are_optional_and_dimmed() {
+        let mut indicator = Indicator::new(51, false);
+        indicator.set_conditions(Conditions { aqi: Some(Category::Unhealthy), ..Default::default() });
+        assert_eq!(indicator.step(), step(Color::OFF, HOLD_MS));
+
+        let mut indicator = Indicator::new(51, true);
+        indicator.set_conditions(Conditions { aqi: Some(Category::Unhealthy), ..Default::default() });
+        assert_eq!(indicator.step(), step(Color::new(51, 0, 0), HOLD_MS));
+    }
+
+    #[test]
+    fn faults_light_the_red_led() {
+        let mut indicator = Indicator::new(255, false);
+        assert!(!indicator.fault());
+        indicator.event(Event::Tx(false));
+        assert!(indicator.fault());
+        indicator.event(Event::Tx(true));
+        assert!(!indicator.fault());
+        indicator.set_conditions(Conditions { sensor_fault: true, ..Default::default() });
+        assert!(indicator.fault());
+    }
+}
@@ -0,0 +1,210 @@
+#![no_std]
+
+//! Decides when the node's watchdog gets fed, and remembers why the node last reset.
+//!
+//! Each critical task checks in as it makes progress. The firmware's supervisor feeds the hardware
+//! watchdog only while every task has checked in within its deadline, so a task stuck on a hung
+//! bus resets the node instead of silencing it. Before a reset it leaves a [`ResetCause`] in the
+//! watchdog's scratch registers, which survive the reset, for the next boot to report.
+
+use core::fmt;
+
+/// The tasks the supervisor waits on
+///
+/// A stalled task's discriminant is left for the next boot, possibly running other firmware, so
+/// each keeps its number for good; a new task takes the next free one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Task {
+    TempHumidity = 0,
+    AirQuality = 1,
+    Radio = 2,
+    Display = 3,
+    Logger = 4,
+}
+
+impl Task {
+    pub const ALL: [Task; 5] = [Task::TempHumidity, Task::AirQuality, Task::Radio, Task::Display, Task::Logger];
+
+    fn from_code(code: u32) -> Option<Task> {
+        Task::ALL.into_iter().find(|&task| task as u32 == code)
+    }
+
+    // where the task sits in ALL, which needn't follow the discriminants
+    fn index(self) -> usize {
+        Task::ALL.iter().position(|&task| task == self).unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Task::TempHumidity => "temp/humidity",
+            Task::AirQuality => "air quality",
+            Task::Radio => "radio",
+            Task::Display => "display",
+            Task::Logger => "logger",
+        }
+    }
+}
+
+/// The longest each task may go without checking in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Deadlines {
+    pub temp_humidity_ms: u64,
+    pub air_quality_ms: u64,
+    pub radio_ms: u64,
+    pub display_ms: u64,
+    pub logger_ms: u64,
+}
+
+impl Deadlines {
+    fn of(&self, task: Task) -> u64 {
+        match task {
+            Task::TempHumidity => self.temp_humidity_ms,
+            Task::AirQuality => self.air_quality_ms,
+            Task::Radio => self.radio_ms,
+            Task::Display => self.display_ms,
+            Task::Logger => self.logger_ms,
+        }
+    }
+}
+
+/// When each task last checked in, in milliseconds since boot
+#[derive(Debug)]
+pub struct Liveness {
+    deadlines: Deadlines,
+    last_ms: [u64; Task::ALL.len()],
+}
+
+impl Liveness {
+    /// Every task starts out as having just checked in
+    pub fn new(now_ms: u64, deadlines: Deadlines) -> Self {
+        Self { deadlines, last_ms: [now_ms; Task::ALL.len()] }
+    }
+
+    pub fn check_in(&mut self, task: Task, now_ms: u64) {
+        self.last_ms[task.index()] = now_ms;
+    }
+
+    /// The first task that has gone longer than its deadline without checking in
+    pub fn overdue(&self, now_ms: u64) -> Option<Task> {
+        Task::ALL.into_iter().find(|&task| now_ms.saturating_sub(self.last_ms[task.index()]) > self.deadlines.of(task))
+    }
+}
+
+// marks the scratch registers as written by this firmware rather than left over from the bootrom
+const MAGIC: u32 = 0x5afe_b007;
+const REBOOT: u32 = 1;
+const BOOT_STALLED: u32 = 2;
+// a stalled task's code is this plus its discriminant
+const STALLED: u32 = 0x10;
+
+/// Why the node last reset
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResetCause {
+    /// Power-on, the reset button, or a reset nothing recorded
+    PowerOn,
+    /// Requested from the shell, or once a charging battery recovers from a shutdown
+    Reboot,
+    /// The supervisor stopped feeding the watchdog because startup didn't finish in time
+    BootStalled,
+    /// The supervisor stopped feeding the watchdog because the task missed its deadline
+    Stalled(Task),
+    /// The watchdog fired without the supervisor deciding to reset: a panic, or a task that never
+    /// yields and starves the supervisor
+    WatchdogTimeout,
+}
+
+impl ResetCause {
+    /// The two scratch words to leave for the next boot
+    pub fn to_scratch(self) -> [u32; 2] {
+        let code = match self {
+            ResetCause::Reboot => REBOOT,
+            ResetCause::BootStalled => BOOT_STALLED,
+            ResetCause::Stalled(task) => STALLED + task as u32,
+            // inferred at boot, so there's nothing to record
+            ResetCause::PowerOn | ResetCause::WatchdogTimeout => return [0, 0],
+        };
+        [MAGIC, code]
+    }
+
+    /// Works out the cause from the scratch words and whether the watchdog timer fired
+    pub fn from_scratch(scratch: [u32; 2], watchdog_timed_out: bool) -> ResetCause {
+        let recorded = match scratch {
+            [MAGIC, REBOOT] => Some(ResetCause::Reboot),
+            [MAGIC, BOOT_STALLED] => Some(ResetCause::BootStalled),
+            [MAGIC, code] => code.checked_sub(STALLED).and_then(Task::from_code).map(ResetCause::Stalled),
+            _ => None,
+        };
+        match recorded {
+            Some(cause) => cause,
+            None if watchdog_timed_out => ResetCause::WatchdogTimeout,
+            None => ResetCause::PowerOn,
+        }
+    }
+}
+
+impl fmt::Display for ResetCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResetCause::PowerOn => write!(f, "power on"),
+            ResetCause::Reboot => write!(f, "reboot requested"),
+            ResetCause::BootStalled => write!(f, "watchdog, startup stalled"),
+            ResetCause::Stalled(task) => write!(f, "watchdog, {} task stalled", task.name()),
+            ResetCause::WatchdogTimeout => write!(f, "watchdog, panic or hang"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEADLINES: Deadlines = Deadlines {
+        temp_humidity_ms: 30_000,
+        air_quality_ms: 30_000,
+        radio_ms: 30_000,
+        display_ms: 10_000,
+        logger_ms: 10_000,
+    };
+
+    #[test]
+    fn finds_the_overdue_task() {
+        let mut liveness = Liveness::new(1_000, DEADLINES);
+        assert_eq!(liveness.overdue(11_000), None);
+        assert_eq!(liveness.overdue(11_001), Some(Task::Display));
+
+        liveness.check_in(Task::Display, 11_000);
+        liveness.check_in(Task::Logger, 11_000);
+        assert_eq!(liveness.overdue(21_000), None);
+        liveness.check_in(Task::Display, 21_000);
+        liveness.check_in(Task::Logger, 21_000);
+        liveness.check_in(Task::TempHumidity, 21_000);
+        liveness.check_in(Task::AirQuality, 21_000);
+        assert_eq!(liveness.overdue(31_001), Some(Task::Radio));
+    }
+
+    #[test]
+    fn records_causes_across_resets() {
+        let causes = [ResetCause::Reboot, ResetCause::BootStalled, ResetCause::Stalled(Task::AirQuality), ResetCause::Stalled(Task::Logger)];
+        for cause in causes {
+            assert_eq!(ResetCause::from_scratch(cause.to_scratch(), false), cause);
+            // a recorded cause wins over the watchdog's own flag
+            assert_eq!(ResetCause::from_scratch(cause.to_scratch(), true), cause);
+        }
+    }
+
+    #[test]
+    fn infers_unrecorded_causes() {
+        assert_eq!(ResetCause::from_scratch([0, 0], false), ResetCause::PowerOn);
+        assert_eq!(ResetCause::from_scratch([0, 0], true), ResetCause::WatchdogTimeout);
+        assert_eq!(ResetCause::from_scratch([MAGIC, 0x99], true), ResetCause::WatchdogTimeout);
+        assert_eq!(ResetCause::from_scratch([0x1234, REBOOT], false), ResetCause::PowerOn);
+    }
+
+    #[test]
+    fn stall_codes_are_fixed() {
+        // firmware on either side of an update has to agree on these
+        assert_eq!(ResetCause::Stalled(Task::TempHumidity).to_scratch(), [MAGIC, 0x10]);
+        assert_eq!(ResetCause::Stalled(Task::Logger).to_scratch(), [MAGIC, 0x14]);
+        assert_eq!(ResetCause::from_scratch([MAGIC, 0x12], true), ResetCause::Stalled(Task::Radio));
+    }
+}
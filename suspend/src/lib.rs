@@ -0,0 +1,43 @@
+#![no_std]
+
+//! A common way for drivers to power their device down between uses, so the firmware can manage
+//! the node's power state in one place.
+//!
+//! Each driver implements [`Suspend`] with whatever its device offers: the SX1276 sleeps, the
+//! PMSA003I stops its fan and the OLED turns its panel off. The firmware decides when, and
+//! [`worth_suspending`] keeps it from cycling a device that would spend more time coming back than
+//! it saves.
+
+use core::future::Future;
+
+pub trait Suspend {
+    type Error;
+
+    /// How long the device needs after `resume` before it gives good results
+    const WARM_UP_MS: u32 = 0;
+
+    /// Puts the device in the lowest-power state that `resume` can bring it back from
+    fn suspend(&mut self) -> impl Future<Output = Result<(), Self::Error>>;
+
+    fn resume(&mut self) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+/// Whether suspending a device between uses `interval_ms` apart saves anything. It has to stay
+/// off for at least as long as it spends warming up again.
+pub fn worth_suspending(interval_ms: u64, warm_up_ms: u32) -> bool {
+    interval_ms >= 2 * u64::from(warm_up_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suspends_only_for_long_enough() {
+        assert!(worth_suspending(3_000, 0));
+        // the PMSA003I's fan takes 30s to settle
+        assert!(!worth_suspending(30_000, 30_000));
+        assert!(!worth_suspending(59_999, 30_000));
+        assert!(worth_suspending(60_000, 30_000));
+    }
+}
@@ -0,0 +1,588 @@
+#![no_std]
+
+//! Over-the-air packet format shared by the nodes, the gateway and the host tools.
+//!
+//! Every packet is a fixed 5 byte [`Header`] followed by a body whose layout depends on
+//! `Header::kind`. All fields are little-endian. Nodes on the firmware from before the header send
+//! a bare 8 byte reading instead, which decodes as [`Body::LegacyEnvReading`].
+
+use core::fmt::Write;
+use heapless::{String, Vec};
+use packed_struct::prelude::*;
+
+pub const HEADER_LEN: usize = 5;
+pub const ENV_READING_LEN: usize = 10;
+pub const BATTERY_LEN: usize = 3;
+pub const ALERT_LEN: usize = 4;
+pub const DERIVED_LEN: usize = 6;
+pub const BUS_FAULTS_LEN: usize = 14;
+pub const MAX_PACKET_LEN: usize = 32;
+/// A packet from before the header: PM2.5, PM10, humidity and temperature, without PM1.0
+pub const LEGACY_ENV_READING_LEN: usize = 8;
+/// Legacy packets carry no node ID or sequence number, so they decode as this node, always with
+/// sequence 0
+pub const LEGACY_NODE_ID: u16 = 0;
+
+pub const KIND_ENV_READING: u8 = 0x01;
+/// An [`EnvReading`] followed by the sender's [`Battery`]
+pub const KIND_ENV_READING_BATTERY: u8 = 0x02;
+/// An [`EnvReading`] sent as soon as it raised or cleared an [`Alert`], rather than on the uplink
+/// interval
+pub const KIND_ALERT: u8 = 0x03;
+/// A [`KIND_ENV_READING`] or [`KIND_ENV_READING_BATTERY`] body, told apart by length, followed by
+/// [`Derived`] values
+pub const KIND_ENV_READING_DERIVED: u8 = 0x04;
+/// An [`EnvReading`] followed by the node's [`BusFaults`], sent after an uplink when they've
+/// changed
+pub const KIND_BUS_FAULTS: u8 = 0x05;
+
+#[derive(PackedStruct, Clone, Debug, PartialEq)]
+#[packed_struct(endian="lsb")]
+pub struct Header {
+    #[packed_field()]
+    pub kind: u8,
+    #[packed_field()]
+    pub node_id: u16,
+    /// Incremented for every packet a node sends, wrapping; gaps mean lost packets
+    #[packed_field()]
+    pub seq: u16,
+}
+
+#[derive(PackedStruct, Clone, Debug, PartialEq)]
+#[packed_struct(endian="lsb")]
+pub struct EnvReading {
+    #[packed_field()]
+    pub aq_pm2_5: u16,
+    #[packed_field()]
+    pub aq_pm10: u16,
+    #[packed_field()]
+    pub humidity: u16,
+    #[packed_field()]
+    pub temperature: u16,
+    #[packed_field()]
+    pub aq_pm1_0: u16,
+}
+
+// the wire form of a legacy packet
+#[derive(PackedStruct, Clone, Debug, PartialEq)]
+#[packed_struct(endian="lsb")]
+struct LegacyEnvReading {
+    #[packed_field()]
+    aq_pm2_5: u16,
+    #[packed_field()]
+    aq_pm10: u16,
+    #[packed_field()]
+    humidity: u16,
+    #[packed_field()]
+    temperature: u16,
+}
+
+impl From<LegacyEnvReading> for EnvReading {
+    fn from(legacy: LegacyEnvReading) -> Self {
+        EnvReading {
+            aq_pm2_5: legacy.aq_pm2_5,
+            aq_pm10: legacy.aq_pm10,
+            humidity: legacy.humidity,
+            temperature: legacy.temperature,
+            aq_pm1_0: 0,
+        }
+    }
+}
+
+impl From<&EnvReading> for LegacyEnvReading {
+    fn from(reading: &EnvReading) -> Self {
+        LegacyEnvReading {
+            aq_pm2_5: reading.aq_pm2_5,
+            aq_pm10: reading.aq_pm10,
+            humidity: reading.humidity,
+            temperature: reading.temperature,
+        }
+    }
+}
+
+#[derive(PackedStruct, Clone, Debug, PartialEq)]
+#[packed_struct(endian="lsb")]
+pub struct Battery {
+    #[packed_field()]
+    pub millivolts: u16,
+    /// Approximate state of charge
+    #[packed_field()]
+    pub percent: u8,
+}
+
+/// Computed by the node from its reading's temperature and humidity, for receivers that would
+/// rather not
+#[derive(PackedStruct, Clone, Debug, PartialEq)]
+#[packed_struct(endian="lsb")]
+pub struct Derived {
+    #[packed_field()]
+    pub dew_point_f: i16,
+    #[packed_field()]
+    pub heat_index_f: i16,
+    /// Tenths of a g/m³
+    #[packed_field()]
+    pub absolute_humidity_dg_m3: u16,
+}
+
+/// Failed transactions on the node's shared I2C bus since boot, per device, and how many times the
+/// bus had to be recovered
+#[derive(PackedStruct, Clone, Debug, Default, PartialEq)]
+#[packed_struct(endian="lsb")]
+pub struct BusFaults {
+    /// Transactions that didn't finish within the device's timeout
+    #[packed_field()]
+    pub temp_humidity_timeouts: u16,
+    /// Transactions that finished with an error, e.g. a NACK
+    #[packed_field()]
+    pub temp_humidity_errors: u16,
+    #[packed_field()]
+    pub air_quality_timeouts: u16,
+    #[packed_field()]
+    pub air_quality_errors: u16,
+    #[packed_field()]
+    pub display_timeouts: u16,
+    #[packed_field()]
+    pub display_errors: u16,
+    #[packed_field()]
+    pub recoveries: u16,
+}
+
+/// A reading a node can raise an alert on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlertMetric {
+    Pm2_5,
+    Temperature,
+    Humidity,
+}
+
+impl AlertMetric {
+    pub const ALL: [AlertMetric; 3] = [AlertMetric::Pm2_5, AlertMetric::Temperature, AlertMetric::Humidity];
+
+    pub fn value(&self, reading: &EnvReading) -> u16 {
+        match self {
+            AlertMetric::Pm2_5 => reading.aq_pm2_5,
+            AlertMetric::Temperature => reading.temperature,
+            AlertMetric::Humidity => reading.humidity,
+        }
+    }
+
+    /// Matches the reading's field in the gateway's JSON
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlertMetric::Pm2_5 => "pm2_5",
+            AlertMetric::Temperature => "temperature_f",
+            AlertMetric::Humidity => "humidity",
+        }
+    }
+
+    fn code(&self) -> u8 {
+        *self as u8
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|metric| metric.code() == code)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlertState {
+    /// Below the low limit
+    Low,
+    /// Above the high limit
+    High,
+    /// Back inside the limit, past its hysteresis
+    Cleared,
+}
+
+impl AlertState {
+    const ALL: [AlertState; 3] = [AlertState::Low, AlertState::High, AlertState::Cleared];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlertState::Low => "low",
+            AlertState::High => "high",
+            AlertState::Cleared => "cleared",
+        }
+    }
+
+    fn code(&self) -> u8 {
+        *self as u8
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|state| state.code() == code)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Alert {
+    pub metric: AlertMetric,
+    pub state: AlertState,
+    /// The limit that was crossed, or that the reading came back inside
+    pub limit: u16,
+}
+
+// the wire form of an Alert
+#[derive(PackedStruct, Clone, Debug, PartialEq)]
+#[packed_struct(endian="lsb")]
+struct AlertFields {
+    #[packed_field()]
+    metric: u8,
+    #[packed_field()]
+    state: u8,
+    #[packed_field()]
+    limit: u16,
+}
+
+impl From<&Alert> for AlertFields {
+    fn from(alert: &Alert) -> Self {
+        Self { metric: alert.metric.code(), state: alert.state.code(), limit: alert.limit }
+    }
+}
+
+impl TryFrom<AlertFields> for Alert {
+    type Error = PacketError;
+
+    fn try_from(fields: AlertFields) -> Result<Self, PacketError> {
+        match (AlertMetric::from_code(fields.metric), AlertState::from_code(fields.state)) {
+            (Some(metric), Some(state)) => Ok(Alert { metric, state, limit: fields.limit }),
+            _ => Err(PacketError::InvalidAlert(fields.metric, fields.state)),
+        }
+    }
+}
+
+impl From<EnvReading> for String<64> {
+
+    fn from(reading: EnvReading) -> Self {
+        let mut msg: String<64> = String::new();
+        core::write!(
+            &mut msg,
+            "Temp   = {}F\nRH     = {}%\nPM 2.5 = {}\nPM 10  = {}",
+            reading.temperature, reading.humidity, reading.aq_pm2_5, reading.aq_pm10
+        ).unwrap();
+        msg
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Body {
+    EnvReading(EnvReading),
+    /// From a node that can measure its battery
+    EnvReadingBattery(EnvReading, Battery),
+    /// The reading that raised or cleared the alert
+    Alert(EnvReading, Alert),
+    /// From a node set to send its derived values too, with its battery if it measures one
+    EnvReadingDerived(EnvReading, Option<Battery>, Derived),
+    /// The reading just sent, for context
+    BusFaults(EnvReading, BusFaults),
+    /// From a node on the firmware before the header, which doesn't read PM1.0, so `aq_pm1_0` is
+    /// left at 0
+    LegacyEnvReading(EnvReading),
+}
+
+impl Body {
+    pub fn reading(&self) -> &EnvReading {
+        match self {
+            Body::EnvReading(reading)
+            | Body::EnvReadingBattery(reading, _)
+            | Body::Alert(reading, _)
+            | Body::EnvReadingDerived(reading, ..)
+            | Body::BusFaults(reading, _)
+            | Body::LegacyEnvReading(reading) => reading,
+        }
+    }
+
+    pub fn battery(&self) -> Option<&Battery> {
+        match self {
+            Body::EnvReadingBattery(_, battery) => Some(battery),
+            Body::EnvReadingDerived(_, battery, _) => battery.as_ref(),
+            Body::EnvReading(_) | Body::Alert(..) | Body::BusFaults(..) | Body::LegacyEnvReading(_) => None,
+        }
+    }
+
+    pub fn alert(&self) -> Option<&Alert> {
+        match self {
+            Body::Alert(_, alert) => Some(alert),
+            Body::EnvReading(_)
+            | Body::EnvReadingBattery(..)
+            | Body::EnvReadingDerived(..)
+            | Body::BusFaults(..)
+            | Body::LegacyEnvReading(_) => None,
+        }
+    }
+
+    pub fn derived(&self) -> Option<&Derived> {
+        match self {
+            Body::EnvReadingDerived(_, _, derived) => Some(derived),
+            Body::EnvReading(_)
+            | Body::EnvReadingBattery(..)
+            | Body::Alert(..)
+            | Body::BusFaults(..)
+            | Body::LegacyEnvReading(_) => None,
+        }
+    }
+
+    pub fn bus_faults(&self) -> Option<&BusFaults> {
+        match self {
+            Body::BusFaults(_, faults) => Some(faults),
+            Body::EnvReading(_)
+            | Body::EnvReadingBattery(..)
+            | Body::Alert(..)
+            | Body::EnvReadingDerived(..)
+            | Body::LegacyEnvReading(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PacketError {
+    InvalidLength(usize),
+    UnknownKind(u8),
+    /// An alert's metric and state codes, one of which isn't known
+    InvalidAlert(u8, u8),
+    Packing(PackingError),
+}
+
+impl From<PackingError> for PacketError {
+    fn from(e: PackingError) -> Self {
+        PacketError::Packing(e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Packet {
+    pub node_id: u16,
+    pub seq: u16,
+    pub body: Body,
+}
+
+impl Packet {
+    pub fn new(node_id: u16, seq: u16, body: Body) -> Self {
+        Self { node_id, seq, body }
+    }
+
+    /// `None` for a legacy packet, which has no header
+    fn kind(&self) -> Option<u8> {
+        match self.body {
+            Body::EnvReading(_) => Some(KIND_ENV_READING),
+            Body::EnvReadingBattery(..) => Some(KIND_ENV_READING_BATTERY),
+            Body::Alert(..) => Some(KIND_ALERT),
+            Body::EnvReadingDerived(..) => Some(KIND_ENV_READING_DERIVED),
+            Body::BusFaults(..) => Some(KIND_BUS_FAULTS),
+            Body::LegacyEnvReading(_) => None,
+        }
+    }
+
+    /// A legacy packet is encoded as it was sent, without the node ID and sequence number
+    pub fn encode(&self) -> Result<Vec<u8, MAX_PACKET_LEN>, PacketError> {
+        let mut bytes: Vec<u8, MAX_PACKET_LEN> = Vec::new();
+        let Some(kind) = self.kind() else {
+            bytes.extend_from_slice(&LegacyEnvReading::from(self.body.reading()).pack()?).unwrap();
+            return Ok(bytes);
+        };
+        let header = Header { kind, node_id: self.node_id, seq: self.seq };
+        // lengths are fixed and well under MAX_PACKET_LEN, so extending can't fail
+        bytes.extend_from_slice(&header.pack()?).unwrap();
+        match &self.body {
+            Body::EnvReading(reading) => bytes.extend_from_slice(&reading.pack()?).unwrap(),
+            Body::EnvReadingBattery(reading, battery) => {
+                bytes.extend_from_slice(&reading.pack()?).unwrap();
+                bytes.extend_from_slice(&battery.pack()?).unwrap();
+            }
+            Body::Alert(reading, alert) => {
+                bytes.extend_from_slice(&reading.pack()?).unwrap();
+                bytes.extend_from_slice(&AlertFields::from(alert).pack()?).unwrap();
+            }
+            Body::EnvReadingDerived(reading, battery, derived) => {
+                bytes.extend_from_slice(&reading.pack()?).unwrap();
+                if let Some(battery) = battery {
+                    bytes.extend_from_slice(&battery.pack()?).unwrap();
+                }
+                bytes.extend_from_slice(&derived.pack()?).unwrap();
+            }
+            Body::BusFaults(reading, faults) => {
+                bytes.extend_from_slice(&reading.pack()?).unwrap();
+                bytes.extend_from_slice(&faults.pack()?).unwrap();
+            }
+            Body::LegacyEnvReading(_) => {}
+        }
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, PacketError> {
+        // shorter than any packet with a header
+        if bytes.len() == LEGACY_ENV_READING_LEN {
+            let reading = LegacyEnvReading::unpack_from_slice(bytes)?.into();
+            return Ok(Self::new(LEGACY_NODE_ID, 0, Body::LegacyEnvReading(reading)));
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(PacketError::InvalidLength(bytes.len()));
+        }
+        let (header, body) = bytes.split_at(HEADER_LEN);
+        let header = Header::unpack_from_slice(header)?;
+        let body = match header.kind {
+            KIND_ENV_READING if body.len() == ENV_READING_LEN => {
+                Body::EnvReading(EnvReading::unpack_from_slice(body)?)
+            }
+            KIND_ENV_READING_BATTERY if body.len() == ENV_READING_LEN + BATTERY_LEN => {
+                let (reading, battery) = body.split_at(ENV_READING_LEN);
+                Body::EnvReadingBattery(EnvReading::unpack_from_slice(reading)?, Battery::unpack_from_slice(battery)?)
+            }
+            KIND_ALERT if body.len() == ENV_READING_LEN + ALERT_LEN => {
+                let (reading, alert) = body.split_at(ENV_READING_LEN);
+                Body::Alert(EnvReading::unpack_from_slice(reading)?, AlertFields::unpack_from_slice(alert)?.try_into()?)
+            }
+            KIND_ENV_READING_DERIVED
+                if body.len() == ENV_READING_LEN + DERIVED_LEN
+                    || body.len() == ENV_READING_LEN + BATTERY_LEN + DERIVED_LEN =>
+            {
+                let (reading, rest) = body.split_at(ENV_READING_LEN);
+                let (battery, derived) = rest.split_at(rest.len() - DERIVED_LEN);
+                let battery = if battery.is_empty() { None } else { Some(Battery::unpack_from_slice(battery)?) };
+                Body::EnvReadingDerived(
+                    EnvReading::unpack_from_slice(reading)?,
+                    battery,
+                    Derived::unpack_from_slice(derived)?,
+                )
+            }
+            KIND_BUS_FAULTS if body.len() == ENV_READING_LEN + BUS_FAULTS_LEN => {
+                let (reading, faults) = body.split_at(ENV_READING_LEN);
+                Body::BusFaults(EnvReading::unpack_from_slice(reading)?, BusFaults::unpack_from_slice(faults)?)
+            }
+            KIND_ENV_READING | KIND_ENV_READING_BATTERY | KIND_ALERT | KIND_ENV_READING_DERIVED | KIND_BUS_FAULTS => {
+                return Err(PacketError::InvalidLength(bytes.len()))
+            }
+            kind => return Err(PacketError::UnknownKind(kind)),
+        };
+        Ok(Self::new(header.node_id, header.seq, body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn reading() -> EnvReading {
+        EnvReading { aq_pm2_5: 12, aq_pm10: 15, humidity: 48, temperature: 68, aq_pm1_0: 8 }
+    }
+
+    #[test]
+    fn encode_env_reading() {
+        let packet = Packet::new(0x0102, 0x0304, Body::EnvReading(reading()));
+        let bytes = packet.encode().unwrap();
+        assert_eq!(
+            &bytes[..],
+            &[KIND_ENV_READING, 0x02, 0x01, 0x04, 0x03, 12, 0, 15, 0, 48, 0, 68, 0, 8, 0]
+        );
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        let packet = Packet::new(7, 65535, Body::EnvReading(reading()));
+        let bytes = packet.encode().unwrap();
+        assert_eq!(Packet::decode(&bytes).unwrap(), packet);
+    }
+
+    #[test]
+    fn battery_round_trip() {
+        let battery = Battery { millivolts: 3_850, percent: 52 };
+        let packet = Packet::new(2, 1, Body::EnvReadingBattery(reading(), battery.clone()));
+        let bytes = packet.encode().unwrap();
+        assert_eq!(bytes[0], KIND_ENV_READING_BATTERY);
+        assert_eq!(&bytes[HEADER_LEN + ENV_READING_LEN..], &[0x0a, 0x0f, 52]);
+        let decoded = Packet::decode(&bytes).unwrap();
+        assert_eq!(decoded.body.reading(), &reading());
+        assert_eq!(decoded.body.battery(), Some(&battery));
+    }
+
+    #[test]
+    fn derived_round_trip() {
+        let battery = Battery { millivolts: 3_850, percent: 52 };
+        let derived = Derived { dew_point_f: -4, heat_index_f: 67, absolute_humidity_dg_m3: 86 };
+        let packet = Packet::new(2, 1, Body::EnvReadingDerived(reading(), Some(battery.clone()), derived.clone()));
+        let bytes = packet.encode().unwrap();
+        assert_eq!(bytes[0], KIND_ENV_READING_DERIVED);
+        assert_eq!(&bytes[HEADER_LEN + ENV_READING_LEN + BATTERY_LEN..], &[0xfc, 0xff, 67, 0, 86, 0]);
+        let decoded = Packet::decode(&bytes).unwrap();
+        assert_eq!(decoded.body.battery(), Some(&battery));
+        assert_eq!(decoded.body.derived(), Some(&derived));
+    }
+
+    #[test]
+    fn derived_without_battery() {
+        let derived = Derived { dew_point_f: 49, heat_index_f: 67, absolute_humidity_dg_m3: 86 };
+        let packet = Packet::new(2, 1, Body::EnvReadingDerived(reading(), None, derived.clone()));
+        let bytes = packet.encode().unwrap();
+        assert_eq!(bytes.len(), HEADER_LEN + ENV_READING_LEN + DERIVED_LEN);
+        let decoded = Packet::decode(&bytes).unwrap();
+        assert_eq!(decoded, packet);
+        assert_eq!(decoded.body.battery(), None);
+        assert_eq!(decoded.body.derived(), Some(&derived));
+    }
+
+    #[test]
+    fn alert_round_trip() {
+        let alert = Alert { metric: AlertMetric::Pm2_5, state: AlertState::High, limit: 35 };
+        let packet = Packet::new(4, 20, Body::Alert(reading(), alert));
+        let bytes = packet.encode().unwrap();
+        assert_eq!(bytes[0], KIND_ALERT);
+        assert_eq!(&bytes[HEADER_LEN + ENV_READING_LEN..], &[0, 1, 35, 0]);
+        let decoded = Packet::decode(&bytes).unwrap();
+        assert_eq!(decoded.body.reading(), &reading());
+        assert_eq!(decoded.body.alert(), Some(&alert));
+    }
+
+    #[test]
+    fn bus_faults_round_trip() {
+        let faults = BusFaults { temp_humidity_timeouts: 3, display_errors: 2, recoveries: 1, ..Default::default() };
+        let packet = Packet::new(5, 30, Body::BusFaults(reading(), faults.clone()));
+        let bytes = packet.encode().unwrap();
+        assert_eq!(bytes[0], KIND_BUS_FAULTS);
+        assert!(bytes.len() <= MAX_PACKET_LEN);
+        assert_eq!(&bytes[HEADER_LEN + ENV_READING_LEN..], &[3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0]);
+        let decoded = Packet::decode(&bytes).unwrap();
+        assert_eq!(decoded.body.reading(), &reading());
+        assert_eq!(decoded.body.bus_faults(), Some(&faults));
+    }
+
+    #[test]
+    fn decode_invalid_alert() {
+        let alert = Alert { metric: AlertMetric::Humidity, state: AlertState::Cleared, limit: 70 };
+        let mut bytes = Packet::new(4, 21, Body::Alert(reading(), alert)).encode().unwrap();
+        bytes[HEADER_LEN + ENV_READING_LEN] = 9;
+        assert_eq!(Packet::decode(&bytes), Err(PacketError::InvalidAlert(9, 2)));
+    }
+
+    #[test]
+    fn decode_legacy_env_reading() {
+        // as the firmware before the header packed its reading
+        let bytes = [12, 0, 15, 0, 48, 0, 68, 0];
+        let packet = Packet::decode(&bytes).unwrap();
+        assert_eq!((packet.node_id, packet.seq), (LEGACY_NODE_ID, 0));
+        assert_eq!(packet.body, Body::LegacyEnvReading(EnvReading { aq_pm1_0: 0, ..reading() }));
+        assert_eq!(&packet.encode().unwrap()[..], &bytes);
+    }
+
+    #[test]
+    fn decode_too_short() {
+        assert_eq!(Packet::decode(&[KIND_ENV_READING, 0, 0]), Err(PacketError::InvalidLength(3)));
+    }
+
+    #[test]
+    fn decode_truncated_body() {
+        let bytes = Packet::new(1, 1, Body::EnvReading(reading())).encode().unwrap();
+        let len = bytes.len() - 1;
+        assert_eq!(Packet::decode(&bytes[..len]), Err(PacketError::InvalidLength(len)));
+    }
+
+    #[test]
+    fn decode_unknown_kind() {
+        assert_eq!(Packet::decode(&[0xee, 0, 0, 0, 0]), Err(PacketError::UnknownKind(0xee)));
+    }
+
+    #[test]
+    fn env_reading_display_text() {
+        let msg: String<64> = reading().into();
+        assert_eq!(msg.as_str(), "Temp   = 68F\nRH     = 48%\nPM 2.5 = 12\nPM 10  = 15");
+    }
+}